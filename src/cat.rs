@@ -16,14 +16,17 @@
  */
 
 use anyhow::{Context, Result};
-use std::io::{self, copy};
+use std::io::{self, Read, Write, copy};
 use std::path::Path;
+use tar::Entries;
 
-use crate::index::ArchivumIndex;
+use crate::compress::CompressionAlgo;
 use crate::scan::EntryType;
+use crate::store::Store;
+use crate::tar_writer::is_manifest;
 
-pub fn cat(index_path: &Path, file: &Path) -> Result<()> {
-    let idx = ArchivumIndex::read(index_path)?;
+pub fn cat(store: &Store, index_path: &Path, file: &Path) -> Result<()> {
+    let idx = store.read_index(index_path)?;
     let index_dir = index_path.parent().unwrap_or(Path::new("."));
 
     let entry = idx
@@ -48,18 +51,41 @@ pub fn cat(index_path: &Path, file: &Path) -> Result<()> {
         (file, entry)
     };
 
-    let part_path = target_entry.part_path(index_dir, &idx.header);
-    let reader = idx.header.compression.wrap_reader(&part_path)?;
-    let mut archive = tar::Archive::new(reader);
-
     let mut stdout = io::stdout();
-    for item in archive.entries()? {
-        let mut item = item?;
-        if item.path()? == target_path {
-            copy(&mut item, &mut stdout)?;
-            return Ok(());
+
+    // a stream that spans parts has one member in each, in order
+    for piece in target_entry.members() {
+        let part_path = piece.part_path(index_dir, &idx.header);
+
+        // an uncompressed part can be walked header to header with ranged
+        // reads, skipping the data of every other member
+        let found = if idx.header.compression == CompressionAlgo::None {
+            let mut archive = tar::Archive::new(store.open_seekable(&part_path)?);
+            copy_member(archive.entries_with_seek()?, target_path, &mut stdout)?
+        } else {
+            let reader = store.open_part(&part_path, &idx.header.compression)?;
+            let mut archive = tar::Archive::new(reader);
+            copy_member(archive.entries()?, target_path, &mut stdout)?
+        };
+
+        if !found {
+            anyhow::bail!("File not found inside tar: {}", file.display());
         }
     }
+    Ok(())
+}
 
-    anyhow::bail!("File not found inside tar: {}", file.display());
+fn copy_member<R: Read>(
+    entries: Entries<'_, R>,
+    target: &Path,
+    out: &mut impl Write,
+) -> Result<bool> {
+    for item in entries {
+        let mut item = item?;
+        if !is_manifest(&mut item)? && item.path()? == target {
+            copy(&mut item, out)?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
@@ -15,11 +15,13 @@
  * limitations under the License.
  */
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use colored::Colorize;
 use hex::encode;
 use indicatif::{ProgressBar, ProgressStyle};
+use md5::Md5;
 use rayon::prelude::*;
+use sha1::Sha1;
 use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::{BufReader, Read};
@@ -28,8 +30,13 @@ use std::sync::{Arc, Mutex};
 
 use crate::index::ArchivumIndex;
 use crate::scan::EntryType;
+use crate::source::Sources;
 
-pub fn compute_checksums(root: &Path, idx: &mut ArchivumIndex, num_threads: usize) -> Result<()> {
+pub fn compute_checksums(
+    sources: &Sources,
+    idx: &mut ArchivumIndex,
+    num_threads: usize,
+) -> Result<()> {
     let total: u64 = idx
         .entries
         .iter()
@@ -52,7 +59,7 @@ pub fn compute_checksums(root: &Path, idx: &mut ArchivumIndex, num_threads: usiz
         .iter()
         .enumerate()
         .filter(|(_, e)| e.entry_type == EntryType::File && e.dedup_of.is_none())
-        .map(|(i, e)| (i, root.join(&e.path), e.size))
+        .map(|(i, e)| (i, sources.resolve(&e.path), e.size))
         .collect();
 
     let pool = rayon::ThreadPoolBuilder::new()
@@ -60,19 +67,23 @@ pub fn compute_checksums(root: &Path, idx: &mut ArchivumIndex, num_threads: usiz
         .build()
         .map_err(|e| anyhow::anyhow!("Failed to build thread pool: {}", e))?;
 
+    // every digest the archive records, from one read of each file
+    let mut algos = vec![HashAlgo::Sha256];
+    algos.extend(idx.header.hash_algos());
+
     let pb_arc = Arc::new(pb);
-    let results: Arc<Mutex<Vec<(usize, String)>>> = Arc::new(Mutex::new(Vec::new()));
+    let results: Arc<Mutex<Vec<(usize, Digests)>>> = Arc::new(Mutex::new(Vec::new()));
 
     pool.install(|| {
         work.par_iter()
-            .map(|(idx_pos, path, _size)| -> Result<(usize, String)> {
-                let hash = hash_file(path)?;
-                Ok((*idx_pos, hash))
+            .map(|(idx_pos, path, _size)| -> Result<(usize, Digests)> {
+                let digests = digest_file(path, &algos)?;
+                Ok((*idx_pos, digests))
             })
             .for_each(|result| match result {
-                Ok((pos, hash)) => {
+                Ok((pos, digests)) => {
                     let size = idx.entries[pos].size;
-                    results.lock().unwrap().push((pos, hash));
+                    results.lock().unwrap().push((pos, digests));
                     pb_arc.inc(size);
                 }
                 Err(e) => {
@@ -83,9 +94,9 @@ pub fn compute_checksums(root: &Path, idx: &mut ArchivumIndex, num_threads: usiz
 
     pb_arc.finish_with_message("checksums done".green().to_string());
 
-    let res = results.lock().unwrap();
-    for (i, hash) in res.iter() {
-        idx.entries[*i].sha256 = Some(hash.clone());
+    let res = std::mem::take(&mut *results.lock().unwrap());
+    for (i, digests) in res {
+        idx.entries[i].set_digests(digests);
     }
 
     // mark duplicate files by sha256
@@ -108,13 +119,142 @@ pub fn compute_checksums(root: &Path, idx: &mut ArchivumIndex, num_threads: usiz
     Ok(())
 }
 
-// 128 KiB chunks, no temp files
-pub fn hash_file(path: &Path) -> Result<String> {
-    let mut hasher = Sha256::new();
+/// A per-file digest algorithm. SHA-256 is always computed (dedup,
+/// verification and the Merkle root rest on it); the others are recorded
+/// in an entry's `hashes` when the archive asks for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HashAlgo {
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgo {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "md5" => Ok(Self::Md5),
+            "sha1" | "sha-1" => Ok(Self::Sha1),
+            "sha256" | "sha-256" => Ok(Self::Sha256),
+            "blake3" | "b3" => Ok(Self::Blake3),
+            other => bail!(
+                "Unknown hash algorithm: '{}'. Use: md5, sha1, sha256, blake3",
+                other
+            ),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    /// As reports print it.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Sha1 => "SHA-1",
+            Self::Sha256 => "SHA-256",
+            Self::Blake3 => "BLAKE3",
+        }
+    }
+
+    /// The algorithms named in `names`, in canonical order and without
+    /// duplicates.
+    pub fn list<S: AsRef<str>>(names: &[S]) -> Result<Vec<Self>> {
+        let mut algos = names
+            .iter()
+            .map(|n| Self::parse(n.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        algos.sort();
+        algos.dedup();
+        Ok(algos)
+    }
+
+    /// `list`, less SHA-256, which every archive records anyway.
+    pub fn extras<S: AsRef<str>>(names: &[S]) -> Result<Vec<Self>> {
+        let mut algos = Self::list(names)?;
+        algos.retain(|a| *a != Self::Sha256);
+        Ok(algos)
+    }
+}
+
+enum State {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+/// (algorithm, hex digest) pairs, as `MultiHasher::finish` returns them.
+pub type Digests = Vec<(HashAlgo, String)>;
+
+/// Several digests of one byte stream, fed in a single pass.
+pub struct MultiHasher(Vec<(HashAlgo, State)>);
+
+impl MultiHasher {
+    pub fn new(algos: &[HashAlgo]) -> Self {
+        Self(
+            algos
+                .iter()
+                .map(|a| {
+                    let state = match a {
+                        HashAlgo::Md5 => State::Md5(Md5::new()),
+                        HashAlgo::Sha1 => State::Sha1(Sha1::new()),
+                        HashAlgo::Sha256 => State::Sha256(Sha256::new()),
+                        HashAlgo::Blake3 => State::Blake3(Box::new(blake3::Hasher::new())),
+                    };
+                    (*a, state)
+                })
+                .collect(),
+        )
+    }
+
+    /// SHA-256 plus the archive's extra algorithms.
+    pub fn with_sha256(extras: &[HashAlgo]) -> Self {
+        let mut algos = vec![HashAlgo::Sha256];
+        algos.extend(extras.iter().filter(|a| **a != HashAlgo::Sha256));
+        Self::new(&algos)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, state) in &mut self.0 {
+            match state {
+                State::Md5(h) => h.update(data),
+                State::Sha1(h) => h.update(data),
+                State::Sha256(h) => h.update(data),
+                State::Blake3(h) => {
+                    h.update(data);
+                }
+            }
+        }
+    }
+
+    /// (algorithm, hex digest), in the order the algorithms were given.
+    pub fn finish(self) -> Digests {
+        self.0
+            .into_iter()
+            .map(|(a, state)| {
+                let hex = match state {
+                    State::Md5(h) => encode(h.finalize()),
+                    State::Sha1(h) => encode(h.finalize()),
+                    State::Sha256(h) => encode(h.finalize()),
+                    State::Blake3(h) => h.finalize().to_hex().to_string(),
+                };
+                (a, hex)
+            })
+            .collect()
+    }
+}
+
+/// Digests of everything `reader` yields, one per algorithm in `algos`.
+pub fn digest_reader<R: Read>(reader: &mut R, algos: &[HashAlgo]) -> Result<Digests> {
+    let mut hasher = MultiHasher::new(algos);
     let mut buf = [0u8; 131072];
-    let file =
-        File::open(path).map_err(|e| anyhow::anyhow!("Cannot open {}: {}", path.display(), e))?;
-    let mut reader = BufReader::new(file);
     loop {
         let n = reader.read(&mut buf)?;
         if n == 0 {
@@ -122,18 +262,44 @@ pub fn hash_file(path: &Path) -> Result<String> {
         }
         hasher.update(&buf[..n]);
     }
-    Ok(encode(hasher.finalize()))
+    Ok(hasher.finish())
+}
+
+// 128 KiB chunks, no temp files
+pub fn digest_file(path: &Path, algos: &[HashAlgo]) -> Result<Digests> {
+    let file =
+        File::open(path).map_err(|e| anyhow::anyhow!("Cannot open {}: {}", path.display(), e))?;
+    digest_reader(&mut BufReader::new(file), algos)
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    hash_file_as(path, HashAlgo::Sha256)
 }
 
 pub fn hash_reader<R: Read>(reader: &mut R) -> Result<String> {
-    let mut hasher = Sha256::new();
+    hash_reader_as(reader, HashAlgo::Sha256)
+}
+
+pub fn hash_file_as(path: &Path, algo: HashAlgo) -> Result<String> {
+    Ok(digest_file(path, &[algo])?.remove(0).1)
+}
+
+pub fn hash_reader_as<R: Read>(reader: &mut R, algo: HashAlgo) -> Result<String> {
+    Ok(digest_reader(reader, &[algo])?.remove(0).1)
+}
+
+/// BLAKE3 (hex) of everything `reader` yields, and how many bytes that was.
+pub fn blake3_reader<R: Read>(reader: &mut R) -> Result<(u64, String)> {
+    let mut hasher = blake3::Hasher::new();
     let mut buf = [0u8; 131072];
+    let mut total = 0u64;
     loop {
         let n = reader.read(&mut buf)?;
         if n == 0 {
             break;
         }
         hasher.update(&buf[..n]);
+        total += n as u64;
     }
-    Ok(encode(hasher.finalize()))
+    Ok((total, hasher.finalize().to_hex().to_string()))
 }
@@ -18,7 +18,7 @@
 use anyhow::{Result, bail};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
@@ -67,7 +67,32 @@ impl CompressionAlgo {
         }
     }
 
-    pub fn wrap_writer(&self, file: File, zstd_level: i32) -> Result<Box<dyn Write>> {
+    pub const ALL: [Self; 5] = [Self::None, Self::Gzip, Self::Bzip2, Self::Lz4, Self::Zstd];
+
+    /// The format of a file that starts with `head` (its first 512 bytes or
+    /// all of it, if shorter), going by magic bytes. A plain tar is
+    /// recognized by its first header.
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else if head.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+            Some(Self::Lz4)
+        } else if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if head.starts_with(b"BZh") {
+            Some(Self::Bzip2)
+        } else if head.len() >= 512 && &head[257..262] == b"ustar" {
+            Some(Self::None)
+        } else {
+            None
+        }
+    }
+
+    pub fn wrap_writer<W: Write + 'static>(
+        &self,
+        file: W,
+        zstd_level: i32,
+    ) -> Result<Box<dyn Write>> {
         match self {
             Self::None => Ok(Box::new(BufWriter::new(file))),
             Self::Gzip => {
@@ -91,13 +116,70 @@ impl CompressionAlgo {
         }
     }
 
+    /// `data` as one complete frame that stores it uncompressed, so it
+    /// decodes like any other frame but can later be overwritten in place
+    /// by a frame for different bytes of the same length. bzip2 has no such
+    /// frames.
+    pub fn stored_frame(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let len = data.len();
+        let mut out = Vec::with_capacity(len + 32);
+        match self {
+            Self::None => {}
+            Self::Gzip => {
+                if len > 0xffff {
+                    bail!("stored gzip block too large ({len} bytes)");
+                }
+                // member header (no name, unknown OS), then a final stored block
+                out.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 0x01]);
+                out.extend_from_slice(&(len as u16).to_le_bytes());
+                out.extend_from_slice(&(!(len as u16)).to_le_bytes());
+            }
+            Self::Bzip2 => bail!("bzip2 cannot store data uncompressed"),
+            Self::Lz4 => {
+                if len > 64 * 1024 {
+                    bail!("stored lz4 block too large ({len} bytes)");
+                }
+                // version 1, independent blocks, 64 KiB maximum, no checksums;
+                // 0x82 is the descriptor's own checksum byte
+                out.extend_from_slice(&[0x04, 0x22, 0x4d, 0x18, 0x60, 0x40, 0x82]);
+                out.extend_from_slice(&(len as u32 | 0x8000_0000).to_le_bytes());
+            }
+            Self::Zstd => {
+                if len > 128 * 1024 {
+                    bail!("stored zstd block too large ({len} bytes)");
+                }
+                // single segment with a 4-byte content size, no checksum; then
+                // one last raw block
+                out.extend_from_slice(&[0x28, 0xb5, 0x2f, 0xfd, 0xa0]);
+                out.extend_from_slice(&(len as u32).to_le_bytes());
+                out.extend_from_slice(&(1 | (len as u32) << 3).to_le_bytes()[..3]);
+            }
+        }
+        out.extend_from_slice(data);
+        match self {
+            Self::Gzip => {
+                let mut crc = flate2::Crc::new();
+                crc.update(data);
+                out.extend_from_slice(&crc.sum().to_le_bytes());
+                out.extend_from_slice(&(len as u32).to_le_bytes());
+            }
+            Self::Lz4 => out.extend_from_slice(&[0; 4]),
+            _ => {}
+        }
+        Ok(out)
+    }
+
     pub fn wrap_reader(&self, path: &Path) -> Result<Box<dyn Read>> {
-        let file = File::open(path)?;
+        self.wrap_read(File::open(path)?)
+    }
+
+    /// Decompressing reader over any byte source (pipes included).
+    pub fn wrap_read<'a, R: Read + 'a>(&self, file: R) -> Result<Box<dyn Read + 'a>> {
         match self {
             Self::None => Ok(Box::new(BufReader::new(file))),
             Self::Gzip => {
-                use flate2::read::GzDecoder;
-                Ok(Box::new(GzDecoder::new(file)))
+                use flate2::read::MultiGzDecoder;
+                Ok(Box::new(MultiGzDecoder::new(file)))
             }
             Self::Bzip2 => {
                 use bzip2::read::BzDecoder;
@@ -105,7 +187,7 @@ impl CompressionAlgo {
             }
             Self::Lz4 => {
                 use lz4_flex::frame::FrameDecoder;
-                Ok(Box::new(FrameDecoder::new(file)))
+                Ok(Box::new(Lz4Reader(FrameDecoder::new(BufReader::new(file)))))
             }
             Self::Zstd => Ok(Box::new(zstd::Decoder::new(file)?)),
         }
@@ -131,3 +213,99 @@ impl<W: Write + 'static> Drop for Lz4Writer<W> {
         }
     }
 }
+
+// lz4_flex reports end of input at the end of each frame; carry on through
+// concatenated frames (stored header frames are followed by the data frame)
+struct Lz4Reader<R: Read>(lz4_flex::frame::FrameDecoder<BufReader<R>>);
+
+impl<R: Read> Read for Lz4Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.0.read(buf)?;
+            if n > 0 || buf.is_empty() || self.0.get_mut().fill_buf()?.is_empty() {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(algo: &CompressionAlgo, bytes: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        algo.wrap_read(bytes)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    fn encode(algo: &CompressionAlgo, data: &[u8]) -> Vec<u8> {
+        let buf = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(b)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut w = algo.wrap_writer(Shared(buf.clone()), 3).unwrap();
+        w.write_all(data).unwrap();
+        drop(w);
+        buf.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn stored_frames_decode_and_concatenate() {
+        let header = vec![7u8; 1024];
+        let body: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        for algo in &[
+            CompressionAlgo::None,
+            CompressionAlgo::Gzip,
+            CompressionAlgo::Lz4,
+            CompressionAlgo::Zstd,
+        ] {
+            let stored = algo.stored_frame(&header).unwrap();
+            assert_eq!(decode(algo, &stored), header, "{}", algo.name());
+
+            let mut stream = stored;
+            stream.extend(encode(algo, &body));
+            let mut want = header.clone();
+            want.extend(&body);
+            assert_eq!(decode(algo, &stream), want, "{}", algo.name());
+        }
+    }
+
+    #[test]
+    fn stored_frame_length_depends_only_on_the_data_length() {
+        for algo in &[
+            CompressionAlgo::Gzip,
+            CompressionAlgo::Lz4,
+            CompressionAlgo::Zstd,
+        ] {
+            let a = algo.stored_frame(&[0; 512]).unwrap();
+            let b = algo.stored_frame(&[0xff; 512]).unwrap();
+            assert_eq!(a.len(), b.len(), "{}", algo.name());
+        }
+    }
+
+    #[test]
+    fn bzip2_and_oversized_blocks_are_refused() {
+        assert!(CompressionAlgo::Bzip2.stored_frame(b"x").is_err());
+        assert!(CompressionAlgo::Gzip.stored_frame(&[0; 0x1_0000]).is_err());
+        assert!(
+            CompressionAlgo::Lz4
+                .stored_frame(&[0; 64 * 1024 + 1])
+                .is_err()
+        );
+        assert!(
+            CompressionAlgo::Zstd
+                .stored_frame(&[0; 128 * 1024 + 1])
+                .is_err()
+        );
+    }
+}
@@ -30,6 +30,10 @@ pub struct Config {
     pub update: UpdateConfig,
     pub output: OutputConfig,
     pub prune: PruneConfig,
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    #[serde(default)]
+    pub s3: S3Config,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,8 +52,34 @@ pub struct DefaultsConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateConfig {
     pub exclude: Vec<String>,
+    /// selection rules shared with update and diff; see selection.rs
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude_regex: Vec<String>,
+    #[serde(default)]
+    pub exclude_if_present: Vec<String>,
+    #[serde(default)]
+    pub exclude_caches: bool,
+    #[serde(default)]
+    pub one_file_system: bool,
+    /// ignore file names to honor, e.g. [".gitignore", ".archivumignore"]
+    #[serde(default)]
+    pub ignore_files: Vec<String>,
     pub dedup: bool,
     pub notes: String,
+    /// normalized tar headers, honors SOURCE_DATE_EPOCH
+    #[serde(default)]
+    pub reproducible: bool,
+    /// exit non-zero when a file changes while it is being archived
+    #[serde(default)]
+    pub fail_on_change: bool,
+    /// times a file that changes while being archived is re-read
+    #[serde(default)]
+    pub retry_changed: u32,
+    /// digests recorded per file besides SHA-256: md5, sha1, blake3
+    #[serde(default)]
+    pub hashes: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -77,6 +107,80 @@ pub struct PruneConfig {
     pub max_age_days: u64,
 }
 
+/// How `HOST:PATH` archives are reached; see remote.rs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// command that runs a program on HOST, e.g. "ssh -p 2222";
+    /// "local" runs this binary directly (HOST is ignored)
+    #[serde(default = "default_transport")]
+    pub transport: String,
+    /// archivum binary on the remote side
+    #[serde(default = "default_program")]
+    pub program: String,
+}
+
+fn default_transport() -> String {
+    "ssh".into()
+}
+
+fn default_program() -> String {
+    "archivum".into()
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            transport: default_transport(),
+            program: default_program(),
+        }
+    }
+}
+
+/// S3-compatible object storage for `s3://BUCKET/PREFIX` archives; see s3.rs.
+/// Credentials come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+/// `AWS_SESSION_TOKEN`, never from this file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    /// e.g. "http://localhost:9000" for MinIO; empty = AWS for `region`
+    #[serde(default)]
+    pub endpoint: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    /// address buckets as ENDPOINT/BUCKET instead of BUCKET.ENDPOINT
+    #[serde(default)]
+    pub path_style: bool,
+    /// multipart upload part size (S3 requires at least 5)
+    #[serde(default = "default_s3_part_size_mb")]
+    pub part_size_mb: u64,
+    /// attempts after the first for throttled, 5xx or dropped requests
+    #[serde(default = "default_s3_retries")]
+    pub retries: u32,
+}
+
+fn default_region() -> String {
+    "us-east-1".into()
+}
+
+fn default_s3_part_size_mb() -> u64 {
+    64
+}
+
+fn default_s3_retries() -> u32 {
+    4
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: default_region(),
+            path_style: false,
+            part_size_mb: default_s3_part_size_mb(),
+            retries: default_s3_retries(),
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -95,8 +199,18 @@ impl Default for Config {
                     "*.tmp".into(),
                     "*.swp".into(),
                 ],
+                include: vec![],
+                exclude_regex: vec![],
+                exclude_if_present: vec![],
+                exclude_caches: false,
+                one_file_system: false,
+                ignore_files: vec![],
                 dedup: false,
                 notes: String::new(),
+                reproducible: false,
+                fail_on_change: false,
+                retry_changed: 0,
+                hashes: vec![],
             },
             restore: RestoreConfig {
                 force: false,
@@ -113,6 +227,8 @@ impl Default for Config {
                 keep_last: 3,
                 max_age_days: 30,
             },
+            remote: RemoteConfig::default(),
+            s3: S3Config::default(),
         }
     }
 }
@@ -298,6 +414,34 @@ impl Config {
         );
         println!("    notes         = {:?}", self.create.notes);
         println!("    exclude       = {:?}", self.create.exclude);
+        println!("    include       = {:?}", self.create.include);
+        println!("    exclude_regex = {:?}", self.create.exclude_regex);
+        println!(
+            "    exclude_if_present = {:?}",
+            self.create.exclude_if_present
+        );
+        println!(
+            "    exclude_caches  = {}",
+            self.create.exclude_caches.to_string().yellow()
+        );
+        println!(
+            "    one_file_system = {}",
+            self.create.one_file_system.to_string().yellow()
+        );
+        println!("    ignore_files  = {:?}", self.create.ignore_files);
+        println!(
+            "    reproducible  = {}",
+            self.create.reproducible.to_string().yellow()
+        );
+        println!(
+            "    fail_on_change = {}",
+            self.create.fail_on_change.to_string().yellow()
+        );
+        println!(
+            "    retry_changed = {}",
+            self.create.retry_changed.to_string().yellow()
+        );
+        println!("    hashes        = {:?}", self.create.hashes);
 
         println!();
         println!("  [restore]");
@@ -328,6 +472,28 @@ impl Config {
             self.prune.max_age_days.to_string().yellow()
         );
 
+        println!();
+        println!("  [remote]");
+        println!("    transport    = {}", self.remote.transport.green());
+        println!("    program      = {}", self.remote.program.green());
+
+        println!();
+        println!("  [s3]");
+        println!("    endpoint     = {}", self.s3.endpoint.green());
+        println!("    region       = {}", self.s3.region.green());
+        println!(
+            "    path_style   = {}",
+            self.s3.path_style.to_string().yellow()
+        );
+        println!(
+            "    part_size_mb = {}",
+            self.s3.part_size_mb.to_string().yellow()
+        );
+        println!(
+            "    retries      = {}",
+            self.s3.retries.to_string().yellow()
+        );
+
         println!("{}", "─".repeat(60).dimmed());
     }
 }
@@ -20,31 +20,38 @@ use colored::Colorize;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use crate::checksum::hash_file;
+use crate::checksum::{HashAlgo, hash_file_as};
 use crate::index::ArchivumIndex;
+use crate::index::raw_path::RawPath;
 use crate::output::OutputCtx;
-use crate::scan::{EntryType, scan_directory};
+use crate::scan::{EntryType, ScanReport};
+use crate::selection::Selection;
+use crate::source::Sources;
 use crate::utils::human;
 
 pub fn diff(
     index_path: &Path,
-    source: &Path,
+    sources: &Sources,
+    selection: &Selection,
     changed_only: bool,
-    use_checksum: bool,
+    checksum: Option<HashAlgo>,
     out: &OutputCtx,
 ) -> Result<()> {
     let idx = ArchivumIndex::read(index_path)?;
+    if let Some(algo) = checksum {
+        idx.require_hash(algo)?;
+    }
 
     out.println(&format!(
         "{} {} vs {}",
         "Diff:".cyan().bold(),
         index_path.display().to_string().yellow(),
-        source.display().to_string().yellow()
+        sources.describe().yellow()
     ));
-    if use_checksum {
+    if let Some(algo) = checksum {
         out.println(&format!(
             "  {}",
-            "Using SHA-256 checksum comparison".dimmed()
+            format!("Using {} checksum comparison", algo.label()).dimmed()
         ));
     }
     out.println("");
@@ -52,11 +59,21 @@ pub fn diff(
     let archived: HashMap<&Path, &crate::index::IndexEntry> = idx
         .entries
         .iter()
-        .filter(|e| e.entry_type == EntryType::File)
+        // streamed entries have no source file to compare with
+        .filter(|e| e.entry_type == EntryType::File && !e.from_stdin)
         .map(|e| (e.path.as_path(), e))
         .collect();
 
-    let current = scan_directory(source, &[])?;
+    let mut report = ScanReport::default();
+    let current = sources.scan(selection, &mut report)?;
+    for err in &report.errors {
+        out.eprintln(&format!(
+            "  {} {} ({})",
+            "unreadable:".yellow(),
+            err.path.display(),
+            err.kind.name()
+        ));
+    }
     let current_map: HashMap<&Path, &crate::scan::ScanEntry> = current
         .iter()
         .filter(|e| e.entry_type == EntryType::File)
@@ -71,7 +88,7 @@ pub fn diff(
     for (&path, se) in &current_map {
         if let Some(ae) = archived.get(path) {
             let size_changed = se.size != ae.size;
-            let mtime_changed = se.mtime != ae.mtime;
+            let mtime_changed = idx.header.clamp_mtime(se.mtime) != ae.mtime;
 
             if size_changed || mtime_changed {
                 let reason = if size_changed {
@@ -80,11 +97,11 @@ pub fn diff(
                     "mtime changed".to_string()
                 };
                 modified.push((path.to_path_buf(), reason));
-            } else if use_checksum {
-                let full_path = source.join(path);
-                match hash_file(&full_path) {
+            } else if let Some(algo) = checksum {
+                let full_path = sources.resolve(path);
+                match hash_file_as(&full_path, algo) {
                     Ok(actual_hash) => {
-                        let stored = ae.sha256.as_deref().unwrap_or("");
+                        let stored = ae.digest(algo).unwrap_or("");
                         if !stored.is_empty() && actual_hash != stored {
                             modified.push((
                                 path.to_path_buf(),
@@ -108,17 +125,19 @@ pub fn diff(
         }
     }
 
+    // as in update, a file the selection left out (or the scan could not
+    // read) is still in the source; only files gone from it are removed
     for &path in archived.keys() {
-        if !current_map.contains_key(path) {
+        if !current_map.contains_key(path) && !sources.still_has(path) {
             removed.push(path.to_path_buf());
         }
     }
 
     if out.json {
         let result = serde_json::json!({
-            "added":    added.iter().map(|(p, s)| serde_json::json!({"path": p, "size": s})).collect::<Vec<_>>(),
-            "removed":  removed.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
-            "modified": modified.iter().map(|(p, r)| serde_json::json!({"path": p, "reason": r})).collect::<Vec<_>>(),
+            "added":    added.iter().map(|(p, s)| serde_json::json!({"path": RawPath(p), "size": s})).collect::<Vec<_>>(),
+            "removed":  removed.iter().map(|p| RawPath(p)).collect::<Vec<_>>(),
+            "modified": modified.iter().map(|(p, r)| serde_json::json!({"path": RawPath(p), "reason": r})).collect::<Vec<_>>(),
             "unchanged": unchanged
         });
         out.raw(&serde_json::to_string_pretty(&result).unwrap());
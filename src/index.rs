@@ -19,12 +19,15 @@ use anyhow::Result;
 use colored::Colorize;
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
+use crate::checksum::HashAlgo;
 use crate::compress::CompressionAlgo;
+use crate::merkle;
 use crate::output::OutputCtx;
-use crate::scan::{EntryType, ScanEntry};
+use crate::scan::{EntryType, ScanEntry, ScanError, ScanReport};
 use crate::utils::{fmt_time, human, now};
 
 pub const INDEX_VERSION: u32 = 3;
@@ -37,6 +40,9 @@ pub struct IndexHeader {
     pub total_files: u64,
     pub total_dirs: u64,
     pub total_symlinks: u64,
+    /// device nodes and FIFOs
+    #[serde(default)]
+    pub total_special: u64,
     pub total_size: u64,
     pub total_parts: u32,
     pub compression: CompressionAlgo,
@@ -47,10 +53,94 @@ pub struct IndexHeader {
     /// index 0 = same dir as index file; used by incremental update
     #[serde(default = "default_part_bases")]
     pub part_bases: Vec<String>,
+    /// normalized tar headers + pinned timestamps (see make_reproducible)
+    #[serde(default)]
+    pub reproducible: bool,
+    /// entry and tar mtimes are clamped to this when set
+    #[serde(default)]
+    pub source_date_epoch: Option<u64>,
+    /// ignore files (.gitignore etc.) applied during the scan, relative to
+    /// the source root
+    #[serde(default)]
+    pub ignore_sources: Vec<String>,
+    /// prefixes of the source roots when several were archived; each root's
+    /// entries live under `<prefix>/`
+    #[serde(default)]
+    pub roots: Vec<String>,
+    /// size and BLAKE3 of each part file as stored, for `verify --quick`
+    #[serde(default)]
+    pub part_hashes: Vec<PartHash>,
+    /// Merkle root over the entries' path, type, mode and content; filled
+    /// in whenever the index is written (see merkle.rs)
+    #[serde(default)]
+    pub merkle_root: Option<String>,
+    /// digest algorithms every file entry carries in `hashes` besides
+    /// SHA-256, by name (see checksum::HashAlgo)
+    #[serde(default)]
+    pub hashes: Vec<String>,
     #[serde(skip)]
     pub _integrity: Option<String>,
 }
 
+impl IndexHeader {
+    /// `mtime` as this archive records it: no later than
+    /// `source_date_epoch`, when one is set.
+    pub fn clamp_mtime(&self, mtime: Option<u64>) -> Option<u64> {
+        mtime.map(|m| self.source_date_epoch.map_or(m, |e| m.min(e)))
+    }
+
+    /// The extra digest algorithms the header lists; names this build does
+    /// not know are left out.
+    pub fn hash_algos(&self) -> Vec<HashAlgo> {
+        let known: Vec<&String> = self
+            .hashes
+            .iter()
+            .filter(|n| HashAlgo::parse(n).is_ok())
+            .collect();
+        HashAlgo::extras(&known).unwrap_or_default()
+    }
+
+    /// Path of part `part` under `part_bases[base]`.
+    pub fn part_file(&self, index_dir: &Path, base: u32, part: u32) -> PathBuf {
+        let base = self
+            .part_bases
+            .get(base as usize)
+            .map(|s| s.as_str())
+            .unwrap_or("");
+        let dir = if base.is_empty() {
+            index_dir.to_path_buf()
+        } else {
+            index_dir.join(base)
+        };
+        dir.join(format!(
+            "data.part{:03}{}",
+            part,
+            self.compression.extension()
+        ))
+    }
+}
+
+/// A finished part file: compressed size and BLAKE3 of its bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartHash {
+    /// which part_bases index this part lives under (None = 0)
+    #[serde(default)]
+    pub base: Option<u32>,
+    pub part: u32,
+    pub size: u64,
+    pub blake3: String,
+}
+
+/// One part's share of a streamed entry that outgrew a part: the size and
+/// digests of the member holding it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Piece {
+    pub size: u64,
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
+}
+
 fn default_zstd_level() -> i32 {
     3
 }
@@ -60,40 +150,233 @@ fn default_part_bases() -> Vec<String> {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexEntry {
+    #[serde(with = "raw_path")]
     pub path: PathBuf,
     pub entry_type: EntryType,
     pub size: u64,
     pub mtime: Option<u64>,
     pub unix_mode: Option<u32>,
     pub sha256: Option<String>,
+    /// further digests by algorithm name, e.g. "md5" -> hex
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
     pub tar_part: u32,
+    #[serde(default, with = "raw_path::opt")]
     pub symlink_target: Option<PathBuf>,
     /// which part_bases index this part lives under (None = 0)
     #[serde(default)]
     pub tar_base: Option<u32>,
     /// if set, this file is a dedup alias and not stored in tar
-    #[serde(default)]
+    #[serde(default, with = "raw_path::opt")]
     pub dedup_of: Option<PathBuf>,
+    /// device numbers for char/block device entries
+    #[serde(default)]
+    pub dev_major: Option<u32>,
+    #[serde(default)]
+    pub dev_minor: Option<u32>,
+    /// size, mtime or content moved while the file was being archived;
+    /// sha256 then describes the bytes that actually went into the tar
+    #[serde(default)]
+    pub changed_during_backup: bool,
+    /// streamed from stdin (`--stdin-name` / `add`), not read from the source
+    #[serde(default)]
+    pub from_stdin: bool,
+    /// set when the entry continues past `tar_part`: one member per part,
+    /// in order, from `tar_part` on (only streams are cut up like this)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pieces: Vec<Piece>,
+}
+
+/// Serde adapter for paths that may not be valid UTF-8. UTF-8 paths stay
+/// plain strings; anything else is stored as `{"hex": "<raw bytes>"}`.
+pub mod raw_path {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::path::{Path, PathBuf};
+
+    /// Borrowed path that serializes with the index encoding (for `json!`).
+    pub struct RawPath<'a>(pub &'a Path);
+
+    impl Serialize for RawPath<'_> {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            serialize(self.0, s)
+        }
+    }
+
+    #[derive(Serialize)]
+    struct HexOut<'a> {
+        hex: &'a str,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Raw { hex: String },
+    }
+
+    impl Repr {
+        fn into_path<E: Error>(self) -> Result<PathBuf, E> {
+            match self {
+                Repr::Text(t) => Ok(PathBuf::from(t)),
+                Repr::Raw { hex } => hex::decode(&hex).map(from_bytes).map_err(E::custom),
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(path: &Path, s: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(text) => s.serialize_str(text),
+            None => HexOut {
+                hex: &hex::encode(to_bytes(path)),
+            }
+            .serialize(s),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<PathBuf, D::Error> {
+        Repr::deserialize(d)?.into_path()
+    }
+
+    #[cfg(unix)]
+    fn to_bytes(path: &Path) -> Vec<u8> {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(not(unix))]
+    fn to_bytes(path: &Path) -> Vec<u8> {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+
+    #[cfg(unix)]
+    fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+        use std::os::unix::ffi::OsStringExt;
+        PathBuf::from(std::ffi::OsString::from_vec(bytes))
+    }
+
+    #[cfg(not(unix))]
+    fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+        PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub mod opt {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(path: &Option<PathBuf>, s: S) -> Result<S::Ok, S::Error> {
+            match path {
+                Some(p) => s.serialize_some(&RawPath(p)),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<PathBuf>, D::Error> {
+            Option::<Repr>::deserialize(d)?
+                .map(Repr::into_path)
+                .transpose()
+        }
+    }
 }
 
 impl IndexEntry {
     pub fn part_path(&self, index_dir: &Path, header: &IndexHeader) -> PathBuf {
-        let base_idx = self.tar_base.unwrap_or(0) as usize;
-        let base = header
-            .part_bases
-            .get(base_idx)
-            .map(|s| s.as_str())
-            .unwrap_or("");
-        let dir = if base.is_empty() {
-            index_dir.to_path_buf()
+        header.part_file(index_dir, self.tar_base.unwrap_or(0), self.tar_part)
+    }
+
+    /// Rebuild an entry from a tar member found in part `part`. Members of
+    /// other kinds (hard links, PAX headers) give `None`; the SHA-256 is
+    /// left unset.
+    pub fn from_tar(path: PathBuf, header: &tar::Header, part: u32) -> Result<Option<Self>> {
+        let entry_type = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => EntryType::File,
+            tar::EntryType::Directory => EntryType::Directory,
+            tar::EntryType::Symlink => EntryType::Symlink,
+            tar::EntryType::Char => EntryType::CharDevice,
+            tar::EntryType::Block => EntryType::BlockDevice,
+            tar::EntryType::Fifo => EntryType::Fifo,
+            _ => return Ok(None),
+        };
+
+        let symlink_target = if entry_type == EntryType::Symlink {
+            header.link_name().ok().flatten().map(|l| l.into_owned())
         } else {
-            index_dir.join(base)
+            None
         };
-        dir.join(format!(
-            "data.part{:03}{}",
-            self.tar_part,
-            header.compression.extension()
-        ))
+
+        let (dev_major, dev_minor) = if entry_type.is_special() {
+            (
+                header.device_major().ok().flatten(),
+                header.device_minor().ok().flatten(),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(Some(Self {
+            path,
+            entry_type,
+            size: header.size()?,
+            mtime: header.mtime().ok(),
+            unix_mode: header.mode().ok(),
+            sha256: None,
+            hashes: BTreeMap::new(),
+            tar_part: part,
+            symlink_target,
+            tar_base: None,
+            dedup_of: None,
+            dev_major,
+            dev_minor,
+            changed_during_backup: false,
+            from_stdin: false,
+            pieces: vec![],
+        }))
+    }
+
+    /// The last part holding some of this entry's content.
+    pub fn last_part(&self) -> u32 {
+        self.tar_part + (self.pieces.len() as u32).saturating_sub(1)
+    }
+
+    /// The members an entry is stored as, each described like an entry of
+    /// its own: the entry itself, or one per piece with that piece's part,
+    /// size and digests.
+    pub fn members(&self) -> Vec<IndexEntry> {
+        if self.pieces.is_empty() {
+            return vec![self.clone()];
+        }
+        (0u32..)
+            .zip(&self.pieces)
+            .map(|(i, p)| IndexEntry {
+                size: p.size,
+                sha256: p.sha256.clone(),
+                hashes: p.hashes.clone(),
+                tar_part: self.tar_part + i,
+                pieces: vec![],
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// The recorded digest under `algo`, if any.
+    pub fn digest(&self, algo: HashAlgo) -> Option<&str> {
+        match algo {
+            HashAlgo::Sha256 => self.sha256.as_deref(),
+            _ => self.hashes.get(algo.name()).map(String::as_str),
+        }
+    }
+
+    /// Record freshly computed digests, replacing any older ones: SHA-256
+    /// goes to `sha256`, the rest to `hashes`.
+    pub fn set_digests(&mut self, digests: Vec<(HashAlgo, String)>) {
+        self.hashes.clear();
+        for (algo, hex) in digests {
+            match algo {
+                HashAlgo::Sha256 => self.sha256 = Some(hex),
+                _ => {
+                    self.hashes.insert(algo.name().to_string(), hex);
+                }
+            }
+        }
     }
 }
 
@@ -101,6 +384,9 @@ impl IndexEntry {
 pub struct ArchivumIndex {
     pub header: IndexHeader,
     pub entries: Vec<IndexEntry>,
+    /// paths skipped during scan or archiving, and why
+    #[serde(default)]
+    pub errors: Vec<ScanError>,
 }
 
 impl ArchivumIndex {
@@ -108,6 +394,7 @@ impl ArchivumIndex {
         let mut files = 0u64;
         let mut dirs = 0u64;
         let mut symlinks = 0u64;
+        let mut special = 0u64;
         let mut size = 0u64;
 
         let entries = scan
@@ -120,6 +407,9 @@ impl ArchivumIndex {
                     }
                     EntryType::Directory => dirs += 1,
                     EntryType::Symlink => symlinks += 1,
+                    EntryType::CharDevice | EntryType::BlockDevice | EntryType::Fifo => {
+                        special += 1
+                    }
                 }
                 IndexEntry {
                     path: e.relative_path,
@@ -128,10 +418,16 @@ impl ArchivumIndex {
                     mtime: e.mtime,
                     unix_mode: e.unix_mode,
                     sha256: None,
+                    hashes: BTreeMap::new(),
                     tar_part: 0,
                     symlink_target: e.symlink_target,
                     tar_base: None,
                     dedup_of: None,
+                    dev_major: e.dev_major,
+                    dev_minor: e.dev_minor,
+                    changed_during_backup: false,
+                    from_stdin: false,
+                    pieces: vec![],
                 }
             })
             .collect();
@@ -145,36 +441,101 @@ impl ArchivumIndex {
                 total_files: files,
                 total_dirs: dirs,
                 total_symlinks: symlinks,
+                total_special: special,
                 total_size: size,
                 total_parts: 0,
                 compression,
                 zstd_level,
                 notes: String::new(),
                 part_bases: vec![String::new()],
+                reproducible: false,
+                source_date_epoch: None,
+                ignore_sources: vec![],
+                roots: vec![],
+                part_hashes: vec![],
+                merkle_root: None,
+                hashes: vec![],
                 _integrity: None,
             },
             entries,
+            errors: vec![],
         }
     }
 
-    pub fn write(&self, path: &Path) -> Result<()> {
-        let json = serde_json::to_vec_pretty(self)?;
+    /// Take over what the scan recorded besides entries.
+    pub fn record_scan(&mut self, report: ScanReport) {
+        self.errors = report.errors;
+        self.header.ignore_sources = report
+            .ignore_sources
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+    }
+
+    /// Pin the creation time to `SOURCE_DATE_EPOCH` (or 0) and ask the tar
+    /// writer for normalized headers, so identical inputs give identical bytes.
+    /// Entry mtimes are clamped to the epoch when the index is written.
+    pub fn make_reproducible(&mut self, epoch: Option<u64>) {
+        let ts = epoch.unwrap_or(0);
+        self.header.created_at_unix = ts;
+        self.header.created_at_human = fmt_time(ts);
+        self.header.reproducible = true;
+        self.header.source_date_epoch = epoch;
+    }
+
+    pub fn changed_during_backup(&self) -> Vec<&IndexEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.changed_during_backup)
+            .collect()
+    }
+
+    /// Write the index and its seal, with the Merkle root brought up to
+    /// date.
+    pub fn write(&mut self, path: &Path) -> Result<()> {
+        self.finish_entries();
+        let (json, seal) = self.to_bytes()?;
         std::fs::write(path, &json)?;
 
         // companion .b3 integrity file
-        let hash = blake3::hash(&json);
-        let b3_path = path.with_extension("json.b3");
-        std::fs::write(&b3_path, hash.to_hex().as_str())?;
+        std::fs::write(seal_path(path), seal)?;
 
         Ok(())
     }
 
+    /// Bring the entries up to date for writing: mtimes clamped to
+    /// `SOURCE_DATE_EPOCH` in a reproducible index, and the Merkle root of
+    /// the entries as they are now.
+    pub fn finish_entries(&mut self) {
+        for e in &mut self.entries {
+            e.mtime = self.header.clamp_mtime(e.mtime);
+        }
+        self.header.merkle_root = Some(merkle::root(&self.entries));
+    }
+
+    /// The index JSON and its BLAKE3 seal (hex), as `write` stores them.
+    pub fn to_bytes(&self) -> Result<(Vec<u8>, String)> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let seal = blake3::hash(&json).to_hex().to_string();
+        Ok((json, seal))
+    }
+
     pub fn read(path: &Path) -> Result<Self> {
         let bytes = std::fs::read(path)?;
 
-        let b3_path = path.with_extension("json.b3");
-        if b3_path.exists() {
-            let stored_hex = std::fs::read_to_string(&b3_path)?;
+        let b3_path = seal_path(path);
+        let seal = if b3_path.exists() {
+            Some(std::fs::read_to_string(&b3_path)?)
+        } else {
+            None
+        };
+        Self::from_bytes(bytes, seal.as_deref(), path)
+    }
+
+    /// Parse index JSON, checking it against `seal` when there is one.
+    /// `path` is only used in messages.
+    pub fn from_bytes(bytes: Vec<u8>, seal: Option<&str>, path: &Path) -> Result<Self> {
+        if let Some(stored_hex) = seal {
             let stored_hex = stored_hex.trim();
             let actual = blake3::hash(&bytes);
             if actual.to_hex().as_str() != stored_hex {
@@ -224,6 +585,12 @@ impl ArchivumIndex {
             "  Symlinks  : {}",
             h.total_symlinks.to_string().cyan()
         ));
+        if h.total_special > 0 {
+            out.println(&format!(
+                "  Special   : {}",
+                h.total_special.to_string().cyan()
+            ));
+        }
         out.println(&format!("  Total size: {}", human(h.total_size).cyan()));
         out.println(&format!(
             "  Parts     : {}",
@@ -237,6 +604,24 @@ impl ArchivumIndex {
             ));
         }
 
+        out.println(&format!(
+            "  Merkle    : {}",
+            self.merkle_root_label().dimmed()
+        ));
+        if !h.hashes.is_empty() {
+            out.println(&format!(
+                "  Hashes    : sha256, {}",
+                h.hashes.join(", ").green()
+            ));
+        }
+
+        if !h.ignore_sources.is_empty() {
+            out.println(&format!(
+                "  Ignores   : {}",
+                h.ignore_sources.join(", ").dimmed()
+            ));
+        }
+
         let deduped = self.entries.iter().filter(|e| e.dedup_of.is_some()).count();
         if deduped > 0 {
             out.println(&format!(
@@ -245,6 +630,30 @@ impl ArchivumIndex {
             ));
         }
 
+        let changed = self.changed_during_backup().len();
+        if changed > 0 {
+            out.println(&format!(
+                "  Changed   : {} files modified during backup",
+                changed.to_string().red()
+            ));
+        }
+
+        if !self.errors.is_empty() {
+            out.println(&format!(
+                "  Skipped   : {} path(s)",
+                self.errors.len().to_string().red()
+            ));
+            for err in &self.errors {
+                out.println(&format!(
+                    "    {} {} ({}: {})",
+                    "!".red(),
+                    err.path.display(),
+                    err.kind.name().yellow(),
+                    err.message.dimmed()
+                ));
+            }
+        }
+
         if verbose || filter.is_some() {
             let globset = filter
                 .map(|f| -> Result<GlobSet> {
@@ -274,19 +683,30 @@ impl ArchivumIndex {
                     EntryType::File => "file".green(),
                     EntryType::Directory => "dir".blue(),
                     EntryType::Symlink => "symlink".yellow(),
+                    EntryType::CharDevice => "chardev".magenta(),
+                    EntryType::BlockDevice => "blockdev".magenta(),
+                    EntryType::Fifo => "fifo".magenta(),
                 };
                 let dedup_tag = if e.dedup_of.is_some() {
                     " [dedup]".dimmed().to_string()
                 } else {
                     String::new()
                 };
+                let changed_tag = if e.changed_during_backup {
+                    " [changed]".red().to_string()
+                } else if e.from_stdin {
+                    " [stdin]".dimmed().to_string()
+                } else {
+                    String::new()
+                };
                 out.println(&format!(
-                    "  {:<8} {:<12} {:<10} {}{}",
+                    "  {:<8} {:<12} {:<10} {}{}{}",
                     format!("part{:03}", e.tar_part),
                     human(e.size),
                     type_str,
                     e.path.display(),
-                    dedup_tag
+                    dedup_tag,
+                    changed_tag
                 ));
             }
         }
@@ -295,8 +715,99 @@ impl ArchivumIndex {
         Ok(())
     }
 
+    /// Fail unless every file entry carries a digest under `algo`.
+    pub fn require_hash(&self, algo: HashAlgo) -> Result<()> {
+        if algo == HashAlgo::Sha256 || self.header.hash_algos().contains(&algo) {
+            return Ok(());
+        }
+        let mut recorded = vec!["sha256"];
+        recorded.extend(self.header.hash_algos().iter().map(|a| a.name()));
+        anyhow::bail!(
+            "Archive records no {} digests (recorded: {})",
+            algo.name(),
+            recorded.join(", ")
+        )
+    }
+
+    /// The recorded Merkle root, or the computed one marked as such for
+    /// indexes written before roots were recorded.
+    pub fn merkle_root_label(&self) -> String {
+        match &self.header.merkle_root {
+            Some(root) => root.clone(),
+            None => format!("{} (computed, not recorded)", merkle::root(&self.entries)),
+        }
+    }
+
     pub fn print_summary_json(&self) -> Result<()> {
         println!("{}", serde_json::to_string_pretty(self)?);
         Ok(())
     }
 }
+
+/// Where the BLAKE3 seal of the index at `path` lives.
+pub fn seal_path(path: &Path) -> PathBuf {
+    path.with_extension("json.b3")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::raw_path;
+    use serde::{Deserialize, Serialize};
+    use std::path::PathBuf;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Holder {
+        #[serde(with = "raw_path")]
+        path: PathBuf,
+        #[serde(default, with = "raw_path::opt")]
+        target: Option<PathBuf>,
+    }
+
+    fn round_trip(h: &Holder) -> (String, Holder) {
+        let json = serde_json::to_string(h).unwrap();
+        let back = serde_json::from_str(&json).unwrap();
+        (json, back)
+    }
+
+    #[test]
+    fn utf8_paths_stay_plain_strings() {
+        let h = Holder {
+            path: PathBuf::from("dir/naïve.txt"),
+            target: None,
+        };
+        let (json, back) = round_trip(&h);
+        assert_eq!(json, r#"{"path":"dir/naïve.txt","target":null}"#);
+        assert_eq!(back, h);
+    }
+
+    #[test]
+    fn missing_optional_path_defaults_to_none() {
+        let h: Holder = serde_json::from_str(r#"{"path":"a"}"#).unwrap();
+        assert_eq!(h.target, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths_round_trip_as_hex() {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let raw = PathBuf::from(OsString::from_vec(b"caf\xe9/\xff.bin".to_vec()));
+        let h = Holder {
+            path: raw.clone(),
+            target: Some(raw.clone()),
+        };
+        let (json, back) = round_trip(&h);
+        assert_eq!(
+            json,
+            r#"{"path":{"hex":"636166e92fff2e62696e"},"target":{"hex":"636166e92fff2e62696e"}}"#
+        );
+        assert_eq!(back, h);
+    }
+
+    #[test]
+    fn bad_hex_is_rejected() {
+        let r: Result<Holder, _> = serde_json::from_str(r#"{"path":{"hex":"zz"}}"#);
+        assert!(r.is_err());
+    }
+}
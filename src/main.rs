@@ -23,21 +23,33 @@ mod config;
 mod diff;
 mod index;
 mod merge;
+mod merkle;
 mod output;
+mod pipe;
 mod prune;
+mod remote;
 mod repair;
 mod restore;
+mod s3;
+mod salvage;
 mod scan;
+mod scrub;
 mod search;
+mod selection;
+mod source;
 mod stats;
+mod store;
+mod stream;
+mod sync;
 mod tar_writer;
 mod update;
 mod utils;
 mod verify;
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use colored::Colorize;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
 use compress::CompressionAlgo;
@@ -90,14 +102,130 @@ pub struct Cli {
     log_file: Option<PathBuf>,
 }
 
+/// Selection rules shared by `create`, `update` and `diff`, so all three
+/// agree on which files are in scope.
+#[derive(Args)]
+struct SelectArgs {
+    /// Exclude glob patterns (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Only include files matching these globs (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+    /// Exclude paths matching this regex (repeatable)
+    #[arg(long, value_name = "REGEX")]
+    exclude_regex: Vec<String>,
+    /// Skip files larger than this (e.g. 500M, 2G)
+    #[arg(long, value_name = "SIZE", value_parser = selection::parse_size)]
+    max_file_size: Option<u64>,
+    /// Skip files smaller than this (e.g. 1K)
+    #[arg(long, value_name = "SIZE", value_parser = selection::parse_size)]
+    min_file_size: Option<u64>,
+    /// Only files modified after this (unix time, YYYY-MM-DD, or age like 7d)
+    #[arg(long, value_name = "TIME", value_parser = selection::parse_time)]
+    newer_than: Option<u64>,
+    /// Only files modified before this (unix time, YYYY-MM-DD, or age like 30d)
+    #[arg(long, value_name = "TIME", value_parser = selection::parse_time)]
+    older_than: Option<u64>,
+    /// Do not cross file system boundaries
+    #[arg(long)]
+    one_file_system: bool,
+    /// Descend at most N directory levels below the source
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Skip directories containing a file with this name (repeatable)
+    #[arg(long, value_name = "NAME")]
+    exclude_if_present: Vec<String>,
+    /// Skip directories tagged with a CACHEDIR.TAG file
+    #[arg(long)]
+    exclude_caches: bool,
+    /// Honor .gitignore, .ignore and .archivumignore files in the source
+    #[arg(long)]
+    ignore_files: bool,
+    /// Honor ignore files with this name (repeatable, e.g. .archivumignore)
+    #[arg(long, value_name = "NAME")]
+    ignore_file: Vec<String>,
+}
+
+impl SelectArgs {
+    /// Merge with the `[create]` config section; list options accumulate.
+    fn rules(self, cfg: &Config) -> selection::SelectionRules {
+        let join = |a: &[String], b: Vec<String>| a.iter().cloned().chain(b).collect();
+        let mut ignore_files: Vec<String> = join(&cfg.create.ignore_files, self.ignore_file);
+        if self.ignore_files {
+            ignore_files.extend(
+                selection::DEFAULT_IGNORE_FILES
+                    .iter()
+                    .map(|s| s.to_string()),
+            );
+        }
+        let mut seen = std::collections::HashSet::new();
+        ignore_files.retain(|n| seen.insert(n.clone()));
+        selection::SelectionRules {
+            excludes: join(&cfg.create.exclude, self.exclude),
+            includes: join(&cfg.create.include, self.include),
+            exclude_regex: join(&cfg.create.exclude_regex, self.exclude_regex),
+            max_file_size: self.max_file_size,
+            min_file_size: self.min_file_size,
+            newer_than: self.newer_than,
+            older_than: self.older_than,
+            one_file_system: self.one_file_system || cfg.create.one_file_system,
+            max_depth: self.max_depth,
+            exclude_if_present: join(&cfg.create.exclude_if_present, self.exclude_if_present),
+            exclude_caches: self.exclude_caches || cfg.create.exclude_caches,
+            ignore_files,
+        }
+    }
+}
+
+/// Where the files come from, beyond the positional `SOURCE`.
+#[derive(Args)]
+struct SourceArgs {
+    /// Add another source root mounted under PREFIX/ (repeatable)
+    #[arg(long, value_name = "PREFIX=DIR")]
+    root: Vec<String>,
+    /// Read the paths to archive from a file (`-` for stdin), NUL or newline separated
+    #[arg(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+}
+
+impl SourceArgs {
+    /// `create` only: SOURCE `-` means "just the stdin stream".
+    fn sources_with_stdin(&self, source: &std::path::Path) -> Result<source::Sources> {
+        let stdin = std::path::Path::new("-");
+        if source == stdin {
+            if !self.root.is_empty() || self.files_from.is_some() {
+                anyhow::bail!("SOURCE '-' cannot be combined with --root or --files-from");
+            }
+            return Ok(source::Sources::none());
+        }
+        if self.files_from.as_deref() == Some(stdin) {
+            anyhow::bail!("--stdin-name and --files-from - both read stdin");
+        }
+        self.sources(source)
+    }
+
+    fn sources(&self, source: &std::path::Path) -> Result<source::Sources> {
+        let sources = source::Sources::new(source, &self.root)?;
+        match &self.files_from {
+            Some(list) => sources.with_files_from(list),
+            None => Ok(sources),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new archive from a source directory
     Create {
+        /// Source directory, or PREFIX=DIR to mount it under PREFIX/
         #[arg(value_name = "SOURCE")]
         source: PathBuf,
-        #[arg(value_name = "OUTPUT")]
-        output: PathBuf,
+        #[arg(value_name = "OUTPUT", required_unless_present = "stdout")]
+        output: Option<PathBuf>,
+        /// Write a single self-describing stream to stdout instead of OUTPUT
+        #[arg(long, conflicts_with_all = ["output", "stdin_name", "split_gb", "split_files"])]
+        stdout: bool,
         /// Max size per archive part in GB (config default: 4.0)
         #[arg(long, value_name = "GB")]
         split_gb: Option<f64>,
@@ -110,9 +238,13 @@ enum Commands {
         /// Zstd compression level (1-22)
         #[arg(long, value_name = "LEVEL")]
         zstd_level: Option<i32>,
-        /// Exclude glob patterns (repeatable)
-        #[arg(long, value_name = "PATTERN")]
-        exclude: Vec<String>,
+        #[command(flatten)]
+        sources: SourceArgs,
+        /// Also archive stdin as a file with this name (SOURCE may then be `-`)
+        #[arg(long, value_name = "NAME")]
+        stdin_name: Option<PathBuf>,
+        #[command(flatten)]
+        select: SelectArgs,
         /// Parallel checksum threads (config default: 4)
         #[arg(long, value_name = "N")]
         threads: Option<usize>,
@@ -122,6 +254,18 @@ enum Commands {
         /// Optional description stored in the index
         #[arg(long, value_name = "TEXT")]
         notes: Option<String>,
+        /// Byte-reproducible output: normalized tar headers, honors SOURCE_DATE_EPOCH
+        #[arg(long)]
+        reproducible: bool,
+        /// Exit with an error if a file changes while it is being archived
+        #[arg(long)]
+        fail_on_change: bool,
+        /// Re-read a file up to N times when it changes while being archived
+        #[arg(long, value_name = "N")]
+        retry_changed: Option<u32>,
+        /// Also record these digests per file: md5 | sha1 | blake3 (repeatable)
+        #[arg(long = "hash", value_name = "ALGO", value_delimiter = ',')]
+        hash: Vec<String>,
     },
 
     /// List contents and statistics of an archive
@@ -138,19 +282,38 @@ enum Commands {
 
     /// Restore an archive to a target directory
     Restore {
-        #[arg(value_name = "INDEX")]
-        index: PathBuf,
-        #[arg(value_name = "TARGET")]
-        target: PathBuf,
+        #[arg(
+            value_name = "INDEX",
+            required_unless_present = "stdin",
+            conflicts_with = "stdin"
+        )]
+        index: Option<PathBuf>,
+        #[arg(value_name = "TARGET", required_unless_present = "stdin")]
+        target: Option<PathBuf>,
+        /// Read a `create --stdout` stream from stdin and restore it into TARGET
+        #[arg(long, value_name = "TARGET")]
+        stdin: Option<PathBuf>,
         /// Only restore files matching this glob
         #[arg(long, value_name = "PATTERN")]
         filter: Option<String>,
+        /// Restore only this source root, without its prefix
+        #[arg(long, value_name = "PREFIX")]
+        root: Option<String>,
         /// Overwrite existing files
         #[arg(long, short)]
         force: bool,
         /// Restore Unix permissions
         #[arg(long)]
         restore_permissions: bool,
+        /// Recover what is readable from damaged parts; list lost files in REPORT
+        #[arg(
+            long,
+            value_name = "REPORT",
+            num_args = 0..=1,
+            default_missing_value = "salvage-report.json",
+            conflicts_with = "stdin"
+        )]
+        salvage: Option<PathBuf>,
     },
 
     /// Verify archive integrity (checksums + structure)
@@ -160,6 +323,51 @@ enum Commands {
         /// Continue on errors instead of stopping
         #[arg(long, short = 'c')]
         continue_on_error: bool,
+        /// Also verify every replica recorded by `sync`
+        #[arg(long)]
+        replicas: bool,
+        /// Only hash the part files against their recorded BLAKE3 (no decompression)
+        #[arg(long)]
+        quick: bool,
+        /// Parts verified in parallel (config default: 4)
+        #[arg(long, value_name = "N")]
+        threads: Option<usize>,
+        /// Also compare every entry with this source directory (or PREFIX=DIR)
+        #[arg(long, value_name = "SOURCE")]
+        against: Option<PathBuf>,
+        /// Another source root for --against, mounted under PREFIX/ (repeatable)
+        #[arg(long, value_name = "PREFIX=DIR", requires = "against")]
+        root: Vec<String>,
+        /// With --against, compare digests instead of bytes
+        #[arg(long, requires = "against")]
+        by_hash: bool,
+        /// Digests to check: md5 | sha1 | sha256 | blake3 (repeatable; default sha256)
+        #[arg(
+            long = "hash",
+            value_name = "ALGO",
+            value_delimiter = ',',
+            conflicts_with_all = ["quick", "prove"]
+        )]
+        hash: Vec<String>,
+        /// Append the result to the scrub journal next to the index
+        #[arg(long)]
+        journal: bool,
+        /// Prove that PATH is in the archive: its Merkle proof and stored data
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["quick", "against", "replicas", "journal"]
+        )]
+        prove: Option<PathBuf>,
+    },
+
+    /// Show the runs journaled by `verify --journal` and when parts degraded
+    ScrubReport {
+        #[arg(value_name = "INDEX")]
+        index: PathBuf,
+        /// Show only the latest N runs
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        last: usize,
     },
 
     /// Compare archive against source directory (drift detection)
@@ -174,6 +382,13 @@ enum Commands {
         /// Use SHA-256 to detect changes (not just mtime+size)
         #[arg(long)]
         checksum: bool,
+        /// Compare this recorded digest instead: md5 | sha1 | sha256 | blake3 (implies --checksum)
+        #[arg(long = "hash", value_name = "ALGO")]
+        hash: Option<String>,
+        #[command(flatten)]
+        sources: SourceArgs,
+        #[command(flatten)]
+        select: SelectArgs,
     },
 
     /// Print detailed info about a specific file in the archive
@@ -207,8 +422,11 @@ enum Commands {
         #[arg(value_name = "INDEX")]
         index: PathBuf,
         /// Glob or substring pattern
-        #[arg(value_name = "PATTERN")]
-        pattern: String,
+        #[arg(value_name = "PATTERN", required_unless_present = "hash")]
+        pattern: Option<String>,
+        /// Only entries with this digest (MD5, SHA-1, SHA-256 or BLAKE3, as recorded)
+        #[arg(long = "hash", value_name = "HEX")]
+        hash: Option<String>,
     },
 
     /// Show detailed statistics for an archive
@@ -217,6 +435,18 @@ enum Commands {
         index: PathBuf,
     },
 
+    /// Stream stdin into an existing archive as a new file
+    Add {
+        #[arg(value_name = "INDEX")]
+        index: PathBuf,
+        /// Path the stream is stored under in the archive
+        #[arg(long, value_name = "NAME")]
+        stdin_name: PathBuf,
+        /// Max size per archive part in GB (config default: 4.0)
+        #[arg(long, value_name = "GB")]
+        split_gb: Option<f64>,
+    },
+
     /// Incremental update: re-archive only changed/new files
     Update {
         /// Existing archive index to update from
@@ -236,13 +466,24 @@ enum Commands {
         compress: Option<String>,
         #[arg(long, value_name = "LEVEL")]
         zstd_level: Option<i32>,
-        #[arg(long, value_name = "PATTERN")]
-        exclude: Vec<String>,
+        #[command(flatten)]
+        sources: SourceArgs,
+        #[command(flatten)]
+        select: SelectArgs,
         #[arg(long, value_name = "N")]
         threads: Option<usize>,
         /// Use SHA-256 comparison to detect changes
         #[arg(long)]
         checksum: bool,
+        /// Byte-reproducible output: normalized tar headers, honors SOURCE_DATE_EPOCH
+        #[arg(long)]
+        reproducible: bool,
+        /// Exit with an error if a file changes while it is being archived
+        #[arg(long)]
+        fail_on_change: bool,
+        /// Re-read a file up to N times when it changes while being archived
+        #[arg(long, value_name = "N")]
+        retry_changed: Option<u32>,
     },
 
     /// Prune old archives in a directory
@@ -274,14 +515,42 @@ enum Commands {
         zstd_level: Option<i32>,
     },
 
+    /// Copy an archive to another directory or backend and record the replica
+    Sync {
+        /// Path to index.arc.json
+        #[arg(value_name = "INDEX")]
+        index: PathBuf,
+        /// Destination directory, HOST:PATH or s3://BUCKET/PREFIX
+        #[arg(value_name = "DEST")]
+        dest: PathBuf,
+    },
+
     /// Rebuild a missing index.arc.json from existing tar parts
     Repair {
         /// Directory containing the archive parts
         #[arg(value_name = "DIR")]
         dir: PathBuf,
-        /// Compression algorithm of the parts
-        #[arg(long, value_name = "ALGO", default_value = "zstd")]
-        compression: String,
+        /// Compression of the parts (detected from their contents if not given)
+        #[arg(long, value_name = "ALGO")]
+        compression: Option<String>,
+        /// Another directory of parts for this archive, e.g. an update's old parts (repeatable)
+        #[arg(long = "base", value_name = "DIR")]
+        bases: Vec<PathBuf>,
+        /// Read past damage in the parts; list lost files in REPORT
+        #[arg(
+            long,
+            value_name = "REPORT",
+            num_args = 0..=1,
+            default_missing_value = "salvage-report.json"
+        )]
+        salvage: Option<PathBuf>,
+    },
+
+    /// Serve archives on stdin/stdout for HOST:PATH clients (run by the transport)
+    Serve {
+        /// Speak the protocol on stdin/stdout (the only mode)
+        #[arg(long, required = true)]
+        stdio: bool,
     },
 
     /// Generate shell completion scripts
@@ -298,8 +567,15 @@ enum Commands {
     Config,
 }
 
+/// Exit code for a run that finished but skipped unreadable paths.
+const EXIT_PARTIAL: i32 = 3;
+
 fn main() {
     if let Err(e) = run() {
+        if let Some(partial) = e.downcast_ref::<scan::PartialSuccess>() {
+            eprintln!("{} {}", "warning:".yellow().bold(), partial);
+            std::process::exit(EXIT_PARTIAL);
+        }
         eprintln!("{} {}", "error:".red().bold(), e);
         for cause in e.chain().skip(1) {
             eprintln!("  {} {}", "caused by:".yellow(), cause);
@@ -322,16 +598,30 @@ fn run() -> Result<()> {
     match cli.command {
         Commands::Create {
             source,
+            sources,
+            stdin_name,
             output,
+            stdout,
             split_gb,
             split_files,
             compress,
             zstd_level,
-            mut exclude,
+            select,
             threads,
             dedup,
             notes,
+            reproducible,
+            fail_on_change,
+            retry_changed,
+            hash,
         } => {
+            // stdout carries the archive, so everything else goes to stderr
+            let out = if stdout { out.on_stderr() } else { out };
+            if stdout && !out.dry_run && std::io::stdout().is_terminal() {
+                anyhow::bail!(
+                    "Refusing to write an archive stream to a terminal; pipe or redirect stdout"
+                );
+            }
             utils::print_banner(&out);
 
             let compress_str = compress.as_deref().unwrap_or(&cfg.defaults.compress);
@@ -347,15 +637,33 @@ fn run() -> Result<()> {
             };
             let thread_count = threads.unwrap_or(cfg.defaults.threads);
             let do_dedup = dedup || cfg.create.dedup;
+            let do_repro = reproducible || cfg.create.reproducible;
+            let do_fail_change = fail_on_change || cfg.create.fail_on_change;
+            let retries = retry_changed.unwrap_or(cfg.create.retry_changed);
+            let hashes = checksum::HashAlgo::extras(if hash.is_empty() {
+                &cfg.create.hashes
+            } else {
+                &hash
+            })?;
 
-            let mut all_excludes = cfg.create.exclude.clone();
-            all_excludes.append(&mut exclude);
+            let selection = select.rules(&cfg).compile()?;
+            let sources = match &stdin_name {
+                Some(name) => {
+                    stream::check_name(name)?;
+                    sources.sources_with_stdin(&source)?
+                }
+                None => sources.sources(&source)?,
+            };
 
+            let dest = match &output {
+                Some(o) => o.display().to_string(),
+                None => "<stdout>".to_string(),
+            };
             out.println(&format!(
                 "{} {} -> {}",
                 "Creating archive:".cyan().bold(),
-                source.display().to_string().yellow(),
-                output.display().to_string().yellow()
+                sources.describe().yellow(),
+                dest.yellow()
             ));
             out.println(&format!(
                 "  split={:.1} GB  split-files={}  compress={}  zstd-level={}  dedup={}  threads={}",
@@ -369,19 +677,31 @@ fn run() -> Result<()> {
             out.println("");
 
             if out.dry_run {
-                out.dry(&format!("would scan: {}", source.display()));
-                out.dry(&format!("would create: {}", output.display()));
+                out.dry(&format!("would scan: {}", sources.describe()));
+                if let Some(name) = &stdin_name {
+                    out.dry(&format!("would stream stdin as: {}", name.display()));
+                }
+                out.dry(&format!("would create: {dest}"));
                 return Ok(());
             }
 
-            if !source.exists() {
-                anyhow::bail!("Source directory does not exist: {}", source.display());
-            }
+            sources.check_exist()?;
 
-            let scan = scan::scan_directory(&source, &all_excludes)
-                .with_context(|| format!("Failed to scan {}", source.display()))?;
+            let mut report = scan::ScanReport::default();
+            let scan = sources.scan(&selection, &mut report)?;
+            if let Some(name) = &stdin_name {
+                if scan.iter().any(|e| &e.relative_path == name) {
+                    anyhow::bail!("--stdin-name {} clashes with a source path", name.display());
+                }
+            }
 
             let mut idx = index::ArchivumIndex::build(scan, algo.clone(), zstd_lvl);
+            idx.record_scan(report);
+            idx.header.roots = sources.prefixes();
+            idx.header.hashes = hashes.iter().map(|a| a.name().to_string()).collect();
+            if do_repro {
+                idx.make_reproducible(utils::source_date_epoch());
+            }
 
             if let Some(n) = notes {
                 idx.header.notes = n;
@@ -389,10 +709,32 @@ fn run() -> Result<()> {
                 idx.header.notes = cfg.create.notes.clone();
             }
 
-            std::fs::create_dir_all(&output)
-                .with_context(|| format!("Failed to create output dir {}", output.display()))?;
+            // a remote OUTPUT gets each part uploaded as soon as it is
+            // written, so only one part at a time is staged locally
+            let target = match &output {
+                Some(o) => {
+                    let (store, dir) = store::Store::connect(o, &cfg)?;
+                    store.mkdir(&dir)?;
+                    // removed again however create ends, parts left over from
+                    // a failed upload included
+                    let (staging, cleanup) = if store.is_remote() {
+                        let s = utils::TempPath::new(
+                            std::env::temp_dir()
+                                .join(format!("archivum-upload-{}", std::process::id())),
+                        );
+                        std::fs::create_dir_all(s.path()).with_context(|| {
+                            format!("Failed to create staging dir {}", s.path().display())
+                        })?;
+                        (s.path().to_path_buf(), Some(s))
+                    } else {
+                        (dir.clone(), None)
+                    };
+                    Some((store, dir, staging, cleanup))
+                }
+                None => None,
+            };
 
-            checksum::compute_checksums(&source, &mut idx, thread_count)?;
+            checksum::compute_checksums(&sources, &mut idx, thread_count)?;
 
             if !do_dedup {
                 for e in idx.entries.iter_mut() {
@@ -400,10 +742,43 @@ fn run() -> Result<()> {
                 }
             }
 
-            tar_writer::write_archive(&source, &output, &mut idx, split, split_f, &algo, zstd_lvl)?;
+            let index_path = match &target {
+                Some((store, dir, staging, _cleanup)) => {
+                    tar_writer::write_archive(
+                        &sources,
+                        staging,
+                        &mut idx,
+                        split,
+                        split_f,
+                        &algo,
+                        zstd_lvl,
+                        retries,
+                        &mut |part| store.upload_part(part, dir),
+                    )?;
 
-            let index_path = output.join("index.arc.json");
-            idx.write(&index_path)?;
+                    // the stream gets a part of its own after the files
+                    if let Some(name) = &stdin_name {
+                        let part = idx.header.total_parts;
+                        let (entry, part_hashes) = stream::write_stream_parts(
+                            &mut std::io::stdin().lock(),
+                            name,
+                            staging,
+                            part,
+                            &idx,
+                            split,
+                            &mut |part| store.upload_part(part, dir),
+                        )?;
+                        stream::push_entry(&mut idx, entry, part_hashes);
+                    }
+                    let index_path = dir.join("index.arc.json");
+                    store.write_index(&mut idx, &index_path)?;
+                    store.location(&index_path)
+                }
+                None => {
+                    pipe::write_stream(&sources, &mut idx, &algo, zstd_lvl, retries)?;
+                    "(end of stream)".to_string()
+                }
+            };
 
             let deduped = idx.entries.iter().filter(|e| e.dedup_of.is_some()).count();
 
@@ -421,16 +796,24 @@ fn run() -> Result<()> {
                     String::new()
                 }
             ));
+            if !idx.errors.is_empty() {
+                out.println(&format!(
+                    "  Skipped: {} path(s) (run `archivum list` for details)",
+                    idx.errors.len().to_string().red()
+                ));
+            }
             out.println(&format!(
                 "  Size  : {}  |  Compression: {}",
                 utils::human(idx.header.total_size).cyan(),
                 algo.name().green()
             ));
-            out.println(&format!(
-                "  Index : {}",
-                index_path.display().to_string().cyan()
-            ));
+            out.println(&format!("  Index : {}", index_path.cyan()));
             out.println(&"─".repeat(60).dimmed().to_string());
+
+            tar_writer::check_changed(&idx, do_fail_change, &out)?;
+            if !idx.errors.is_empty() {
+                return Err(scan::PartialSuccess(idx.errors.len()).into());
+            }
         }
 
         Commands::List {
@@ -450,22 +833,112 @@ fn run() -> Result<()> {
         Commands::Restore {
             index,
             target,
+            stdin,
             filter,
+            root,
             force,
             restore_permissions,
+            salvage,
         } => {
             utils::print_banner(&out);
             let do_force = force || cfg.restore.force;
             let do_perm = restore_permissions || cfg.restore.restore_permissions;
-            restore::restore(&index, &target, filter.as_deref(), do_force, do_perm, &out)?;
+            if let Some(target) = stdin {
+                restore::restore_stream(
+                    &target,
+                    filter.as_deref(),
+                    root.as_deref(),
+                    do_force,
+                    do_perm,
+                    &out,
+                )?;
+            } else {
+                let (Some(index), Some(target)) = (index, target) else {
+                    anyhow::bail!("INDEX and TARGET are required");
+                };
+                let (store, index) = store::Store::connect(&index, &cfg)?;
+                restore::restore(
+                    &store,
+                    &index,
+                    &target,
+                    filter.as_deref(),
+                    root.as_deref(),
+                    do_force,
+                    do_perm,
+                    salvage.as_deref(),
+                    &out,
+                )?;
+            }
         }
 
         Commands::Verify {
             index,
             continue_on_error,
+            replicas,
+            quick,
+            threads,
+            against,
+            root,
+            by_hash,
+            hash,
+            journal,
+            prove,
         } => {
             utils::print_banner(&out);
-            verify::verify(&index, continue_on_error, &out)?;
+            let thread_count = threads.unwrap_or(cfg.defaults.threads);
+            let algos = if hash.is_empty() {
+                vec![checksum::HashAlgo::Sha256]
+            } else {
+                checksum::HashAlgo::list(&hash)?
+            };
+            let against = match against {
+                Some(source) => {
+                    if quick {
+                        anyhow::bail!(
+                            "--against reads every file; it cannot be combined with --quick"
+                        );
+                    }
+                    let sources = source::Sources::new(&source, &root)?;
+                    sources.check_exist()?;
+                    Some(verify::Against { sources, by_hash })
+                }
+                None => None,
+            };
+            let (store, index) = store::Store::connect(&index, &cfg)?;
+            if let Some(path) = prove {
+                verify::prove(&store, &index, &path, &out)?;
+            } else if quick {
+                verify::verify_quick(&store, &index, continue_on_error, journal, &out)?;
+            } else {
+                verify::verify(
+                    &store,
+                    &index,
+                    continue_on_error,
+                    thread_count,
+                    against.as_ref(),
+                    &algos,
+                    journal,
+                    &out,
+                )?;
+            }
+            if replicas {
+                sync::verify_replicas(
+                    &store,
+                    &index,
+                    continue_on_error,
+                    quick,
+                    thread_count,
+                    &algos,
+                    journal,
+                    &cfg,
+                    &out,
+                )?;
+            }
+        }
+
+        Commands::ScrubReport { index, last } => {
+            let (store, index) = store::Store::connect(&index, &cfg)?;
+            scrub::report(&store, &index, last, &out)?;
         }
 
         Commands::Diff {
@@ -473,24 +946,38 @@ fn run() -> Result<()> {
             source,
             changed_only,
             checksum,
+            hash,
+            sources,
+            select,
         } => {
-            let use_cs = checksum || cfg.update.checksum_diff;
-            diff::diff(&index, &source, changed_only, use_cs, &out)?;
+            let algo = match hash {
+                Some(h) => Some(checksum::HashAlgo::parse(&h)?),
+                None if checksum || cfg.update.checksum_diff => Some(checksum::HashAlgo::Sha256),
+                None => None,
+            };
+            let selection = select.rules(&cfg).compile()?;
+            let sources = sources.sources(&source)?;
+            diff::diff(&index, &sources, &selection, changed_only, algo, &out)?;
         }
 
         Commands::Info { index, file } => {
             let idx = index::ArchivumIndex::read(&index)?;
             if let Some(entry) = idx.entries.iter().find(|e| e.path == file) {
                 if out.json {
+                    use index::raw_path::RawPath;
                     let j = serde_json::json!({
-                        "path": entry.path,
+                        "path": RawPath(&entry.path),
                         "type": format!("{:?}", entry.entry_type),
                         "size": entry.size,
                         "sha256": entry.sha256,
+                        "hashes": entry.hashes,
                         "tar_part": entry.tar_part,
                         "mtime": entry.mtime,
                         "unix_mode": entry.unix_mode,
-                        "dedup_of": entry.dedup_of
+                        "dedup_of": entry.dedup_of.as_deref().map(RawPath),
+                        "dev_major": entry.dev_major,
+                        "dev_minor": entry.dev_minor,
+                        "changed_during_backup": entry.changed_during_backup
                     });
                     println!("{}", serde_json::to_string_pretty(&j).unwrap());
                 } else {
@@ -507,6 +994,12 @@ fn run() -> Result<()> {
                         "SHA-256:".cyan(),
                         entry.sha256.as_deref().unwrap_or("-").yellow()
                     );
+                    for (name, hex) in &entry.hashes {
+                        let label = checksum::HashAlgo::parse(name)
+                            .map(|a| a.label().to_string())
+                            .unwrap_or_else(|_| name.clone());
+                        println!("{} {}", format!("{}:", label).cyan(), hex.yellow());
+                    }
                     println!(
                         "{} {}",
                         "Tar part:".cyan(),
@@ -518,6 +1011,12 @@ fn run() -> Result<()> {
                     if let Some(mode) = entry.unix_mode {
                         println!("{} {:o}", "Mode:".cyan(), mode);
                     }
+                    if let (Some(major), Some(minor)) = (entry.dev_major, entry.dev_minor) {
+                        println!("{} {}, {}", "Device:".cyan(), major, minor);
+                    }
+                    if entry.changed_during_backup {
+                        println!("{} {}", "Status:".cyan(), "changed during backup".red());
+                    }
                     if let Some(ref orig) = entry.dedup_of {
                         println!(
                             "{} {}",
@@ -537,23 +1036,40 @@ fn run() -> Result<()> {
             file,
             output,
         } => {
-            let idx = index::ArchivumIndex::read(&index)?;
+            let (store, index) = store::Store::connect(&index, &cfg)?;
+            let idx = store.read_index(&index)?;
             let base = index.parent().unwrap_or(std::path::Path::new("."));
-            restore::extract_single(&idx, base, &file, output.as_deref(), &out)?;
+            restore::extract_single(&store, &idx, base, &file, output.as_deref(), &out)?;
         }
 
         Commands::Cat { index, file } => {
-            cat::cat(&index, &file)?;
+            let (store, index) = store::Store::connect(&index, &cfg)?;
+            cat::cat(&store, &index, &file)?;
         }
 
-        Commands::Search { index, pattern } => {
-            search::search(&index, &pattern, &out)?;
+        Commands::Search {
+            index,
+            pattern,
+            hash,
+        } => {
+            search::search(&index, pattern.as_deref(), hash.as_deref(), &out)?;
         }
 
         Commands::Stats { index } => {
             stats::stats(&index, &out)?;
         }
 
+        Commands::Add {
+            index,
+            stdin_name,
+            split_gb,
+        } => {
+            utils::print_banner(&out);
+            let split =
+                (split_gb.unwrap_or(cfg.defaults.split_gb) * 1024.0 * 1024.0 * 1024.0) as u64;
+            stream::add(&index, &stdin_name, split, &out)?;
+        }
+
         Commands::Update {
             old_index,
             source,
@@ -562,9 +1078,13 @@ fn run() -> Result<()> {
             split_files,
             compress,
             zstd_level,
-            mut exclude,
+            sources,
+            select,
             threads,
             checksum,
+            reproducible,
+            fail_on_change,
+            retry_changed,
         } => {
             utils::print_banner(&out);
             let compress_str = compress.as_deref().unwrap_or(&cfg.defaults.compress);
@@ -579,20 +1099,26 @@ fn run() -> Result<()> {
             };
             let thread_count = threads.unwrap_or(cfg.defaults.threads);
             let use_cs = checksum || cfg.update.checksum_diff;
-            let mut all_excludes = cfg.create.exclude.clone();
-            all_excludes.append(&mut exclude);
+            let do_repro = reproducible || cfg.create.reproducible;
+            let do_fail_change = fail_on_change || cfg.create.fail_on_change;
+            let retries = retry_changed.unwrap_or(cfg.create.retry_changed);
+            let selection = select.rules(&cfg).compile()?;
+            let sources = sources.sources(&source)?;
 
             update::update(
                 &old_index,
-                &source,
+                &sources,
                 &output,
                 split,
                 split_f,
                 &algo,
                 zstd_lvl,
                 thread_count,
-                &all_excludes,
+                &selection,
                 use_cs,
+                do_repro,
+                do_fail_change,
+                retries,
                 &out,
             )?;
         }
@@ -618,9 +1144,30 @@ fn run() -> Result<()> {
             merge::merge(&indexes, &output, split, &algo, zstd_lvl, &out)?;
         }
 
-        Commands::Repair { dir, compression } => {
+        Commands::Repair {
+            dir,
+            compression,
+            bases,
+            salvage,
+        } => {
+            utils::print_banner(&out);
+            repair::repair(
+                &dir,
+                compression.as_deref(),
+                &bases,
+                salvage.as_deref(),
+                &out,
+            )?;
+        }
+
+        Commands::Sync { index, dest } => {
             utils::print_banner(&out);
-            repair::repair(&dir, &compression, &out)?;
+            let (store, index) = store::Store::connect(&index, &cfg)?;
+            sync::sync(&store, &index, &dest, &cfg, &out)?;
+        }
+
+        Commands::Serve { stdio: _ } => {
+            remote::serve_stdio()?;
         }
 
         Commands::Completions { shell } => {
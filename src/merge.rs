@@ -23,32 +23,46 @@ use std::io::{self, copy};
 use std::path::{Path, PathBuf};
 
 use crate::compress::CompressionAlgo;
-use crate::index::{ArchivumIndex, INDEX_VERSION, IndexEntry, IndexHeader};
+use crate::index::{ArchivumIndex, INDEX_VERSION, IndexEntry, IndexHeader, PartHash};
 use crate::output::OutputCtx;
 use crate::scan::EntryType;
+use crate::tar_writer::{hash_part, is_manifest};
 use crate::utils::{fmt_time, now};
 
 struct PartWriter {
     builder: tar::Builder<Box<dyn io::Write>>,
     current_size: u64,
+    path: PathBuf,
+    part: u32,
 }
 
 impl PartWriter {
-    fn open(path: &Path, algo: &CompressionAlgo, zstd_level: i32) -> Result<Self> {
+    fn open(path: &Path, part: u32, algo: &CompressionAlgo, zstd_level: i32) -> Result<Self> {
         let f =
             fs::File::create(path).with_context(|| format!("Cannot create {}", path.display()))?;
         let writer: Box<dyn io::Write> = algo.wrap_writer(f, zstd_level)?;
         Ok(Self {
             builder: tar::Builder::new(writer),
             current_size: 0,
+            path: path.to_path_buf(),
+            part,
         })
     }
 
-    fn finish(mut self) -> Result<()> {
+    /// Close the part and hash the finished file.
+    fn finish(mut self) -> Result<PartHash> {
         self.builder
             .finish()
             .context("Failed to finalize tar part")?;
-        Ok(())
+        let Self {
+            builder,
+            path,
+            part,
+            ..
+        } = self;
+        // dropping the encoder writes its trailer
+        drop(builder);
+        hash_part(&path, None, part)
     }
 }
 
@@ -83,6 +97,8 @@ pub fn merge(
     let mut work_list: Vec<(PathBuf, IndexEntry)> = vec![];
     let mut seen_paths: HashSet<PathBuf> = HashSet::new();
     let mut total_skipped = 0usize;
+    // only digests every input carries can be promised for all entries
+    let mut hashes: Option<Vec<String>> = None;
 
     for idx_path in index_paths {
         let idx = ArchivumIndex::read(idx_path)
@@ -94,6 +110,13 @@ pub fn merge(
             idx_path.display().to_string().yellow(),
             idx.header.total_files
         ));
+        hashes = Some(match hashes {
+            None => idx.header.hashes.clone(),
+            Some(h) => h
+                .into_iter()
+                .filter(|n| idx.header.hashes.contains(n))
+                .collect(),
+        });
 
         for entry in idx.entries {
             if entry.entry_type != EntryType::File || entry.dedup_of.is_some() {
@@ -126,60 +149,71 @@ pub fn merge(
     let mut new_entries: Vec<IndexEntry> = vec![];
 
     let first_path = output_dir.join(format!("data.part{:03}{}", current_part, ext));
-    let mut pw = PartWriter::open(&first_path, algo, zstd_level)?;
+    let mut pw = PartWriter::open(&first_path, current_part, algo, zstd_level)?;
+    let mut part_hashes = vec![];
 
     for (src_dir, mut entry) in work_list {
-        let src_part_path = src_dir.join(format!(
-            "data.part{:03}{}",
-            entry.tar_part,
-            algo.extension()
-        ));
-
         let overhead = 512 + entry.size.div_ceil(512) * 512;
 
         if pw.current_size > 0 && pw.current_size + overhead > split_bytes {
-            pw.finish()?;
+            part_hashes.push(pw.finish()?);
             current_part += 1;
             let next_path = output_dir.join(format!("data.part{:03}{}", current_part, ext));
-            pw = PartWriter::open(&next_path, algo, zstd_level)?;
+            pw = PartWriter::open(&next_path, current_part, algo, zstd_level)?;
         }
 
-        if let Ok(reader) = algo.wrap_reader(&src_part_path) {
+        // a stream that spans parts is joined back into a single member
+        let mut buf: Vec<u8> = Vec::with_capacity(entry.size as usize);
+        let mut found = false;
+        for piece in entry.members() {
+            let src_part_path = src_dir.join(format!(
+                "data.part{:03}{}",
+                piece.tar_part,
+                algo.extension()
+            ));
+            let Ok(reader) = algo.wrap_reader(&src_part_path) else {
+                continue;
+            };
             let mut src_archive = tar::Archive::new(reader);
-            if let Ok(entries_iter) = src_archive.entries() {
-                for item in entries_iter.flatten() {
-                    let mut item = item;
-                    let matches = item
+            let Ok(entries_iter) = src_archive.entries() else {
+                continue;
+            };
+            for item in entries_iter.flatten() {
+                let mut item = item;
+                let matches = !is_manifest(&mut item).unwrap_or(true)
+                    && item
                         .path()
                         .map(|p| p.as_ref() == entry.path.as_path())
                         .unwrap_or(false);
-                    if matches {
-                        let mut buf: Vec<u8> = Vec::with_capacity(entry.size as usize);
-                        copy(&mut item, &mut buf)?;
-
-                        let mut header = tar::Header::new_gnu();
-                        header.set_path(&entry.path)?;
-                        header.set_size(buf.len() as u64);
-                        header.set_mode(entry.unix_mode.unwrap_or(0o644));
-                        if let Some(mtime) = entry.mtime {
-                            header.set_mtime(mtime);
-                        }
-                        header.set_cksum();
-
-                        pw.builder.append(&header, &mut io::Cursor::new(&buf))?;
-                        pw.current_size += overhead;
-                        break;
-                    }
+                if matches {
+                    copy(&mut item, &mut buf)?;
+                    found = true;
+                    break;
                 }
             }
         }
 
+        if found {
+            let mut header = tar::Header::new_gnu();
+            header.set_path(&entry.path)?;
+            header.set_size(buf.len() as u64);
+            header.set_mode(entry.unix_mode.unwrap_or(0o644));
+            if let Some(mtime) = entry.mtime {
+                header.set_mtime(mtime);
+            }
+            header.set_cksum();
+
+            pw.builder.append(&header, &mut io::Cursor::new(&buf))?;
+            pw.current_size += overhead;
+        }
+
+        entry.pieces.clear();
         entry.tar_part = current_part;
         entry.tar_base = None;
         new_entries.push(entry);
     }
 
-    pw.finish()?;
+    part_hashes.push(pw.finish()?);
 
     let total_parts = current_part + 1;
     let total_files = new_entries.len() as u64;
@@ -193,7 +227,7 @@ pub fn merge(
     ));
 
     let ts = now();
-    let merged_idx = ArchivumIndex {
+    let mut merged_idx = ArchivumIndex {
         header: IndexHeader {
             version: INDEX_VERSION,
             created_at_unix: ts,
@@ -201,15 +235,24 @@ pub fn merge(
             total_files,
             total_dirs: 0,
             total_symlinks: 0,
+            total_special: 0,
             total_size,
             total_parts,
             compression: algo.clone(),
             zstd_level,
             notes: format!("Merged from {} archives", index_paths.len()),
             part_bases: vec![String::new()],
+            reproducible: false,
+            source_date_epoch: None,
+            ignore_sources: vec![],
+            roots: vec![],
+            part_hashes,
+            merkle_root: None,
+            hashes: hashes.unwrap_or_default(),
             _integrity: None,
         },
         entries: new_entries,
+        errors: vec![],
     };
 
     let index_path = output_dir.join("index.arc.json");
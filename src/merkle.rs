@@ -0,0 +1,294 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Content fingerprint: a BLAKE3 Merkle tree over one record per entry,
+// sorted by path. The root identifies what an archive holds independent of
+// how the index is formatted, split or annotated; two archives with the same
+// root hold the same paths with the same types, permissions and content.
+//
+// A record is `path NUL type NUL mode NUL content`: the raw path bytes, the
+// entry type's name, the permission bits in octal (`-` if unknown) and, for
+// a file its SHA-256, for a symlink its target, for a device node
+// `major,minor`, and nothing otherwise. Leaves are BLAKE3(0x00 || record),
+// inner nodes BLAKE3(0x01 || left || right); a node without a sibling moves
+// up a level unchanged. An archive with no entries has BLAKE3("") as root.
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::index::IndexEntry;
+use crate::scan::EntryType;
+
+type Hash = [u8; 32];
+
+/// The record an entry contributes to the tree.
+pub fn record(e: &IndexEntry) -> Vec<u8> {
+    let mode = match e.unix_mode {
+        Some(m) => format!("{:o}", m & 0o7777),
+        None => "-".into(),
+    };
+    let content: Vec<u8> = match e.entry_type {
+        EntryType::File => e.sha256.clone().unwrap_or_default().into_bytes(),
+        EntryType::Symlink => e
+            .symlink_target
+            .as_deref()
+            .map(|t| t.as_os_str().as_encoded_bytes().to_vec())
+            .unwrap_or_default(),
+        EntryType::CharDevice | EntryType::BlockDevice => {
+            format!("{},{}", e.dev_major.unwrap_or(0), e.dev_minor.unwrap_or(0)).into_bytes()
+        }
+        EntryType::Directory | EntryType::Fifo => vec![],
+    };
+    let mut r = e.path.as_os_str().as_encoded_bytes().to_vec();
+    for field in [e.entry_type.name().as_bytes(), mode.as_bytes(), &content] {
+        r.push(0);
+        r.extend_from_slice(field);
+    }
+    r
+}
+
+fn leaf(record: &[u8]) -> Hash {
+    let mut h = blake3::Hasher::new();
+    h.update(&[0]);
+    h.update(record);
+    *h.finalize().as_bytes()
+}
+
+fn node(left: &Hash, right: &Hash) -> Hash {
+    let mut h = blake3::Hasher::new();
+    h.update(&[1]);
+    h.update(left);
+    h.update(right);
+    *h.finalize().as_bytes()
+}
+
+/// Leaf hashes of `entries` in path order, with the entries.
+fn leaves(entries: &[IndexEntry]) -> Vec<(&IndexEntry, Hash)> {
+    let mut sorted: Vec<&IndexEntry> = entries.iter().collect();
+    sorted.sort_by(|a, b| {
+        a.path
+            .as_os_str()
+            .as_encoded_bytes()
+            .cmp(b.path.as_os_str().as_encoded_bytes())
+    });
+    sorted.into_iter().map(|e| (e, leaf(&record(e)))).collect()
+}
+
+/// One level up: pairs hashed together, a lone last node carried over.
+fn level_up(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [l, r] => node(l, r),
+            [one] => *one,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn hex(h: &Hash) -> String {
+    blake3::Hash::from(*h).to_hex().to_string()
+}
+
+/// Root of the tree over `entries`, in hex.
+pub fn root(entries: &[IndexEntry]) -> String {
+    let mut level: Vec<Hash> = leaves(entries).into_iter().map(|(_, h)| h).collect();
+    if level.is_empty() {
+        return blake3::hash(b"").to_hex().to_string();
+    }
+    while level.len() > 1 {
+        level = level_up(&level);
+    }
+    hex(&level[0])
+}
+
+/// One sibling on the way from a leaf to the root.
+#[derive(Debug, Serialize)]
+pub struct Step {
+    /// which side of the running hash the sibling goes on
+    pub side: &'static str,
+    pub hash: String,
+}
+
+/// That `path` is in the tree with root `root`: hash `record` as a leaf,
+/// fold in each step's sibling, and the result is the root.
+#[derive(Debug, Serialize)]
+pub struct Proof {
+    #[serde(with = "crate::index::raw_path")]
+    pub path: std::path::PathBuf,
+    /// the leaf record; NUL-separated, so shown with `\u0000` in JSON
+    pub record: String,
+    pub leaf: String,
+    /// position of the leaf among `leaves` in path order
+    pub index: usize,
+    pub leaves: usize,
+    pub steps: Vec<Step>,
+    pub root: String,
+}
+
+impl Proof {
+    /// Recompute the root from the record and the steps.
+    pub fn check(&self, record: &[u8]) -> bool {
+        let mut h = leaf(record);
+        if hex(&h) != self.leaf {
+            return false;
+        }
+        for step in &self.steps {
+            let Some(sibling) = blake3::Hash::from_hex(&step.hash).ok() else {
+                return false;
+            };
+            h = match step.side {
+                "left" => node(sibling.as_bytes(), &h),
+                _ => node(&h, sibling.as_bytes()),
+            };
+        }
+        hex(&h) == self.root
+    }
+}
+
+/// An inclusion proof for the entry at `path`, if there is one.
+pub fn prove(entries: &[IndexEntry], path: &Path) -> Option<Proof> {
+    let leaves = leaves(entries);
+    let mut i = leaves.iter().position(|(e, _)| e.path == path)?;
+    let (entry, leaf_hash) = leaves[i];
+    let index = i;
+
+    let mut level: Vec<Hash> = leaves.iter().map(|(_, h)| *h).collect();
+    let mut steps = vec![];
+    while level.len() > 1 {
+        let sibling = i ^ 1;
+        if sibling < level.len() {
+            steps.push(Step {
+                side: if sibling < i { "left" } else { "right" },
+                hash: hex(&level[sibling]),
+            });
+        }
+        level = level_up(&level);
+        i /= 2;
+    }
+
+    Some(Proof {
+        path: entry.path.clone(),
+        record: String::from_utf8_lossy(&record(entry)).into_owned(),
+        leaf: hex(&leaf_hash),
+        index,
+        leaves: leaves.len(),
+        steps,
+        root: hex(&level[0]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    fn file(path: &str, sha: &str) -> IndexEntry {
+        IndexEntry {
+            path: PathBuf::from(path),
+            entry_type: EntryType::File,
+            size: 3,
+            mtime: Some(1),
+            unix_mode: Some(0o100644),
+            sha256: Some(sha.into()),
+            hashes: BTreeMap::new(),
+            tar_part: 0,
+            symlink_target: None,
+            tar_base: None,
+            dedup_of: None,
+            dev_major: None,
+            dev_minor: None,
+            changed_during_backup: false,
+            from_stdin: false,
+            pieces: vec![],
+        }
+    }
+
+    fn files(n: usize) -> Vec<IndexEntry> {
+        (0..n)
+            .map(|i| file(&format!("f{i:02}"), &format!("{i:064x}")))
+            .collect()
+    }
+
+    #[test]
+    fn records_and_small_roots_follow_the_documented_layout() {
+        let a = file("a.txt", "abc");
+        assert_eq!(record(&a), b"a.txt\x00file\x00644\x00abc");
+        let mut h = blake3::Hasher::new();
+        h.update(b"\x00a.txt\x00file\x00644\x00abc");
+        assert_eq!(
+            root(std::slice::from_ref(&a)),
+            h.finalize().to_hex().as_str()
+        );
+        assert_eq!(root(&[]), blake3::hash(b"").to_hex().as_str());
+
+        let link = IndexEntry {
+            entry_type: EntryType::Symlink,
+            unix_mode: None,
+            sha256: None,
+            symlink_target: Some(PathBuf::from("a.txt")),
+            ..file("l", "")
+        };
+        assert_eq!(record(&link), b"l\0symlink\0-\0a.txt");
+    }
+
+    #[test]
+    fn every_leaf_proves_whatever_the_tree_size() {
+        for n in 1..=9 {
+            let entries = files(n);
+            let want = root(&entries);
+            for e in &entries {
+                let proof = prove(&entries, &e.path).unwrap();
+                assert_eq!(proof.root, want, "{n} leaves");
+                assert_eq!(proof.leaves, n);
+                assert!(proof.check(&record(e)), "{} of {n}", e.path.display());
+                let mut other = e.clone();
+                other.sha256 = Some("e".repeat(64));
+                assert!(!proof.check(&record(&other)));
+            }
+            assert!(prove(&entries, Path::new("absent")).is_none());
+        }
+    }
+
+    #[test]
+    fn root_ignores_order_and_storage_but_not_content() {
+        let entries = files(5);
+        let want = root(&entries);
+        let mut reversed = entries.clone();
+        reversed.reverse();
+        assert_eq!(root(&reversed), want);
+
+        let mut stored = entries.clone();
+        stored[2].tar_part = 7;
+        stored[2].mtime = Some(99);
+        stored[2].tar_base = Some(1);
+        assert_eq!(root(&stored), want);
+
+        let changes: [fn(&mut IndexEntry); 4] = [
+            |e| e.sha256 = Some("f".repeat(64)),
+            |e| e.unix_mode = Some(0o100600),
+            |e| e.path = PathBuf::from("renamed"),
+            |e| e.entry_type = EntryType::Fifo,
+        ];
+        for change in changes {
+            let mut changed = entries.clone();
+            change(&mut changed[2]);
+            assert_ne!(root(&changed), want);
+        }
+    }
+}
@@ -28,6 +28,7 @@ pub struct OutputCtx {
     pub quiet: bool,
     pub dry_run: bool,
     log: Option<Arc<Mutex<File>>>,
+    stderr: bool,
 }
 
 impl OutputCtx {
@@ -47,12 +48,19 @@ impl OutputCtx {
             quiet,
             dry_run,
             log,
+            stderr: false,
         })
     }
 
+    /// Send regular output to stderr, for commands whose stdout is data.
+    pub fn on_stderr(mut self) -> Self {
+        self.stderr = true;
+        self
+    }
+
     pub fn println(&self, line: &str) {
         if !self.quiet {
-            println!("{}", line);
+            self.emit(line);
         }
         self.write_log(line);
     }
@@ -64,11 +72,19 @@ impl OutputCtx {
 
     pub fn dry(&self, line: &str) {
         if !self.quiet {
-            println!("[dry-run] {}", line);
+            self.emit(&format!("[dry-run] {}", line));
         }
         self.write_log(&format!("[dry-run] {}", line));
     }
 
+    fn emit(&self, line: &str) {
+        if self.stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
     fn write_log(&self, line: &str) {
         if let Some(log) = &self.log {
             let plain = strip_ansi(line);
@@ -0,0 +1,222 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Single-stream archives for pipes (`create --stdout`, `restore --stdin`).
+//
+// ```text
+// "ARCVSTR1"  u32 len  header JSON
+// u32 len  chunk ... u32 0            compressed tar, chunked
+// u64 len  index JSON  blake3 (32 bytes)  "ARCVEND1"
+// ```
+//
+// All integers are little-endian. The chunk framing lets a reader find the
+// end of the compressed tar without seeking or trusting the decompressor
+// to stop exactly at its frame end.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufWriter, Read, Write};
+
+use crate::compress::CompressionAlgo;
+use crate::index::ArchivumIndex;
+use crate::source::Sources;
+use crate::tar_writer::write_single_stream;
+
+const MAGIC: &[u8; 8] = b"ARCVSTR1";
+const END_MAGIC: &[u8; 8] = b"ARCVEND1";
+const FORMAT: u32 = 1;
+const CHUNK: usize = 1 << 16;
+
+/// What a reader needs before the tar data starts.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamHeader {
+    pub format: u32,
+    pub compression: CompressionAlgo,
+    pub created_at_unix: u64,
+    pub archivum: String,
+    /// root prefixes, so `restore --stdin --root` can be checked up front
+    #[serde(default)]
+    pub roots: Vec<String>,
+}
+
+/// Write header, tar data and trailing index to stdout.
+pub fn write_stream(
+    sources: &Sources,
+    idx: &mut ArchivumIndex,
+    algo: &CompressionAlgo,
+    zstd_level: i32,
+    retries: u32,
+) -> Result<()> {
+    let mut stdout = io::stdout();
+    let header = StreamHeader {
+        format: FORMAT,
+        compression: algo.clone(),
+        created_at_unix: idx.header.created_at_unix,
+        archivum: env!("CARGO_PKG_VERSION").to_string(),
+        roots: idx.header.roots.clone(),
+    };
+    let json = serde_json::to_vec(&header)?;
+    stdout.write_all(MAGIC)?;
+    stdout.write_all(&(json.len() as u32).to_le_bytes())?;
+    stdout.write_all(&json)?;
+
+    let body = BufWriter::with_capacity(CHUNK, ChunkWriter(io::stdout()));
+    write_single_stream(sources, idx, body, algo, zstd_level, retries)?;
+
+    stdout.write_all(&0u32.to_le_bytes())?;
+    idx.finish_entries();
+    let (json, _) = idx.to_bytes()?;
+    stdout.write_all(&(json.len() as u64).to_le_bytes())?;
+    stdout.write_all(&json)?;
+    stdout.write_all(blake3::hash(&json).as_bytes())?;
+    stdout.write_all(END_MAGIC)?;
+    stdout.flush().context("Failed to write archive stream")?;
+    Ok(())
+}
+
+/// Read and check the stream header.
+pub fn read_header<R: Read>(r: &mut R) -> Result<StreamHeader> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)
+        .context("Stream is empty or truncated")?;
+    if &magic != MAGIC {
+        bail!("Not an archivum stream (bad magic); was it written by `create --stdout`?");
+    }
+    let len = read_u32(r)?;
+    let json = read_block(r, len as u64)?;
+    let header: StreamHeader = serde_json::from_slice(&json).context("Corrupt stream header")?;
+    if header.format != FORMAT {
+        bail!("Unsupported stream format {}", header.format);
+    }
+    Ok(header)
+}
+
+/// Read the trailing index once the chunked body has been consumed, and
+/// check it against its BLAKE3 hash.
+pub fn read_trailer<R: Read>(r: &mut R) -> Result<ArchivumIndex> {
+    let mut len = [0u8; 8];
+    r.read_exact(&mut len)
+        .context("Stream truncated before the index")?;
+    let json = read_block(r, u64::from_le_bytes(len))?;
+    let mut hash = [0u8; 32];
+    r.read_exact(&mut hash)
+        .context("Stream truncated inside the index")?;
+    let mut end = [0u8; 8];
+    r.read_exact(&mut end)
+        .context("Stream truncated after the index")?;
+    if &end != END_MAGIC {
+        bail!("Stream trailer is corrupt (bad end marker)");
+    }
+    if blake3::hash(&json).as_bytes() != &hash {
+        bail!("Index integrity check FAILED: stream index does not match its BLAKE3 hash");
+    }
+    serde_json::from_slice(&json).context("Corrupt stream index")
+}
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b).context("Stream truncated")?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_block<R: Read>(r: &mut R, len: u64) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        bail!("Stream truncated");
+    }
+    Ok(buf)
+}
+
+/// Every write becomes one length-prefixed chunk; `end` writes the
+/// zero-length terminator.
+pub struct ChunkWriter<W: Write>(pub W);
+
+impl<W: Write> ChunkWriter<W> {
+    pub fn end(mut self) -> io::Result<W> {
+        self.0.write_all(&0u32.to_le_bytes())?;
+        Ok(self.0)
+    }
+}
+
+impl<W: Write> Write for ChunkWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = buf.len().min(u32::MAX as usize);
+        self.0.write_all(&(n as u32).to_le_bytes())?;
+        self.0.write_all(&buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Reads chunk payloads back to back and reports EOF at the zero-length
+/// terminator, leaving the inner reader positioned at the trailer.
+pub struct ChunkReader<R: Read> {
+    inner: R,
+    remaining: u32,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    /// Skip whatever the decompressor left unread (tar padding, frame
+    /// footers) up to the terminator and hand back the inner reader.
+    pub fn finish(mut self) -> Result<R> {
+        io::copy(&mut self, &mut io::sink()).context("Stream truncated inside the data")?;
+        Ok(self.inner)
+    }
+}
+
+impl<R: Read> Read for ChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            let mut len = [0u8; 4];
+            self.inner.read_exact(&mut len)?;
+            self.remaining = u32::from_le_bytes(len);
+            if self.remaining == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let want = buf.len().min(self.remaining as usize);
+        let n = self.inner.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stream ended inside a chunk",
+            ));
+        }
+        self.remaining -= n as u32;
+        Ok(n)
+    }
+}
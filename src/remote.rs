@@ -0,0 +1,392 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Command-transport remotes: `HOST:PATH` archives are reached by running
+// `archivum serve --stdio` on HOST through a transport command (ssh by
+// default) and talking to it over its stdin/stdout.
+//
+// Every request and reply is a u32 length + JSON object. File data follows
+// the reply (get) or the request (put) as chunks framed as in pipe.rs. One
+// request is in flight at a time.
+
+use anyhow::{Context, Result, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::MutexGuard;
+
+use crate::config::RemoteConfig;
+use crate::index::raw_path;
+use crate::pipe::{ChunkReader, ChunkWriter};
+use crate::store::open_range;
+
+const PROTOCOL: u32 = 1;
+const CHUNK: usize = 1 << 16;
+/// Longest message accepted. Requests and replies are small; a larger
+/// length is text from something else on the line, e.g. a login banner.
+const MAX_MESSAGE: usize = 16 << 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Hello {
+        protocol: u32,
+    },
+    /// size of a regular file, or none if it does not exist
+    Stat {
+        #[serde(with = "raw_path")]
+        path: PathBuf,
+    },
+    /// `len` bytes from `offset` (to the end if unset), sent after the reply
+    Get {
+        #[serde(with = "raw_path")]
+        path: PathBuf,
+        offset: u64,
+        len: Option<u64>,
+    },
+    /// the file's content follows the request
+    Put {
+        #[serde(with = "raw_path")]
+        path: PathBuf,
+    },
+    Mkdir {
+        #[serde(with = "raw_path")]
+        path: PathBuf,
+    },
+    Bye,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Reply {
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    size: Option<u64>,
+}
+
+impl Reply {
+    fn err(e: impl std::fmt::Display) -> Self {
+        Self {
+            error: Some(e.to_string()),
+            size: None,
+        }
+    }
+
+    fn size(size: Option<u64>) -> Self {
+        Self { error: None, size }
+    }
+}
+
+/// Split `HOST:PATH`, scp style. Paths that exist locally, and ones whose
+/// part before the first `:` contains a `/` or is a single letter (a
+/// Windows drive), stay local.
+pub fn parse_remote(arg: &Path) -> Option<(String, PathBuf)> {
+    if arg.exists() {
+        return None;
+    }
+    let text = arg.to_str()?;
+    let (host, path) = text.split_once(':')?;
+    if host.len() < 2 || host.contains('/') || path.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), PathBuf::from(path)))
+}
+
+// ── server ─────────────────────────────────────────────────────────────────
+
+/// `serve --stdio`: answer requests on stdin until `bye` or EOF. Paths are
+/// taken relative to the working directory, with the user's permissions.
+pub fn serve_stdio() -> Result<()> {
+    let mut rx = BufReader::new(io::stdin().lock());
+    let mut tx = BufWriter::new(io::stdout().lock());
+
+    while let Some(req) = recv(&mut rx)? {
+        match req {
+            Request::Hello { protocol } => {
+                if protocol == PROTOCOL {
+                    send(&mut tx, &Reply::default())?;
+                } else {
+                    send(
+                        &mut tx,
+                        &Reply::err(format!(
+                            "protocol {protocol} not supported (server speaks {PROTOCOL})"
+                        )),
+                    )?;
+                }
+            }
+            Request::Stat { path } => {
+                let size = fs::metadata(&path)
+                    .ok()
+                    .filter(|m| m.is_file())
+                    .map(|m| m.len());
+                send(&mut tx, &Reply::size(size))?;
+            }
+            Request::Get { path, offset, len } => match open_range(&path, offset, len) {
+                Ok((file, size)) => {
+                    send(&mut tx, &Reply::size(Some(size)))?;
+                    // a read error ends the data early; the client sees a
+                    // short read against `size`
+                    let mut body = BufWriter::with_capacity(CHUNK, ChunkWriter(&mut tx));
+                    let _ = io::copy(&mut file.take(size), &mut body);
+                    let chunks = body.into_inner().map_err(|e| e.into_error())?;
+                    chunks.end()?;
+                }
+                Err(e) => send(&mut tx, &Reply::err(format!("{}: {}", path.display(), e)))?,
+            },
+            Request::Put { path } => {
+                let reply = match receive_file(&mut rx, &path)? {
+                    Ok(n) => Reply::size(Some(n)),
+                    Err(e) => Reply::err(format!("{}: {}", path.display(), e)),
+                };
+                send(&mut tx, &reply)?;
+            }
+            Request::Mkdir { path } => {
+                let reply = match fs::create_dir_all(&path) {
+                    Ok(()) => Reply::default(),
+                    Err(e) => Reply::err(format!("{}: {}", path.display(), e)),
+                };
+                send(&mut tx, &reply)?;
+            }
+            Request::Bye => return Ok(()),
+        }
+        tx.flush()?;
+    }
+    Ok(())
+}
+
+/// Store an uploaded file through a temporary name, so a broken upload
+/// never leaves a half-written part under the real name. The outer error
+/// is a protocol failure; the inner one goes back to the client.
+fn receive_file<R: Read>(rx: &mut R, path: &Path) -> Result<io::Result<u64>> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".upload");
+    let tmp = PathBuf::from(tmp);
+
+    let mut chunks = ChunkReader::new(rx);
+    let result = File::create(&tmp).and_then(|f| {
+        let mut w = BufWriter::new(f);
+        let n = io::copy(&mut chunks, &mut w)?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(n)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    // whatever was not consumed still has to come off the wire
+    chunks.finish()?;
+    Ok(result)
+}
+
+fn send<W: Write, T: Serialize>(w: &mut W, msg: &T) -> Result<()> {
+    let json = serde_json::to_vec(msg)?;
+    w.write_all(&(json.len() as u32).to_le_bytes())?;
+    w.write_all(&json)?;
+    Ok(())
+}
+
+/// Next message, or `None` on a clean EOF between messages.
+fn recv<R: Read, T: DeserializeOwned>(r: &mut R) -> Result<Option<T>> {
+    let mut len = [0u8; 4];
+    let mut got = 0;
+    while got < len.len() {
+        match r.read(&mut len[got..])? {
+            0 if got == 0 => return Ok(None),
+            0 => bail!("connection closed inside a message"),
+            n => got += n,
+        }
+    }
+    let size = u32::from_le_bytes(len) as usize;
+    if size > MAX_MESSAGE {
+        bail!(
+            "message of {size} bytes ({:?}...): not an archivum serve --stdio endpoint",
+            String::from_utf8_lossy(&len)
+        );
+    }
+    let mut json = vec![0u8; size];
+    r.read_exact(&mut json)
+        .context("connection closed inside a message")?;
+    Ok(Some(
+        serde_json::from_slice(&json).context("malformed message")?,
+    ))
+}
+
+// ── client ─────────────────────────────────────────────────────────────────
+
+/// A connection to `archivum serve --stdio` on HOST.
+pub struct Remote {
+    host: String,
+    child: Child,
+    tx: BufWriter<ChildStdin>,
+    rx: BufReader<ChildStdout>,
+}
+
+impl Remote {
+    /// Start the transport (`ARCHIVUM_RSH`, else `remote.transport`) and
+    /// check the protocol version.
+    pub fn connect(host: &str, cfg: &RemoteConfig) -> Result<Self> {
+        let transport = std::env::var("ARCHIVUM_RSH").unwrap_or_else(|_| cfg.transport.clone());
+        let mut cmd = if transport == "local" {
+            Command::new(std::env::current_exe()?)
+        } else {
+            let mut words = transport.split_whitespace();
+            let program = words.next().context("remote.transport is empty")?;
+            let mut c = Command::new(program);
+            c.args(words).arg(host).arg(&cfg.program);
+            c
+        };
+        cmd.args(["serve", "--stdio"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit());
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Cannot start transport '{transport}' for {host}"))?;
+
+        let mut remote = Self {
+            host: host.to_string(),
+            tx: BufWriter::new(child.stdin.take().context("transport has no stdin")?),
+            rx: BufReader::new(child.stdout.take().context("transport has no stdout")?),
+            child,
+        };
+        remote
+            .call(&Request::Hello { protocol: PROTOCOL })
+            .with_context(|| format!("Cannot talk to archivum on {host}"))?;
+        Ok(remote)
+    }
+
+    fn call(&mut self, req: &Request) -> Result<Reply> {
+        send(&mut self.tx, req)?;
+        self.tx.flush()?;
+        self.reply()
+    }
+
+    fn reply(&mut self) -> Result<Reply> {
+        let reply: Reply =
+            recv(&mut self.rx)?.with_context(|| format!("{}: connection closed", self.host))?;
+        if let Some(e) = reply.error {
+            bail!("{}: {}", self.host, e);
+        }
+        Ok(reply)
+    }
+
+    pub fn stat(&mut self, path: &Path) -> Result<Option<u64>> {
+        Ok(self
+            .call(&Request::Stat {
+                path: path.to_path_buf(),
+            })?
+            .size)
+    }
+
+    pub fn mkdir(&mut self, path: &Path) -> Result<()> {
+        self.call(&Request::Mkdir {
+            path: path.to_path_buf(),
+        })?;
+        Ok(())
+    }
+
+    /// Upload `data` as `path`, returning the byte count the server stored.
+    pub fn put(&mut self, data: &mut dyn Read, path: &Path) -> Result<u64> {
+        send(
+            &mut self.tx,
+            &Request::Put {
+                path: path.to_path_buf(),
+            },
+        )?;
+        let mut body = BufWriter::with_capacity(CHUNK, ChunkWriter(&mut self.tx));
+        io::copy(data, &mut body)
+            .with_context(|| format!("Upload of {} failed", path.display()))?;
+        body.into_inner().map_err(|e| e.into_error())?.end()?;
+        self.tx.flush()?;
+        Ok(self.reply()?.size.unwrap_or(0))
+    }
+}
+
+impl Drop for Remote {
+    fn drop(&mut self) {
+        let _ = send(&mut self.tx, &Request::Bye);
+        let _ = self.tx.flush();
+        let _ = self.child.wait();
+    }
+}
+
+/// File data coming back from a `get`. Holds the connection until dropped;
+/// whatever is left unread is drained then so the next request lines up.
+struct RemoteRead<'a> {
+    chunks: ChunkReader<ConnRx<'a>>,
+    expected: u64,
+    got: u64,
+}
+
+struct ConnRx<'a>(MutexGuard<'a, Remote>);
+
+impl Read for ConnRx<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.rx.read(buf)
+    }
+}
+
+impl Read for RemoteRead<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.chunks.read(buf)?;
+        self.got += n as u64;
+        if n == 0 && !buf.is_empty() && self.got != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "remote read ended after {} of {} bytes",
+                    self.got, self.expected
+                ),
+            ));
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for RemoteRead<'_> {
+    fn drop(&mut self) {
+        let _ = io::copy(&mut self.chunks, &mut io::sink());
+    }
+}
+
+/// `len` bytes of `path` from `offset` (to the end if unset). The reader
+/// holds the connection until it is dropped.
+pub fn get<'a>(
+    mut conn: MutexGuard<'a, Remote>,
+    path: &Path,
+    offset: u64,
+    len: Option<u64>,
+) -> Result<Box<dyn Read + 'a>> {
+    send(
+        &mut conn.tx,
+        &Request::Get {
+            path: path.to_path_buf(),
+            offset,
+            len,
+        },
+    )?;
+    conn.tx.flush()?;
+    let expected = conn.reply()?.size.unwrap_or(0);
+    Ok(Box::new(RemoteRead {
+        chunks: ChunkReader::new(ConnRx(conn)),
+        expected,
+        got: 0,
+    }))
+}
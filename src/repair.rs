@@ -17,144 +17,496 @@
 
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::fs;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
+use crate::checksum::{Digests, HashAlgo, MultiHasher, digest_reader};
 use crate::compress::CompressionAlgo;
-use crate::index::{ArchivumIndex, INDEX_VERSION, IndexEntry, IndexHeader};
+use crate::index::{ArchivumIndex, INDEX_VERSION, IndexEntry, IndexHeader, Piece, seal_path};
 use crate::output::OutputCtx;
-use crate::scan::EntryType;
+use crate::salvage;
+use crate::scan::{EntryType, ScanError};
+use crate::source::path_from_bytes;
+use crate::store::Store;
+use crate::tar_writer::{PAX_COPY, hash_part, is_manifest};
+use crate::update::relative_path;
 use crate::utils::{fmt_time, now};
 
-pub fn repair(archive_dir: &Path, compression: &str, out: &OutputCtx) -> Result<()> {
-    let algo = CompressionAlgo::parse(compression)
-        .with_context(|| format!("Unknown compression: '{}'", compression))?;
-    let ext = algo.extension();
+/// What is left of the old index next to the parts.
+struct Fragment {
+    header: Option<IndexHeader>,
+    entries: Vec<IndexEntry>,
+    errors: Vec<ScanError>,
+    /// the whole file parsed
+    complete: bool,
+    /// whether the `.b3` seal matches, if there is one
+    sealed: Option<bool>,
+}
+
+/// A part file found on disk: which base and part it is, and what it holds.
+struct Found {
+    base: u32,
+    part: u32,
+    path: PathBuf,
+    /// as named by its extension
+    named: CompressionAlgo,
+    /// as detected from its first bytes
+    detected: Option<CompressionAlgo>,
+}
+
+pub fn repair(
+    archive_dir: &Path,
+    compression: Option<&str>,
+    extra_bases: &[PathBuf],
+    salvage: Option<&Path>,
+    out: &OutputCtx,
+) -> Result<()> {
+    let explicit = compression
+        .map(|c| CompressionAlgo::parse(c).with_context(|| format!("Unknown compression: '{}'", c)))
+        .transpose()?;
 
     out.println(&format!(
         "{} {}",
         "Repairing index in:".cyan().bold(),
         archive_dir.display().to_string().yellow()
     ));
-    out.println(&format!("  Compression: {}", algo.name().green()));
-    out.println("");
 
-    // find all data.part### files
-    let mut found_parts: Vec<(u32, std::path::PathBuf)> = vec![];
-    for entry in fs::read_dir(archive_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        let name = path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-
-        if let Some(rest) = name.strip_prefix("data.part") {
-            if name.ends_with(ext) {
-                let num_part = rest.trim_end_matches(ext.trim_start_matches('.'));
-                let num_part = num_part.trim_end_matches('.');
-                if let Ok(n) = num_part.parse::<u32>() {
-                    found_parts.push((n, path));
-                }
+    let index_path = archive_dir.join("index.arc.json");
+    let old = read_fragment(&index_path);
+    match &old {
+        None => out.println("  Old index: none"),
+        Some(f) => {
+            let seal = match f.sealed {
+                Some(true) => "seal matches".green().to_string(),
+                Some(false) => "seal does not match".yellow().to_string(),
+                None => "no seal".to_string(),
+            };
+            if f.complete {
+                out.println(&format!(
+                    "  Old index: readable, {} entries, {}",
+                    f.entries.len(),
+                    seal
+                ));
+            } else {
+                out.println(&format!(
+                    "  Old index: damaged; recovered {}{} entries",
+                    if f.header.is_some() {
+                        "header and "
+                    } else {
+                        ""
+                    },
+                    f.entries.len()
+                ));
             }
         }
     }
+    let old_header = old.as_ref().and_then(|f| f.header.as_ref());
+    // members get the digests the old index had, besides SHA-256
+    let mut algos = vec![HashAlgo::Sha256];
+    algos.extend(old_header.map(|h| h.hash_algos()).unwrap_or_default());
 
-    found_parts.sort_by_key(|(n, _)| *n);
+    // bases: the archive dir, those the old index names, then --base
+    let mut bases = vec![String::new()];
+    let named = old_header.map(|h| &h.part_bases[..]).unwrap_or_default();
+    for b in named.iter().skip(1) {
+        if !bases.contains(b) {
+            bases.push(b.clone());
+        }
+    }
+    for dir in extra_bases {
+        let rel = relative_path(archive_dir, dir)
+            .to_string_lossy()
+            .into_owned();
+        let rel = if rel == "." { String::new() } else { rel };
+        if !bases.contains(&rel) {
+            bases.push(rel);
+        }
+    }
 
-    if found_parts.is_empty() {
-        anyhow::bail!(
-            "No {} parts found in {}. Check --compression flag.",
-            ext,
-            archive_dir.display()
-        );
+    let mut found: Vec<Found> = vec![];
+    for (b, name) in bases.iter().enumerate() {
+        let dir = base_dir(archive_dir, name);
+        let list = match fs::read_dir(&dir) {
+            Ok(l) => l,
+            Err(e) => {
+                out.eprintln(&format!("  Cannot read {}: {}", dir.display(), e));
+                continue;
+            }
+        };
+        for entry in list {
+            let path = entry?.path();
+            let Some((part, named)) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(parse_part_name)
+            else {
+                continue;
+            };
+            let detected = detect(&path)?;
+            found.push(Found {
+                base: b as u32,
+                part,
+                path,
+                named,
+                detected,
+            });
+        }
+    }
+    if found.is_empty() {
+        anyhow::bail!("No data.part files found in {}", archive_dir.display());
     }
 
-    out.println(&format!("  Found {} part(s)", found_parts.len()));
+    // one format per archive: as given, or what most parts hold
+    let detected = explicit.is_none();
+    let algo = match explicit {
+        Some(a) => a,
+        // ties (and nothing recognized) go to the later of ALL: zstd
+        None => CompressionAlgo::ALL
+            .into_iter()
+            .max_by_key(|a| {
+                found
+                    .iter()
+                    .filter(|f| f.detected.as_ref() == Some(a))
+                    .count()
+            })
+            .unwrap_or_default(),
+    };
+    out.println(&format!(
+        "  Compression: {}{}",
+        algo.name().green(),
+        if detected { " (detected)" } else { "" }
+    ));
 
-    let mut entries: Vec<IndexEntry> = vec![];
-    let mut total_files = 0u64;
-    let mut total_size = 0u64;
+    let parts = pick_parts(found, &algo, archive_dir, &bases, out)?;
+    out.println(&format!(
+        "  Found {} part(s) in {} base(s)",
+        parts.len(),
+        parts.keys().map(|(b, _)| b).collect::<HashSet<_>>().len()
+    ));
+    out.println("");
+
+    let mut part_hashes = vec![];
+    let mut members: Vec<(IndexEntry, Vec<PathBuf>, String)> = vec![];
+    let mut manifests: Vec<(u32, Manifest)> = vec![];
+    let mut report = salvage::Report::default();
 
-    for (part_num, part_path) in &found_parts {
+    for ((base, part), part_path) in &parts {
         out.println(&format!(
             "  Scanning {} ...",
-            part_path.file_name().unwrap().to_string_lossy().yellow()
+            part_path.display().to_string().yellow()
         ));
+        // the parts as found become the baseline for `verify --quick`
+        let base_tag = (*base > 0).then_some(*base);
+        part_hashes.push(hash_part(part_path, base_tag, *part)?);
 
-        let reader = match algo.wrap_reader(part_path) {
-            Ok(r) => r,
-            Err(e) => {
-                out.eprintln(&format!("  Cannot read {}: {}", part_path.display(), e));
-                continue;
-            }
+        let mut found = vec![];
+        let read = if salvage.is_some() {
+            salvage_members(
+                part_path,
+                &algo,
+                *part,
+                &algos,
+                &mut report,
+                &mut found,
+                out,
+            )?
+        } else {
+            read_members(part_path, &algo, *part, &algos, &mut found, out)?
         };
+        manifests.extend(found.into_iter().map(|m| (*base, m)));
+        let part_name = part_path.display().to_string();
+        for (mut entry, copies) in read {
+            entry.tar_base = base_tag;
+            members.push((entry, copies, part_name.clone()));
+        }
+    }
 
-        let mut archive = tar::Archive::new(reader);
+    if !manifests.is_empty() {
+        out.println(&format!(
+            "  Manifests: {} of {} part(s) carry one",
+            manifests.len().to_string().green(),
+            parts.len()
+        ));
+    }
+    let old = with_manifests(old, manifests);
+    let old_header = old.as_ref().and_then(|f| f.header.as_ref());
 
-        for item in archive.entries()? {
-            let item = match item {
-                Ok(i) => i,
-                Err(e) => {
-                    out.eprintln(&format!("  Entry error in part {}: {}", part_num, e));
+    let mut changed_parts = 0usize;
+    for (hash, path) in part_hashes.iter().zip(parts.values()) {
+        let recorded = old_header.and_then(|h| {
+            h.part_hashes
+                .iter()
+                .find(|p| p.base == hash.base && p.part == hash.part)
+        });
+        if recorded.is_some_and(|r| r.blake3 != hash.blake3) {
+            out.eprintln(&format!(
+                "  {} {} differs from the part the old index recorded",
+                "warning:".yellow(),
+                path.display()
+            ));
+            changed_parts += 1;
+        }
+    }
+
+    // where the old index put each path, to settle members stored twice
+    let old_at: HashMap<&Path, &IndexEntry> = old
+        .iter()
+        .flat_map(|f| &f.entries)
+        .map(|e| (e.path.as_path(), e))
+        .collect();
+
+    let mut entries: Vec<IndexEntry> = vec![];
+    let mut at: HashMap<PathBuf, usize> = HashMap::new();
+    // (alias, origin, part) as named by PAX records
+    let mut aliases: Vec<(PathBuf, PathBuf, String)> = vec![];
+    let mut superseded = 0usize;
+
+    for (entry, copies, part_name) in members {
+        for copy in copies {
+            aliases.push((copy, entry.path.clone(), part_name.clone()));
+        }
+        // a path in several bases: the old index decides, else the
+        // lowest base (the newest parts of an update) wins
+        match at.get(&entry.path) {
+            None => {
+                at.insert(entry.path.clone(), entries.len());
+                entries.push(entry);
+            }
+            Some(&i) => {
+                // a stream that spans parts has a member in each of them
+                if continues(&entries[i], &entry) {
+                    add_piece(&mut entries[i], entry);
                     continue;
                 }
-            };
+                superseded += 1;
+                let prev = &entries[i];
+                if let Some(o) = old_at.get(entry.path.as_path()) {
+                    if same_place(o, &entry) && !same_place(o, prev) {
+                        entries[i] = entry;
+                    }
+                }
+            }
+        }
+    }
 
-            let header = item.header();
-            let path = item.path()?.into_owned();
-            let size = header.size()?;
-            let mtime = header.mtime().ok();
-            let mode = header.mode().ok();
-
-            let entry_type = match header.entry_type() {
-                tar::EntryType::Regular | tar::EntryType::Continuous => EntryType::File,
-                tar::EntryType::Directory => EntryType::Directory,
-                tar::EntryType::Symlink => EntryType::Symlink,
-                _ => continue,
-            };
+    // a joined stream's digests cover all of its pieces
+    for e in entries.iter_mut().filter(|e| !e.pieces.is_empty()) {
+        match digest_pieces(e, &parts, &algo, &algos) {
+            Ok(digests) => e.set_digests(digests),
+            Err(err) => out.eprintln(&format!(
+                "  {} cannot hash {}: {:#}",
+                "warning:".yellow(),
+                e.path.display(),
+                err
+            )),
+        }
+    }
 
-            let symlink_target = if entry_type == EntryType::Symlink {
-                header.link_name().ok().flatten().map(|l| l.into_owned())
-            } else {
-                None
-            };
+    // fill in what only the old index knew, where it agrees with the parts
+    let mut content_changed = 0usize;
+    for e in entries.iter_mut() {
+        let Some(o) = old_at.get(e.path.as_path()) else {
+            continue;
+        };
+        if !same_place(o, e) || o.entry_type != e.entry_type || o.size != e.size {
+            continue;
+        }
+        if o.sha256.is_some() && e.sha256.is_some() && o.sha256 != e.sha256 {
+            content_changed += 1;
+            continue;
+        }
+        if e.hashes.is_empty() && o.sha256 == e.sha256 {
+            e.hashes = o.hashes.clone();
+        }
+        e.mtime = o.mtime;
+        e.unix_mode = o.unix_mode;
+        e.changed_during_backup = o.changed_during_backup;
+        e.from_stdin = o.from_stdin;
+    }
 
-            if entry_type == EntryType::File {
-                total_files += 1;
-                total_size += size;
+    // with the complete old entry list, older parts' members it does not
+    // list are files the update left behind
+    let mut dropped = 0usize;
+    if old.as_ref().is_some_and(|f| f.complete) {
+        entries.retain(|e| {
+            let keep = e.tar_base.unwrap_or(0) == 0 || old_at.contains_key(e.path.as_path());
+            if !keep {
+                dropped += 1;
             }
+            keep
+        });
+    }
 
-            entries.push(IndexEntry {
-                path,
-                entry_type,
-                size,
-                mtime,
-                unix_mode: mode,
-                sha256: None, // can't recover without source
-                tar_part: *part_num,
-                symlink_target,
-                tar_base: None,
-                dedup_of: None,
-            });
+    // aliases take their origin's content and, lacking their own header,
+    // its metadata (or the old index's, if it has them)
+    let mut wanted: BTreeMap<PathBuf, (PathBuf, String)> = BTreeMap::new();
+    for (alias, origin, part) in aliases {
+        wanted.entry(alias).or_insert((origin, part));
+    }
+    for o in old.iter().flat_map(|f| &f.entries) {
+        if let Some(origin) = &o.dedup_of {
+            wanted
+                .entry(o.path.clone())
+                .or_insert((origin.clone(), String::new()));
+        }
+    }
+    let recovered: HashMap<PathBuf, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.path.clone(), i))
+        .collect();
+    let origins: HashMap<PathBuf, IndexEntry> = entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File)
+        .map(|e| (e.path.clone(), e.clone()))
+        .collect();
+    let mut restored_aliases = 0usize;
+    for (alias, (origin, part)) in wanted {
+        let Some(orig) = origins.get(&origin) else {
+            if !recovered.contains_key(&alias) {
+                report.lose(&alias, &part, "dedup origin lost");
+            }
+            continue;
+        };
+        // a member of the same name in older parts is the file before an
+        // update made it an alias
+        let replaces = match recovered.get(&alias) {
+            None => None,
+            Some(&i) => {
+                let newer = orig.tar_base.unwrap_or(0) < entries[i].tar_base.unwrap_or(0)
+                    || old_at
+                        .get(alias.as_path())
+                        .is_some_and(|o| o.dedup_of.is_some());
+                if !newer {
+                    continue;
+                }
+                Some(i)
+            }
+        };
+        let mut entry = IndexEntry {
+            path: alias,
+            dedup_of: Some(origin),
+            ..orig.clone()
+        };
+        if let Some(o) = old_at.get(entry.path.as_path()) {
+            entry.mtime = o.mtime;
+            entry.unix_mode = o.unix_mode;
+        }
+        match replaces {
+            Some(i) => entries[i] = entry,
+            None => entries.push(entry),
+        }
+        restored_aliases += 1;
+    }
+
+    // the old index's order where it is known, the parts' after that
+    let rank: HashMap<&Path, usize> = old
+        .iter()
+        .flat_map(|f| &f.entries)
+        .enumerate()
+        .map(|(i, e)| (e.path.as_path(), i))
+        .collect();
+    entries.sort_by_key(|e| rank.get(e.path.as_path()).copied().unwrap_or(usize::MAX));
+
+    let missing: Vec<&IndexEntry> = old
+        .iter()
+        .flat_map(|f| &f.entries)
+        .filter(|o| !entries.iter().any(|e| e.path == o.path))
+        .collect();
+    for o in &missing {
+        if !report.lost.iter().any(|l| l.path == o.path) {
+            report.lose(&o.path, "", "in the old index, not in the parts");
+        }
+    }
+
+    // identical content stored twice (e.g. by `merge`) stays as it is:
+    // both copies are in the parts
+    let mut by_sha: HashMap<&str, usize> = HashMap::new();
+    for e in entries.iter().filter(|e| e.dedup_of.is_none()) {
+        if let Some(sha) = &e.sha256 {
+            *by_sha.entry(sha).or_default() += 1;
         }
     }
+    let duplicates: usize = by_sha.values().map(|n| n - 1).sum();
+
+    let files = entries.iter().filter(|e| e.entry_type == EntryType::File);
+    let total_files = files.clone().count() as u64;
+    let total_size: u64 = files.clone().map(|e| e.size).sum();
+    // the old index's extra digests, as far as every file has them again
+    let hashes: Vec<String> = old_header
+        .map(|h| &h.hashes[..])
+        .unwrap_or_default()
+        .iter()
+        .filter(|n| files.clone().all(|e| e.hashes.contains_key(*n)))
+        .cloned()
+        .collect();
 
     out.println(&format!(
-        "  Recovered {} file entries from {} parts",
+        "  Recovered {} file entries from {} parts, with SHA-256 checksums",
         total_files.to_string().green(),
-        found_parts.len()
+        parts.len()
     ));
-    out.println("  Note: SHA-256 checksums cannot be recovered without the source.");
+    if restored_aliases > 0 {
+        out.println(&format!(
+            "  Restored {} dedup alias(es)",
+            restored_aliases.to_string().green()
+        ));
+    }
+    if duplicates > 0 {
+        out.println(&format!(
+            "  {} {} file(s) duplicate another's content",
+            "Note:".yellow(),
+            duplicates
+        ));
+    }
+    if superseded > 0 {
+        out.println(&format!(
+            "  {} {} member(s) stored again in another part; kept one of each",
+            "Note:".yellow(),
+            superseded
+        ));
+    }
+    if dropped > 0 {
+        out.println(&format!(
+            "  {} {} member(s) of older parts not in the old index left out",
+            "Note:".yellow(),
+            dropped
+        ));
+    }
+    if content_changed > 0 {
+        out.println(&format!(
+            "  {} {} file(s) no longer match the old index's checksums",
+            "warning:".yellow(),
+            content_changed
+        ));
+    }
+    if changed_parts > 0 {
+        out.println(&format!(
+            "  {} {} part(s) changed since they were written; check them with verify",
+            "warning:".yellow(),
+            changed_parts
+        ));
+    }
+    if !missing.is_empty() {
+        out.println(&format!(
+            "  {} {} entr(ies) of the old index are not in the parts",
+            "warning:".yellow(),
+            missing.len()
+        ));
+    }
     out.println("");
 
     let ts = now();
-    let idx = ArchivumIndex {
+    let repaired = "Repaired index - checksums recomputed from tar contents";
+    let mut idx = ArchivumIndex {
         header: IndexHeader {
             version: INDEX_VERSION,
-            created_at_unix: ts,
-            created_at_human: fmt_time(ts),
+            created_at_unix: old_header.map(|h| h.created_at_unix).unwrap_or(ts),
+            created_at_human: old_header
+                .map(|h| h.created_at_human.clone())
+                .unwrap_or_else(|| fmt_time(ts)),
             total_files,
             total_dirs: entries
                 .iter()
@@ -164,23 +516,47 @@ pub fn repair(archive_dir: &Path, compression: &str, out: &OutputCtx) -> Result<
                 .iter()
                 .filter(|e| e.entry_type == EntryType::Symlink)
                 .count() as u64,
+            total_special: entries.iter().filter(|e| e.entry_type.is_special()).count() as u64,
             total_size,
-            total_parts: found_parts.last().map(|(n, _)| n + 1).unwrap_or(0),
+            total_parts: parts
+                .keys()
+                .filter(|(b, _)| *b == 0)
+                .map(|(_, p)| p + 1)
+                .max()
+                .unwrap_or(0),
             compression: algo,
-            zstd_level: 3,
-            notes: "Repaired index - checksums not available".into(),
-            part_bases: vec![String::new()],
+            zstd_level: old_header.map(|h| h.zstd_level).unwrap_or(3),
+            notes: match old_header.map(|h| h.notes.as_str()) {
+                Some(n) if !n.is_empty() => format!("{} (was: {})", repaired, n),
+                _ => repaired.into(),
+            },
+            part_bases: bases,
+            reproducible: old_header.is_some_and(|h| h.reproducible),
+            source_date_epoch: old_header.and_then(|h| h.source_date_epoch),
+            ignore_sources: old_header
+                .map(|h| h.ignore_sources.clone())
+                .unwrap_or_default(),
+            roots: old_header.map(|h| h.roots.clone()).unwrap_or_default(),
+            part_hashes,
+            merkle_root: None,
+            hashes,
             _integrity: None,
         },
         entries,
+        errors: old
+            .filter(|f| f.complete)
+            .map(|f| f.errors)
+            .unwrap_or_default(),
     };
 
     if out.dry_run {
         out.dry("would write index.arc.json");
         return Ok(());
     }
+    if let Some(path) = salvage {
+        report.finish(path, out)?;
+    }
 
-    let index_path = archive_dir.join("index.arc.json");
     idx.write(&index_path)?;
 
     out.println(&format!(
@@ -191,3 +567,501 @@ pub fn repair(archive_dir: &Path, compression: &str, out: &OutputCtx) -> Result<
 
     Ok(())
 }
+
+fn base_dir(archive_dir: &Path, base: &str) -> PathBuf {
+    if base.is_empty() {
+        archive_dir.to_path_buf()
+    } else {
+        archive_dir.join(base)
+    }
+}
+
+/// `data.partNNN` plus any compression's extension.
+fn parse_part_name(name: &str) -> Option<(u32, CompressionAlgo)> {
+    let rest = name.strip_prefix("data.part")?;
+    let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    let part = rest[..digits].parse().ok()?;
+    let algo = CompressionAlgo::ALL
+        .into_iter()
+        .find(|a| &rest[digits..] == a.extension())?;
+    Some((part, algo))
+}
+
+fn detect(path: &Path) -> Result<Option<CompressionAlgo>> {
+    let mut head = Vec::with_capacity(512);
+    File::open(path)
+        .with_context(|| format!("Cannot open {}", path.display()))?
+        .take(512)
+        .read_to_end(&mut head)?;
+    Ok(CompressionAlgo::detect(&head))
+}
+
+/// Settle which file is each (base, part) of an `algo` archive. A part
+/// holding `algo` data under another extension is renamed, since the
+/// index finds parts by name; parts holding other formats are left out.
+fn pick_parts(
+    found: Vec<Found>,
+    algo: &CompressionAlgo,
+    archive_dir: &Path,
+    bases: &[String],
+    out: &OutputCtx,
+) -> Result<BTreeMap<(u32, u32), PathBuf>> {
+    let mut by_slot: BTreeMap<(u32, u32), Vec<Found>> = BTreeMap::new();
+    for f in found {
+        by_slot.entry((f.base, f.part)).or_default().push(f);
+    }
+
+    let mut parts = BTreeMap::new();
+    for ((base, part), mut cands) in by_slot {
+        // right content under the right name, then right content, then a
+        // damaged start under the right name (salvage may still read it)
+        let rank = |f: &Found| match (f.detected.as_ref() == Some(algo), &f.named == algo) {
+            (true, true) => 0,
+            (true, false) => 1,
+            (false, true) if f.detected.is_none() => 2,
+            _ => 3,
+        };
+        cands.sort_by_key(rank);
+        for other in &cands[1..] {
+            out.eprintln(&format!(
+                "  {} {} skipped: another file is part {:03}",
+                "warning:".yellow(),
+                other.path.display(),
+                part
+            ));
+        }
+        let pick = cands.swap_remove(0);
+        match rank(&pick) {
+            0 | 2 => {}
+            1 => {
+                let to = base_dir(archive_dir, &bases[base as usize]).join(format!(
+                    "data.part{:03}{}",
+                    part,
+                    algo.extension()
+                ));
+                if out.dry_run {
+                    out.dry(&format!(
+                        "rename {} -> {}",
+                        pick.path.display(),
+                        to.display()
+                    ));
+                } else {
+                    fs::rename(&pick.path, &to).with_context(|| {
+                        format!("Cannot rename {} to {}", pick.path.display(), to.display())
+                    })?;
+                    out.println(&format!(
+                        "  Renamed {} -> {} (holds {} data)",
+                        pick.path.display(),
+                        to.display(),
+                        algo.name()
+                    ));
+                }
+                parts.insert((base, part), to);
+                continue;
+            }
+            _ => {
+                out.eprintln(&format!(
+                    "  {} {} skipped: holds {} data, not {}",
+                    "warning:".yellow(),
+                    pick.path.display(),
+                    pick.detected.map(|d| d.name()).unwrap_or("unrecognized"),
+                    algo.name()
+                ));
+                continue;
+            }
+        }
+        parts.insert((base, part), pick.path);
+    }
+    Ok(parts)
+}
+
+/// Every member of a part as an index entry (with its digests), and the
+/// dedup aliases its `ARCHIVUM.copy` records name. Reading stops at the
+/// first error in the part.
+fn read_members(
+    part_path: &Path,
+    algo: &CompressionAlgo,
+    part: u32,
+    algos: &[HashAlgo],
+    manifests: &mut Vec<Manifest>,
+    out: &OutputCtx,
+) -> Result<Vec<(IndexEntry, Vec<PathBuf>)>> {
+    let mut members = vec![];
+    let reader = match algo.wrap_reader(part_path) {
+        Ok(r) => r,
+        Err(e) => {
+            out.eprintln(&format!("  Cannot read {}: {}", part_path.display(), e));
+            return Ok(members);
+        }
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    for item in archive.entries()? {
+        let mut item = match item {
+            Ok(i) => i,
+            Err(e) => {
+                out.eprintln(&format!("  Entry error in part {}: {}", part, e));
+                continue;
+            }
+        };
+
+        if is_manifest(&mut item)? {
+            match read_manifest(&mut item) {
+                Ok(m) => manifests.push(m),
+                Err(e) => out.eprintln(&format!(
+                    "  {} unreadable manifest in part {}: {}",
+                    "warning:".yellow(),
+                    part,
+                    e
+                )),
+            }
+            continue;
+        }
+
+        let path = item.path()?.into_owned();
+        let mut copies = vec![];
+        if let Some(pax) = item.pax_extensions()? {
+            for ext in pax.flatten() {
+                if ext.key() == Ok(PAX_COPY) {
+                    copies.push(path_from_bytes(ext.value_bytes()));
+                }
+            }
+        }
+        let Some(mut entry) = IndexEntry::from_tar(path, item.header(), part)? else {
+            continue;
+        };
+
+        // the member is the archived content, so its hash is the one
+        // `create` recorded
+        if entry.entry_type == EntryType::File {
+            match digest_reader(&mut item, algos) {
+                Ok(digests) => entry.set_digests(digests),
+                Err(e) => {
+                    out.eprintln(&format!(
+                        "  Cannot read {} in part {}: {}",
+                        entry.path.display(),
+                        part,
+                        e
+                    ));
+                    continue;
+                }
+            }
+        }
+        members.push((entry, copies));
+    }
+    Ok(members)
+}
+
+/// `read_members` for `--salvage`: reads past damage, and reports members
+/// cut off by it as lost.
+fn salvage_members(
+    part_path: &Path,
+    algo: &CompressionAlgo,
+    part: u32,
+    algos: &[HashAlgo],
+    report: &mut salvage::Report,
+    manifests: &mut Vec<Manifest>,
+    out: &OutputCtx,
+) -> Result<Vec<(IndexEntry, Vec<PathBuf>)>> {
+    let mut members = vec![];
+    let name = part_path.display().to_string();
+    let salvaged = match salvage::salvage_part(&Store::Local, part_path, algo) {
+        Ok(s) => s,
+        Err(e) => {
+            out.eprintln(&format!("  Cannot read {}: {:#}", name, e));
+            return Ok(members);
+        }
+    };
+    report.part(name.clone(), &salvaged, out);
+    for m in &salvaged.members {
+        if m.manifest {
+            // a manifest cut off by the damage is no loss of its own
+            if let Some(manifest) = m
+                .complete
+                .then(|| read_manifest(&mut salvaged.open(m)?))
+                .transpose()?
+            {
+                manifests.push(manifest);
+            }
+            continue;
+        }
+        let Some(mut entry) = IndexEntry::from_tar(m.path.clone(), &m.header, part)? else {
+            continue;
+        };
+        if entry.entry_type == EntryType::File {
+            if !m.complete {
+                report.lose(&m.path, &name, "truncated");
+                continue;
+            }
+            entry.set_digests(digest_reader(&mut salvaged.open(m)?, algos)?);
+        }
+        members.push((entry, m.copies.clone()));
+    }
+    Ok(members)
+}
+
+/// A part's slice of the index, as `write_archive` ends each part with.
+struct Manifest {
+    index: ArchivumIndex,
+    /// Where each of its entries sits in the full index.
+    positions: Vec<usize>,
+}
+
+fn read_manifest(r: &mut dyn Read) -> Result<Manifest> {
+    let mut data = vec![];
+    r.read_to_end(&mut data)?;
+    let mut value: serde_json::Value = serde_json::from_slice(&data)?;
+    let positions = match value.get_mut("positions").map(serde_json::Value::take) {
+        Some(p) => serde_json::from_value(p)?,
+        None => vec![],
+    };
+    Ok(Manifest {
+        index: serde_json::from_value(value)?,
+        positions,
+    })
+}
+
+/// Fold the parts' manifests into what survives of the JSON index. They
+/// were written with the parts, so their entries take precedence over an
+/// index that is damaged or fails its seal; a complete index whose seal
+/// matches is the exact one and is kept as it is. The old index's header,
+/// if it parses, still wins, and so does the order and, for older bases,
+/// the membership of a complete entry list: only it knows what an update
+/// carried over.
+fn with_manifests(old: Option<Fragment>, manifests: Vec<(u32, Manifest)>) -> Option<Fragment> {
+    if manifests.is_empty()
+        || old
+            .as_ref()
+            .is_some_and(|f| f.complete && f.sealed == Some(true))
+    {
+        return old;
+    }
+
+    // the last part of base 0 has the fullest header (every earlier part's
+    // hash) and the errors of the whole run
+    let mut header = None;
+    let mut errors = vec![];
+    let mut listed = vec![];
+    for (base, m) in manifests {
+        if base == 0 {
+            header = Some(m.index.header);
+            errors = m.index.errors;
+        }
+        let positions = m.positions.into_iter().chain(std::iter::repeat(usize::MAX));
+        listed.extend(
+            m.index
+                .entries
+                .into_iter()
+                .zip(positions)
+                .map(|(e, at)| (base, at, e)),
+        );
+    }
+    // back in index order, the newest base first
+    listed.sort_by_key(|(base, at, _)| (*base, *at));
+
+    let mut entries = vec![];
+    let mut seen = HashSet::new();
+    for (base, _, mut e) in listed {
+        // manifests of older parts know them as base 0; what those
+        // referenced further back cannot be placed
+        if base > 0 {
+            if e.tar_base.is_some() {
+                continue;
+            }
+            e.tar_base = Some(base);
+        }
+        if seen.insert(e.path.clone()) {
+            entries.push(e);
+        }
+    }
+
+    let (complete, sealed) = match old {
+        None => (false, None),
+        Some(f) => {
+            header = f.header.or(header);
+            if f.complete {
+                // its order stands, and older bases' members it does not
+                // list were left behind by an update
+                errors = f.errors;
+                let listed: HashSet<&Path> = f.entries.iter().map(|e| e.path.as_path()).collect();
+                let (known, new): (Vec<_>, Vec<_>) = entries
+                    .into_iter()
+                    .partition(|e| listed.contains(e.path.as_path()));
+                let mut known: HashMap<PathBuf, IndexEntry> =
+                    known.into_iter().map(|e| (e.path.clone(), e)).collect();
+                entries = f
+                    .entries
+                    .into_iter()
+                    .map(|o| known.remove(&o.path).unwrap_or(o))
+                    .collect();
+                entries.extend(new.into_iter().filter(|e| e.tar_base.is_none()));
+            } else {
+                entries.extend(f.entries.into_iter().filter(|e| !seen.contains(&e.path)));
+            }
+            (f.complete, f.sealed)
+        }
+    };
+    Some(Fragment {
+        header,
+        entries,
+        errors,
+        complete,
+        sealed,
+    })
+}
+
+/// Whether `next` is a member in the part right after `e`'s last one, in
+/// the same base: the next piece of a stream.
+fn continues(e: &IndexEntry, next: &IndexEntry) -> bool {
+    e.tar_base == next.tar_base
+        && e.entry_type == EntryType::File
+        && next.entry_type == EntryType::File
+        && next.tar_part == e.last_part() + 1
+}
+
+/// Take `next`, the member in the part after `e`'s last, as the next piece
+/// of the stream `e`. Only streams span parts, so the entry is one.
+fn add_piece(e: &mut IndexEntry, next: IndexEntry) {
+    let piece = |e: &IndexEntry| Piece {
+        size: e.size,
+        sha256: e.sha256.clone(),
+        hashes: e.hashes.clone(),
+    };
+    if e.pieces.is_empty() {
+        e.pieces.push(piece(e));
+    }
+    e.pieces.push(piece(&next));
+    e.size += next.size;
+    e.sha256 = None;
+    e.hashes.clear();
+    e.from_stdin = true;
+}
+
+/// Digests of a stream that spans parts, read piece by piece.
+fn digest_pieces(
+    e: &IndexEntry,
+    parts: &BTreeMap<(u32, u32), PathBuf>,
+    algo: &CompressionAlgo,
+    algos: &[HashAlgo],
+) -> Result<Digests> {
+    let mut hasher = MultiHasher::new(algos);
+    let mut buf = vec![0u8; 1 << 16];
+    for piece in e.members() {
+        let path = parts
+            .get(&(piece.tar_base.unwrap_or(0), piece.tar_part))
+            .with_context(|| format!("part {:03} is gone", piece.tar_part))?;
+        let mut archive = tar::Archive::new(algo.wrap_reader(path)?);
+        let mut found = false;
+        for item in archive.entries()? {
+            let mut item = item?;
+            if is_manifest(&mut item)? || item.path()? != piece.path {
+                continue;
+            }
+            loop {
+                let n = item.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+            }
+            found = true;
+            break;
+        }
+        if !found {
+            anyhow::bail!("no piece in {}", path.display());
+        }
+    }
+    Ok(hasher.finish())
+}
+
+fn same_place(a: &IndexEntry, b: &IndexEntry) -> bool {
+    a.tar_base.unwrap_or(0) == b.tar_base.unwrap_or(0) && a.tar_part == b.tar_part
+}
+
+/// Whatever parses of the index at `path`: all of it, or the header and
+/// the entry objects that are still intact.
+fn read_fragment(path: &Path) -> Option<Fragment> {
+    let bytes = fs::read(path).ok()?;
+    let sealed = fs::read_to_string(seal_path(path))
+        .ok()
+        .map(|s| s.trim() == blake3::hash(&bytes).to_hex().as_str());
+
+    if let Ok(idx) = serde_json::from_slice::<ArchivumIndex>(&bytes) {
+        return Some(Fragment {
+            header: Some(idx.header),
+            entries: idx.entries,
+            errors: idx.errors,
+            complete: true,
+            sealed,
+        });
+    }
+
+    let header = find(&bytes, b"\"header\"", 0)
+        .and_then(|i| bytes[i..].iter().position(|&b| b == b'{').map(|j| i + j))
+        .and_then(|start| Some((start, object_end(&bytes, start)?)))
+        .and_then(|(start, end)| serde_json::from_slice(&bytes[start..end]).ok());
+
+    // entry objects are the ones that open with a "path" key
+    let mut entries = vec![];
+    let mut i = 0;
+    while let Some(start) = bytes[i..].iter().position(|&b| b == b'{').map(|j| i + j) {
+        i = start + 1;
+        let key = bytes[i..].iter().position(|b| !b.is_ascii_whitespace());
+        if !key.is_some_and(|k| bytes[i + k..].starts_with(b"\"path\"")) {
+            continue;
+        }
+        let Some(end) = object_end(&bytes, start) else {
+            break;
+        };
+        if let Ok(e) = serde_json::from_slice::<IndexEntry>(&bytes[start..end]) {
+            entries.push(e);
+            i = end;
+        }
+    }
+
+    if header.is_none() && entries.is_empty() {
+        return None;
+    }
+    Some(Fragment {
+        header,
+        entries,
+        errors: vec![],
+        complete: false,
+        sealed,
+    })
+}
+
+fn find(hay: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    hay[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| from + i)
+}
+
+/// End (exclusive) of the JSON object opening at `start`, if it closes.
+fn object_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let (mut depth, mut in_str, mut escaped) = (0usize, false, false);
+    for (i, &b) in bytes.iter().enumerate().skip(start) {
+        if in_str {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_str = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_str = true,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
@@ -19,16 +19,23 @@ use anyhow::{Context, Result};
 use colored::Colorize;
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use indicatif::{ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
-use std::io::copy;
+use std::io::{self, Read, Write, copy};
 use std::path::{Component, Path, PathBuf};
 use tar::Archive;
 
+use crate::checksum::hash_reader;
 use crate::index::{ArchivumIndex, IndexEntry};
 use crate::output::OutputCtx;
+use crate::pipe::{self, ChunkReader};
+use crate::salvage;
 use crate::scan::EntryType;
-use crate::utils::human;
+use crate::source::path_from_bytes;
+use crate::store::Store;
+use crate::tar_writer::{PAX_COPY, PAX_SHA256, is_manifest};
+use crate::utils::{TempPath, human};
 
 fn safe_join(base: &Path, path: &Path) -> Result<PathBuf> {
     if path.is_absolute() {
@@ -69,18 +76,25 @@ fn safe_join(base: &Path, path: &Path) -> Result<PathBuf> {
     Ok(full)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn restore(
+    store: &Store,
     index_path: &Path,
     target: &Path,
     filter: Option<&str>,
+    root: Option<&str>,
     force: bool,
     restore_permissions: bool,
+    salvage: Option<&Path>,
     out: &OutputCtx,
 ) -> Result<()> {
-    let idx = ArchivumIndex::read(index_path)
-        .with_context(|| format!("Cannot read index: {}", index_path.display()))?;
+    let idx = store
+        .read_index(index_path)
+        .with_context(|| format!("Cannot read index: {}", store.location(index_path)))?;
     let index_dir = index_path.parent().unwrap_or(Path::new("."));
 
+    let root = check_root(root, &idx.header.roots)?;
+
     let globset = build_filter(filter)?;
 
     out.println(&format!(
@@ -106,7 +120,10 @@ pub fn restore(
         if !matches_filter(&globset, &entry.path) {
             continue;
         }
-        let dest = safe_join(target, &entry.path)?;
+        let Some(rel) = dest_rel(root, &entry.path) else {
+            continue;
+        };
+        let dest = safe_join(target, rel)?;
         if out.dry_run {
             out.dry(&format!("mkdir {}", dest.display()));
         } else {
@@ -123,8 +140,11 @@ pub fn restore(
         if entry.entry_type != EntryType::Symlink {
             continue;
         }
+        let Some(rel) = dest_rel(root, &entry.path) else {
+            continue;
+        };
         if let Some(link_target) = &entry.symlink_target {
-            let link_path = safe_join(target, &entry.path)?;
+            let link_path = safe_join(target, rel)?;
             if out.dry_run {
                 out.dry(&format!(
                     "symlink {} -> {}",
@@ -156,9 +176,66 @@ pub fn restore(
         }
     }
 
-    // pass 3: regular files, grouped by tar_part
-    let mut dedup_done: HashMap<PathBuf, PathBuf> = HashMap::new();
-    let mut by_part: HashMap<u32, Vec<&IndexEntry>> = HashMap::new();
+    // pass 3: device nodes and FIFOs
+    for entry in &idx.entries {
+        if !entry.entry_type.is_special() {
+            continue;
+        }
+        if !matches_filter(&globset, &entry.path) {
+            continue;
+        }
+        let Some(rel) = dest_rel(root, &entry.path) else {
+            continue;
+        };
+        let dest = safe_join(target, rel)?;
+        if out.dry_run {
+            out.dry(&format!("mknod {}", dest.display()));
+            continue;
+        }
+        if dest.symlink_metadata().is_ok() {
+            if force {
+                fs::remove_file(&dest).ok();
+            } else {
+                out.println(&format!(
+                    "  {} {}",
+                    "skip (exists):".dimmed(),
+                    dest.display()
+                ));
+                continue;
+            }
+        }
+        if let Some(p) = dest.parent() {
+            fs::create_dir_all(p)?;
+        }
+        #[cfg(unix)]
+        match make_special(&dest, entry) {
+            Ok(()) => {
+                if restore_permissions {
+                    apply_permissions(&dest, entry);
+                }
+            }
+            Err(e) => out.eprintln(&format!(
+                "  {} cannot create {}: {}",
+                "warning:".yellow(),
+                dest.display(),
+                e
+            )),
+        }
+        #[cfg(not(unix))]
+        out.println("  special files skipped on non-Unix");
+    }
+
+    // pass 4: regular files, grouped by part file. A dedup alias is written
+    // from its origin's tar member, so it restores even when the origin
+    // itself is filtered out.
+    let files: HashMap<&Path, &IndexEntry> = idx
+        .entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File)
+        .map(|e| (e.path.as_path(), e))
+        .collect();
+    let mut by_part: HashMap<PathBuf, HashMap<PathBuf, Wanted>> = HashMap::new();
+    let mut pieced: Vec<(&IndexEntry, PathBuf)> = vec![];
     for entry in &idx.entries {
         if entry.entry_type != EntryType::File {
             continue;
@@ -166,17 +243,53 @@ pub fn restore(
         if !matches_filter(&globset, &entry.path) {
             continue;
         }
-        if entry.dedup_of.is_some() {
+        let Some(rel) = dest_rel(root, &entry.path) else {
+            continue;
+        };
+        let dest = safe_join(target, rel)?;
+        if !entry.pieces.is_empty() {
+            pieced.push((entry, dest));
             continue;
         }
-        by_part.entry(entry.tar_part).or_default().push(entry);
+        let origin = match &entry.dedup_of {
+            None => entry,
+            Some(orig) => match files.get(orig.as_path()) {
+                Some(o) => *o,
+                None => {
+                    out.eprintln(&format!(
+                        "  {} dedup origin {} of {} is not in the index",
+                        "warning:".yellow(),
+                        orig.display(),
+                        entry.path.display()
+                    ));
+                    continue;
+                }
+            },
+        };
+        by_part
+            .entry(origin.part_path(index_dir, &idx.header))
+            .or_default()
+            .entry(origin.path.clone())
+            .or_insert_with(|| Wanted {
+                size: origin.size,
+                sha256: origin.sha256.as_deref(),
+                outputs: vec![],
+            })
+            .outputs
+            .push((entry, dest));
     }
 
-    let total_files: u64 = by_part.values().map(|v| v.len() as u64).sum();
+    let total_files: u64 = by_part
+        .values()
+        .flat_map(|m| m.values())
+        .map(|w| w.outputs.len() as u64)
+        .sum::<u64>()
+        + pieced.len() as u64;
     let total_bytes: u64 = by_part
         .values()
-        .flat_map(|v| v.iter())
-        .map(|e| e.size)
+        .flat_map(|m| m.values())
+        .map(|w| w.size)
+        .chain(pieced.iter().map(|(e, _)| e.size))
         .sum();
 
     let pb = ProgressBar::new(total_bytes);
@@ -188,133 +301,724 @@ pub fn restore(
         .progress_chars("=> "),
     );
 
-    let mut sorted_parts: Vec<u32> = by_part.keys().cloned().collect();
+    let mut sorted_parts: Vec<PathBuf> = by_part.keys().cloned().collect();
     sorted_parts.sort_unstable();
+    let mut report = salvage::Report::default();
 
-    for part in sorted_parts {
-        let entries = &by_part[&part];
-
-        let part_path = {
-            let rep = entries[0];
-            rep.part_path(index_dir, &idx.header)
-        };
-
-        let mut want: HashMap<PathBuf, &IndexEntry> = HashMap::new();
-        for e in entries {
-            want.insert(e.path.clone(), e);
-        }
+    for part_path in sorted_parts {
+        let mut want = by_part.remove(&part_path).unwrap_or_default();
 
         if out.dry_run {
-            for e in entries {
-                let out_path = safe_join(target, &e.path)?;
-                out.dry(&format!(
-                    "restore {} ({})",
-                    out_path.display(),
-                    human(e.size)
-                ));
-                pb.inc(e.size);
+            for w in want.values() {
+                for (e, dest) in &w.outputs {
+                    out.dry(&format!("restore {} ({})", dest.display(), human(e.size)));
+                }
+                pb.inc(w.size);
             }
             continue;
         }
 
-        let reader = idx
-            .header
-            .compression
-            .wrap_reader(&part_path)
-            .with_context(|| format!("Cannot open part {}", part_path.display()))?;
+        if salvage.is_some() {
+            salvage_part(
+                store,
+                &part_path,
+                &idx,
+                want,
+                &mut report,
+                force,
+                restore_permissions,
+                &pb,
+                out,
+            )?;
+            continue;
+        }
+
+        let reader = store.open_part(&part_path, &idx.header.compression)?;
         let mut archive = Archive::new(reader);
 
         for item in archive.entries()? {
             let mut item = item?;
+            if is_manifest(&mut item)? {
+                continue;
+            }
             let item_path = item.path()?.into_owned();
+            let Some(w) = want.remove(&item_path) else {
+                continue;
+            };
+            write_outputs(&mut item, w.outputs, force, restore_permissions, out)?;
+            pb.inc(w.size);
+        }
+    }
 
-            if let Some(entry) = want.remove(&item_path) {
-                let out_path = safe_join(target, &entry.path)?;
+    // streams that span parts, one piece per part
+    for (entry, dest) in pieced {
+        if out.dry_run {
+            out.dry(&format!(
+                "restore {} ({})",
+                dest.display(),
+                human(entry.size)
+            ));
+            pb.inc(entry.size);
+            continue;
+        }
+        restore_pieces(
+            store,
+            &idx,
+            index_dir,
+            entry,
+            &dest,
+            salvage.map(|_| &mut report),
+            force,
+            restore_permissions,
+            &pb,
+            out,
+        )?;
+    }
 
-                if out_path.exists() && !force {
-                    out.println(&format!(
-                        "  {} {}",
-                        "skip (exists):".dimmed(),
-                        out_path.display()
-                    ));
-                    pb.inc(entry.size);
-                    continue;
-                }
+    pb.finish_with_message(format!(
+        "{}  ({} files, {})",
+        "restore complete".green(),
+        total_files,
+        human(total_bytes)
+    ));
 
-                if let Some(p) = out_path.parent() {
-                    fs::create_dir_all(p)?;
-                }
+    out.println("");
+    out.println(&format!(
+        "  {} {}",
+        "Restored to:".cyan().bold(),
+        target.display().to_string().yellow()
+    ));
 
-                let mut f = OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(&out_path)
-                    .with_context(|| format!("Cannot write {}", out_path.display()))?;
+    if let Some(path) = salvage.filter(|_| !out.dry_run) {
+        report.finish(path, out)?;
+        if !report.lost.is_empty() {
+            anyhow::bail!(
+                "{} file(s) could not be recovered; see {}",
+                report.lost.len(),
+                path.display()
+            );
+        }
+    }
 
-                copy(&mut item, &mut f)?;
-                dedup_done.insert(entry.path.clone(), out_path.clone());
-                pb.inc(entry.size);
+    Ok(())
+}
 
-                #[cfg(unix)]
-                if restore_permissions {
-                    apply_permissions(&out_path, entry);
+/// `restore --salvage`: restore what can be read of a part's members. A
+/// member counts as recovered only if its content still has the checksum
+/// the index recorded; everything else wanted from the part is reported
+/// lost.
+#[allow(clippy::too_many_arguments)]
+fn salvage_part(
+    store: &Store,
+    part_path: &Path,
+    idx: &ArchivumIndex,
+    mut want: HashMap<PathBuf, Wanted>,
+    report: &mut salvage::Report,
+    force: bool,
+    restore_permissions: bool,
+    pb: &ProgressBar,
+    out: &OutputCtx,
+) -> Result<()> {
+    let part = store.location(part_path);
+    let lose = |report: &mut salvage::Report, w: Wanted, reason: &str| {
+        for (e, _) in &w.outputs {
+            report.lose(&e.path, &part, reason);
+        }
+        pb.inc(w.size);
+    };
+
+    let salvaged = match salvage::salvage_part(store, part_path, &idx.header.compression) {
+        Ok(s) => s,
+        Err(e) => {
+            out.eprintln(&format!("  {} {:#}", "salvage:".yellow(), e));
+            let reason = format!("part unreadable: {:#}", e);
+            for (_, w) in want {
+                lose(report, w, &reason);
+            }
+            return Ok(());
+        }
+    };
+    report.part(part.clone(), &salvaged, out);
+
+    for m in salvaged.members.iter().filter(|m| !m.manifest) {
+        let Some(w) = want.remove(&m.path) else {
+            continue;
+        };
+        if !m.complete {
+            lose(report, w, "truncated");
+            continue;
+        }
+        if let Some(sha) = w.sha256 {
+            if hash_reader(&mut salvaged.open(m)?)? != sha {
+                lose(report, w, "checksum mismatch");
+                continue;
+            }
+        }
+        let size = w.size;
+        write_outputs(
+            &mut salvaged.open(m)?,
+            w.outputs,
+            force,
+            restore_permissions,
+            out,
+        )?;
+        pb.inc(size);
+    }
+    for (_, w) in want {
+        lose(report, w, "not found in readable data");
+    }
+    Ok(())
+}
+
+/// Restore an entry stored as one member in each of several parts by
+/// appending the pieces in part order. With `--salvage` each piece must
+/// match its own recorded digest, and a missing or damaged piece loses the
+/// whole file.
+#[allow(clippy::too_many_arguments)]
+fn restore_pieces(
+    store: &Store,
+    idx: &ArchivumIndex,
+    index_dir: &Path,
+    entry: &IndexEntry,
+    dest: &Path,
+    mut salvage: Option<&mut salvage::Report>,
+    force: bool,
+    restore_permissions: bool,
+    pb: &ProgressBar,
+    out: &OutputCtx,
+) -> Result<()> {
+    if !make_room(dest, force, out)? {
+        pb.inc(entry.size);
+        return Ok(());
+    }
+    if let Some(p) = dest.parent() {
+        fs::create_dir_all(p)?;
+    }
+    let mut f = File::create(dest).with_context(|| format!("Cannot write {}", dest.display()))?;
+
+    let mut done = 0;
+    for piece in entry.members() {
+        let part_path = piece.part_path(index_dir, &idx.header);
+        let part = store.location(&part_path);
+        let Some(report) = salvage.as_deref_mut() else {
+            let reader = store.open_part(&part_path, &idx.header.compression)?;
+            let mut archive = Archive::new(reader);
+            let mut found = false;
+            for item in archive.entries()? {
+                let mut item = item?;
+                if !is_manifest(&mut item)? && item.path()? == piece.path {
+                    copy(&mut item, &mut f)?;
+                    found = true;
+                    break;
                 }
             }
+            if !found {
+                anyhow::bail!("{} is missing its piece in {}", entry.path.display(), part);
+            }
+            pb.inc(piece.size);
+            done += piece.size;
+            continue;
+        };
+
+        let lost = match salvage::salvage_part(store, &part_path, &idx.header.compression) {
+            Err(e) => Some(format!("part unreadable: {:#}", e)),
+            Ok(salvaged) => {
+                report.part(part.clone(), &salvaged, out);
+                match salvaged
+                    .members
+                    .iter()
+                    .find(|m| !m.manifest && m.path == piece.path)
+                {
+                    None => Some("not found in readable data".to_string()),
+                    Some(m) if !m.complete => Some("truncated".to_string()),
+                    Some(m) => {
+                        let sha = hash_reader(&mut salvaged.open(m)?)?;
+                        if piece.sha256.as_ref().is_some_and(|s| *s != sha) {
+                            Some("checksum mismatch".to_string())
+                        } else {
+                            copy(&mut salvaged.open(m)?, &mut f)?;
+                            None
+                        }
+                    }
+                }
+            }
+        };
+        if let Some(reason) = lost {
+            report.lose(&entry.path, &part, &reason);
+            drop(f);
+            fs::remove_file(dest).ok();
+            pb.inc(entry.size - done);
+            return Ok(());
         }
+        pb.inc(piece.size);
+        done += piece.size;
     }
 
-    pb.finish_with_message(format!(
-        "{}  ({} files, {})",
-        "restore complete".green(),
-        total_files,
-        human(total_bytes)
+    #[cfg(unix)]
+    if restore_permissions {
+        apply_permissions(dest, entry);
+    }
+    #[cfg(not(unix))]
+    let _ = restore_permissions;
+    Ok(())
+}
+
+/// Where a streamed file is written until its checksum has been checked:
+/// a hidden name next to `dest`, so the rename stays on one file system.
+fn staging_path(dest: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(".archivum-partial");
+    dest.with_file_name(name)
+}
+
+/// `restore --stdin`: unpack a `create --stdout` stream in one pass.
+///
+/// Every file is hashed as it goes by, whether it is written or not. The
+/// hashes are checked against the index at the end of the stream; the
+/// per-member PAX checksums only matter when that index never arrives.
+pub fn restore_stream(
+    target: &Path,
+    filter: Option<&str>,
+    root: Option<&str>,
+    force: bool,
+    restore_permissions: bool,
+    out: &OutputCtx,
+) -> Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let header = pipe::read_header(&mut input)?;
+    let root = check_root(root, &header.roots)?;
+    let globset = build_filter(filter)?;
+
+    out.println(&format!(
+        "{} {} ({}) -> {}",
+        "Restoring:".cyan().bold(),
+        "<stdin>".yellow(),
+        header.compression.name(),
+        target.display().to_string().yellow()
     ));
+    out.println("");
 
-    // pass 4: deduped files - copy from already-restored originals
-    let dedup_entries: Vec<&IndexEntry> = idx
-        .entries
-        .iter()
-        .filter(|e| {
-            e.entry_type == EntryType::File
-                && e.dedup_of.is_some()
-                && matches_filter(&globset, &e.path)
-        })
-        .collect();
+    if out.dry_run {
+        out.dry(&format!("would create directory: {}", target.display()));
+    } else {
+        fs::create_dir_all(target)
+            .with_context(|| format!("Cannot create target dir {}", target.display()))?;
+    }
 
-    for entry in dedup_entries {
-        let original = entry.dedup_of.as_ref().unwrap();
-        if let Some(src) = dedup_done.get(original) {
-            let dest = safe_join(target, &entry.path)?;
-            if out.dry_run {
-                out.dry(&format!(
-                    "copy dedup {} from {}",
-                    dest.display(),
-                    src.display()
-                ));
-            } else {
-                if let Some(p) = dest.parent() {
-                    fs::create_dir_all(p)?;
+    let wanted = |path: &Path| -> Result<Option<PathBuf>> {
+        if !matches_filter(&globset, path) {
+            return Ok(None);
+        }
+        match dest_rel(root, path) {
+            Some(rel) => Ok(Some(safe_join(target, rel)?)),
+            None => Ok(None),
+        }
+    };
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("  {spinner:.cyan} Restoring  {bytes}  {bytes_per_sec}")
+            .unwrap(),
+    );
+
+    // path -> SHA-256 of what the stream carried (aliases included)
+    let mut seen: HashMap<PathBuf, String> = HashMap::new();
+    let mut pax_mismatch: Vec<PathBuf> = vec![];
+    let mut dirs: Vec<(PathBuf, IndexEntry)> = vec![];
+    let mut restored = 0u64;
+    let mut restored_bytes = 0u64;
+
+    let mut chunks = ChunkReader::new(&mut input);
+    let body: Result<()> = (|| {
+        let reader = header.compression.wrap_read(&mut chunks)?;
+        let mut archive = Archive::new(reader);
+        for item in archive.entries()? {
+            let mut item = item?;
+            let path = item.path()?.into_owned();
+
+            let mut expected = None;
+            let mut copies = vec![];
+            if let Some(pax) = item.pax_extensions()? {
+                for ext in pax {
+                    let ext = ext?;
+                    match ext.key() {
+                        Ok(PAX_SHA256) => expected = Some(ext.value()?.to_string()),
+                        Ok(PAX_COPY) => copies.push(path_from_bytes(ext.value_bytes())),
+                        _ => {}
+                    }
                 }
-                if dest.exists() && !force {
-                    continue;
+            }
+
+            let Some(entry) = IndexEntry::from_tar(path, item.header(), 0)? else {
+                continue;
+            };
+            let dest = wanted(&entry.path)?;
+
+            match entry.entry_type {
+                EntryType::Directory => {
+                    let Some(dest) = dest else { continue };
+                    if out.dry_run {
+                        out.dry(&format!("mkdir {}", dest.display()));
+                    } else {
+                        fs::create_dir_all(&dest)?;
+                        dirs.push((dest, entry));
+                    }
+                }
+                EntryType::Symlink => {
+                    let (Some(link_path), Some(link_target)) = (dest, &entry.symlink_target) else {
+                        continue;
+                    };
+                    if out.dry_run {
+                        out.dry(&format!(
+                            "symlink {} -> {}",
+                            link_path.display(),
+                            link_target.display()
+                        ));
+                        continue;
+                    }
+                    if !make_room(&link_path, force, out)? {
+                        continue;
+                    }
+                    #[cfg(unix)]
+                    std::os::unix::fs::symlink(link_target, &link_path).with_context(|| {
+                        format!("Cannot create symlink {}", link_path.display())
+                    })?;
+                    #[cfg(not(unix))]
+                    out.println("  symlinks skipped on non-Unix");
+                }
+                EntryType::File => {
+                    // the member itself plus any dedup aliases riding on it
+                    let mut outputs = vec![];
+                    for p in std::iter::once(&entry.path).chain(&copies) {
+                        if let Some(d) = wanted(p)? {
+                            outputs.push(d);
+                        }
+                    }
+                    if out.dry_run {
+                        for d in &outputs {
+                            out.dry(&format!("restore {} ({})", d.display(), human(entry.size)));
+                        }
+                        outputs.clear();
+                    }
+                    let mut targets = vec![];
+                    for d in outputs {
+                        if make_room(&d, force, out)? {
+                            if let Some(p) = d.parent() {
+                                fs::create_dir_all(p)?;
+                            }
+                            targets.push(d);
+                        }
+                    }
+
+                    // written under a staging name, and only moved into place
+                    // once the in-stream checksum has matched
+                    let staged = targets.first().map(|d| TempPath::new(staging_path(d)));
+                    let first = match &staged {
+                        Some(t) => Some(
+                            File::create(t.path())
+                                .with_context(|| format!("Cannot write {}", t.path().display()))?,
+                        ),
+                        None => None,
+                    };
+                    let mut sink = HashingWriter {
+                        inner: first,
+                        hasher: Sha256::new(),
+                        pb: &pb,
+                    };
+                    copy(&mut item, &mut sink)
+                        .with_context(|| format!("Stream broke inside {}", entry.path.display()))?;
+                    let sha = hex::encode(sink.hasher.finalize());
+                    drop(sink.inner);
+
+                    if expected.as_deref().is_some_and(|e| e != sha) {
+                        out.eprintln(&format!(
+                            "  {} {} (in-stream checksum mismatch, not written)",
+                            "CORRUPT".red().bold(),
+                            entry.path.display()
+                        ));
+                        pax_mismatch.push(entry.path.clone());
+                        pax_mismatch.extend(copies.iter().cloned());
+                        targets.clear();
+                    } else if let (Some(t), Some(d)) = (&staged, targets.first()) {
+                        fs::rename(t.path(), d)
+                            .with_context(|| format!("Cannot write {}", d.display()))?;
+                    }
+
+                    // the first destination is streamed, the rest are copies
+                    for d in targets.iter().skip(1) {
+                        fs::copy(&targets[0], d)
+                            .with_context(|| format!("Cannot write {}", d.display()))?;
+                    }
+                    for d in &targets {
+                        #[cfg(unix)]
+                        if restore_permissions {
+                            apply_permissions(d, &entry);
+                        }
+                        restored += 1;
+                        restored_bytes += entry.size;
+                    }
+
+                    for p in copies {
+                        seen.insert(p, sha.clone());
+                    }
+                    seen.insert(entry.path, sha);
+                }
+                _ => {
+                    let Some(dest) = dest else { continue };
+                    if out.dry_run {
+                        out.dry(&format!("mknod {}", dest.display()));
+                        continue;
+                    }
+                    if !make_room(&dest, force, out)? {
+                        continue;
+                    }
+                    #[cfg(unix)]
+                    match make_special(&dest, &entry) {
+                        Ok(()) => {
+                            if restore_permissions {
+                                apply_permissions(&dest, &entry);
+                            }
+                        }
+                        Err(e) => out.eprintln(&format!(
+                            "  {} cannot create {}: {}",
+                            "warning:".yellow(),
+                            dest.display(),
+                            e
+                        )),
+                    }
+                    #[cfg(not(unix))]
+                    out.println("  special files skipped on non-Unix");
                 }
-                fs::copy(src, &dest)?;
             }
         }
+        Ok(())
+    })();
+    pb.finish_and_clear();
+
+    let trailer = body
+        .context("Stream data is corrupt or truncated")
+        .and_then(|()| chunks.finish())
+        .and_then(|mut rest| pipe::read_trailer(&mut rest));
+    let idx = match trailer {
+        Ok(idx) => idx,
+        Err(e) => {
+            out.eprintln(&format!(
+                "  {} {} file(s) written before the stream failed; {} had a bad in-stream checksum",
+                "INCOMPLETE".red().bold(),
+                restored,
+                pax_mismatch.len()
+            ));
+            return Err(e);
+        }
+    };
+
+    #[cfg(unix)]
+    if restore_permissions {
+        // deepest first, so read-only parents don't block their children
+        for (dest, entry) in dirs.iter().rev() {
+            apply_permissions(dest, entry);
+        }
     }
+    #[cfg(not(unix))]
+    let _ = dirs;
 
-    out.println("");
+    let mut missing = 0usize;
+    let mut corrupt = 0usize;
+    for e in idx
+        .entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File)
+    {
+        match (seen.get(&e.path), &e.sha256) {
+            (None, _) => {
+                missing += 1;
+                out.eprintln(&format!(
+                    "  {} {}",
+                    "MISSING".red().bold(),
+                    e.path.display()
+                ));
+            }
+            // already reported, and left unwritten, when it went by
+            (Some(_), _) if pax_mismatch.contains(&e.path) => corrupt += 1,
+            (Some(got), Some(want)) if got != want => {
+                corrupt += 1;
+                out.eprintln(&format!(
+                    "  {} {}",
+                    "CORRUPT".red().bold(),
+                    e.path.display()
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    out.println(&format!(
+        "  {} {} file(s), {}  |  stream verified: {}",
+        "Restored".green(),
+        restored,
+        human(restored_bytes),
+        if missing + corrupt == 0 {
+            "OK".green().bold().to_string()
+        } else {
+            "FAILED".red().bold().to_string()
+        }
+    ));
     out.println(&format!(
         "  {} {}",
         "Restored to:".cyan().bold(),
         target.display().to_string().yellow()
     ));
 
+    if missing + corrupt > 0 {
+        anyhow::bail!(
+            "Stream verification failed: {} missing, {} corrupt",
+            missing,
+            corrupt
+        );
+    }
+    Ok(())
+}
+
+/// Validate `--root` against the archive's root prefixes.
+fn check_root<'a>(root: Option<&'a str>, roots: &[String]) -> Result<Option<&'a str>> {
+    let root = root.map(|r| r.trim_end_matches('/'));
+    if let Some(r) = root {
+        if roots.is_empty() {
+            anyhow::bail!("--root given, but this archive has a single unnamed root");
+        }
+        if !roots.iter().any(|p| p == r) {
+            anyhow::bail!(
+                "No root '{}' in this archive (roots: {})",
+                r,
+                roots.join(", ")
+            );
+        }
+    }
+    Ok(root)
+}
+
+/// Clear `path` for a new entry under `--force`; `false` if it exists and
+/// should be left alone.
+fn make_room(path: &Path, force: bool, out: &OutputCtx) -> Result<bool> {
+    if path.symlink_metadata().is_err() {
+        return Ok(true);
+    }
+    if force {
+        fs::remove_file(path).ok();
+        return Ok(true);
+    }
+    out.println(&format!(
+        "  {} {}",
+        "skip (exists):".dimmed(),
+        path.display()
+    ));
+    Ok(false)
+}
+
+/// Hashes everything written to it; writes it on to `inner` if there is one.
+struct HashingWriter<'a> {
+    inner: Option<File>,
+    hasher: Sha256,
+    pb: &'a ProgressBar,
+}
+
+impl Write for HashingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match &mut self.inner {
+            Some(f) => f.write(buf)?,
+            None => buf.len(),
+        };
+        self.hasher.update(&buf[..n]);
+        self.pb.inc(n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Some(f) => f.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Write a member's data to the first of `outputs` and copy it to the rest
+/// (its dedup aliases).
+fn write_outputs(
+    data: &mut dyn Read,
+    outputs: Vec<(&IndexEntry, PathBuf)>,
+    force: bool,
+    restore_permissions: bool,
+    out: &OutputCtx,
+) -> Result<()> {
+    // the first destination is streamed from the tar, the rest are
+    // copies of it
+    let mut written: Option<PathBuf> = None;
+    for (entry, out_path) in outputs {
+        if out_path.exists() && !force {
+            out.println(&format!(
+                "  {} {}",
+                "skip (exists):".dimmed(),
+                out_path.display()
+            ));
+            continue;
+        }
+
+        if let Some(p) = out_path.parent() {
+            fs::create_dir_all(p)?;
+        }
+
+        match &written {
+            None => {
+                let mut f = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&out_path)
+                    .with_context(|| format!("Cannot write {}", out_path.display()))?;
+                copy(data, &mut f)?;
+                written = Some(out_path.clone());
+            }
+            Some(src) => {
+                fs::copy(src, &out_path)
+                    .with_context(|| format!("Cannot write {}", out_path.display()))?;
+            }
+        }
+
+        #[cfg(unix)]
+        if restore_permissions {
+            apply_permissions(&out_path, entry);
+        }
+        #[cfg(not(unix))]
+        let _ = entry;
+    }
     Ok(())
 }
 
+/// A tar member to restore and every index entry (the file itself and its
+/// dedup aliases) that takes its content.
+struct Wanted<'a> {
+    size: u64,
+    sha256: Option<&'a str>,
+    outputs: Vec<(&'a IndexEntry, PathBuf)>,
+}
+
+/// Where an entry lands under the restore target: its index path, or with
+/// `--root` the part below that root. `None` for entries outside the root
+/// and for the root directory itself.
+fn dest_rel<'a>(root: Option<&str>, path: &'a Path) -> Option<&'a Path> {
+    match root {
+        None => Some(path),
+        Some(r) => path
+            .strip_prefix(r)
+            .ok()
+            .filter(|p| !p.as_os_str().is_empty()),
+    }
+}
+
 pub fn extract_single(
+    store: &Store,
     idx: &ArchivumIndex,
     index_dir: &Path,
     file: &Path,
@@ -343,21 +1047,28 @@ pub fn extract_single(
         (file, entry)
     };
 
-    let part_path = target_entry.part_path(index_dir, &idx.header);
+    let out_path = match output {
+        Some(p) => p.to_path_buf(),
+        None => file
+            .file_name()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| file.to_path_buf()),
+    };
 
-    let reader = idx.header.compression.wrap_reader(&part_path)?;
-    let mut archive = tar::Archive::new(reader);
+    // a stream that spans parts has one member in each, in order
+    let mut f = None;
+    for piece in target_entry.members() {
+        let part_path = piece.part_path(index_dir, &idx.header);
+        let reader = store.open_part(&part_path, &idx.header.compression)?;
+        let mut archive = tar::Archive::new(reader);
 
-    for item in archive.entries()? {
-        let mut item = item?;
-        if item.path()? == target_path {
-            let out_path = match output {
-                Some(p) => p.to_path_buf(),
-                None => file
-                    .file_name()
-                    .map(PathBuf::from)
-                    .unwrap_or_else(|| file.to_path_buf()),
-            };
+        let mut found = false;
+        for item in archive.entries()? {
+            let mut item = item?;
+            if is_manifest(&mut item)? || item.path()? != target_path {
+                continue;
+            }
+            found = true;
 
             if out.dry_run {
                 out.dry(&format!(
@@ -368,25 +1079,31 @@ pub fn extract_single(
                 return Ok(());
             }
 
-            if let Some(p) = out_path.parent() {
-                if !p.as_os_str().is_empty() {
-                    fs::create_dir_all(p)?;
+            if f.is_none() {
+                if let Some(p) = out_path.parent() {
+                    if !p.as_os_str().is_empty() {
+                        fs::create_dir_all(p)?;
+                    }
                 }
+                f = Some(
+                    File::create(&out_path)
+                        .with_context(|| format!("Cannot write {}", out_path.display()))?,
+                );
             }
-
-            let mut f = File::create(&out_path)
-                .with_context(|| format!("Cannot write {}", out_path.display()))?;
-            copy(&mut item, &mut f)?;
-            out.println(&format!(
-                "{} {}",
-                "Extracted:".green().bold(),
-                out_path.display().to_string().yellow()
-            ));
-            return Ok(());
+            copy(&mut item, f.as_mut().unwrap())?;
+            break;
+        }
+        if !found {
+            anyhow::bail!("File not found in tar part: {}", file.display());
         }
     }
 
-    anyhow::bail!("File not found in tar part: {}", file.display());
+    out.println(&format!(
+        "{} {}",
+        "Extracted:".green().bold(),
+        out_path.display().to_string().yellow()
+    ));
+    Ok(())
 }
 
 fn build_filter(pattern: Option<&str>) -> Result<Option<GlobSet>> {
@@ -415,3 +1132,28 @@ fn apply_permissions(path: &Path, entry: &IndexEntry) {
         let _ = fs::set_permissions(path, perms);
     }
 }
+
+// needs CAP_MKNOD for device nodes; FIFOs work for any user
+#[cfg(unix)]
+fn make_special(path: &Path, entry: &IndexEntry) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let kind = match entry.entry_type {
+        EntryType::CharDevice => libc::S_IFCHR,
+        EntryType::BlockDevice => libc::S_IFBLK,
+        _ => libc::S_IFIFO,
+    };
+    let perm = (entry.unix_mode.unwrap_or(0o644) & 0o7777) as libc::mode_t;
+    let dev = libc::makedev(
+        entry.dev_major.unwrap_or(0) as _,
+        entry.dev_minor.unwrap_or(0) as _,
+    );
+    // SAFETY: c_path is a valid NUL-terminated string for the duration of the call
+    let rc = unsafe { libc::mknod(c_path.as_ptr(), kind | perm, dev) };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,516 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Best-effort reading of damaged parts.
+//!
+//! A part is decompressed to a scratch file for as long as the decoder
+//! accepts it. When it stops, decoding restarts at the next frame (zstd,
+//! lz4), member (gzip) or stream (bzip2) magic in the compressed bytes, if
+//! there is one. The tar stream in the recovered data is then walked member
+//! by member; a header that does not parse is skipped by scanning ahead for
+//! the next block with a ustar magic and a valid checksum. Every member
+//! whose data lies wholly inside recovered data comes back.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tar::Archive;
+
+use crate::compress::CompressionAlgo;
+use crate::index::raw_path;
+use crate::output::OutputCtx;
+use crate::source::path_from_bytes;
+use crate::store::Store;
+use crate::tar_writer::{PAX_COPY, PAX_MANIFEST};
+
+const BLOCK: u64 = 512;
+/// How much of the recovered data a header search reads at a time.
+const SCAN_CHUNK: usize = 1 << 20;
+
+const BAD_HEADER: &str = "invalid tar header";
+
+static SCRATCH: AtomicUsize = AtomicUsize::new(0);
+
+/// A stretch of a part that could not be read.
+#[derive(Clone, Serialize)]
+pub struct Damage {
+    /// `compression` (offset in the part file) or `tar` (offset in the
+    /// recovered, decompressed data)
+    pub layer: &'static str,
+    pub offset: u64,
+    /// Where reading picked up again; `None` if nothing after it was usable.
+    pub resumed_at: Option<u64>,
+    pub error: String,
+}
+
+/// A tar member found in a salvaged part.
+pub struct Member {
+    pub path: PathBuf,
+    pub header: tar::Header,
+    /// Dedup aliases named by its `ARCHIVUM.copy` records.
+    pub copies: Vec<PathBuf>,
+    /// The part's manifest rather than an archived entry.
+    pub manifest: bool,
+    /// False when the recovered data ends before the member's does.
+    pub complete: bool,
+    data: u64,
+    size: u64,
+}
+
+/// What could be read from one part. The recovered data lives in a scratch
+/// file that is removed on drop.
+pub struct Salvaged {
+    pub members: Vec<Member>,
+    pub damage: Vec<Damage>,
+    scratch: PathBuf,
+}
+
+impl Salvaged {
+    /// The data of `m`, which must be complete.
+    pub fn open(&self, m: &Member) -> Result<impl Read + use<>> {
+        Window::open(&self.scratch, m.data, m.data + m.size)
+            .with_context(|| format!("Cannot read salvaged {}", m.path.display()))
+    }
+
+    pub fn complete(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|m| m.complete && !m.manifest)
+            .count()
+    }
+}
+
+impl Drop for Salvaged {
+    fn drop(&mut self) {
+        fs::remove_file(&self.scratch).ok();
+    }
+}
+
+/// Read what can be read of the part at `path`.
+///
+/// Needs room in the temp directory for the part's decompressed size.
+pub fn salvage_part(store: &Store, path: &Path, algo: &CompressionAlgo) -> Result<Salvaged> {
+    let scratch = std::env::temp_dir().join(format!(
+        "archivum-salvage-{}-{}",
+        std::process::id(),
+        SCRATCH.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&scratch)
+        .with_context(|| format!("Cannot create {}", scratch.display()))?;
+    // from here on the scratch file goes away with `salvaged`
+    let mut salvaged = Salvaged {
+        members: vec![],
+        damage: vec![],
+        scratch,
+    };
+
+    let segments = unpack(store, path, algo, &mut file, &mut salvaged.damage)?;
+    file.flush()?;
+    drop(file);
+
+    let mut members: Vec<Member> = vec![];
+    for (start, end) in segments {
+        let mut at = start;
+        loop {
+            let (last, err) = read_members(&salvaged.scratch, at, end, &mut members)?;
+            let Some(error) = err else {
+                break;
+            };
+            let next = next_header(&salvaged.scratch, last + 1, end)?;
+            salvaged.damage.push(Damage {
+                layer: "tar",
+                offset: last,
+                resumed_at: next,
+                error,
+            });
+            match next {
+                Some(n) => at = n,
+                None => break,
+            }
+        }
+    }
+
+    // a member can turn up twice when a restart point lies inside data
+    // that had already been read; keep the best copy
+    let mut seen: HashMap<(PathBuf, bool), usize> = HashMap::new();
+    for m in members {
+        let key = (m.path.clone(), m.manifest);
+        match seen.get(&key) {
+            Some(&i) => {
+                if m.complete && !salvaged.members[i].complete {
+                    salvaged.members[i] = m;
+                }
+            }
+            None => {
+                seen.insert(key, salvaged.members.len());
+                salvaged.members.push(m);
+            }
+        }
+    }
+    Ok(salvaged)
+}
+
+/// Decompress `path` into `out`, restarting after errors where the format
+/// allows. Returns the stretches of `out` that hold contiguous data.
+fn unpack(
+    store: &Store,
+    path: &Path,
+    algo: &CompressionAlgo,
+    out: &mut File,
+    damage: &mut Vec<Damage>,
+) -> Result<Vec<(u64, u64)>> {
+    let Some(magic) = frame_magic(algo) else {
+        let n = io::copy(&mut store.reader(path, 0, None)?, out)
+            .with_context(|| format!("Cannot read {}", store.location(path)))?;
+        return Ok(vec![(0, n)]);
+    };
+
+    let mut segments = vec![];
+    let mut start = 0u64;
+    loop {
+        let seg_start = out.stream_position()?;
+        let failed = decode_from(store, path, algo, start, out)?;
+        let seg_end = out.stream_position()?;
+        if seg_end > seg_start {
+            segments.push((seg_start, seg_end));
+        }
+        let Some((at, error)) = failed else {
+            break;
+        };
+        // a decoder can fail before consuming anything, such as on a bad
+        // frame header; searching from there would find the same magic
+        let next = find_magic(store, path, at.max(start + 1), magic)?;
+        damage.push(Damage {
+            layer: "compression",
+            offset: at,
+            resumed_at: next,
+            error,
+        });
+        match next {
+            Some(n) => start = n,
+            None => break,
+        }
+    }
+    Ok(segments)
+}
+
+/// Decode everything from `offset` on, across frame (member, stream)
+/// boundaries. Returns where in the part decoding failed and why, or
+/// `None` if it reached the end.
+fn decode_from(
+    store: &Store,
+    path: &Path,
+    algo: &CompressionAlgo,
+    offset: u64,
+    out: &mut File,
+) -> Result<Option<(u64, String)>> {
+    // the decoders read through our buffer, so what they consumed is known
+    let mut src = BufReader::new(Counted {
+        inner: store.reader(path, offset, None)?,
+        n: 0,
+    });
+    let res = (|| -> io::Result<()> {
+        let mut dec: Box<dyn Read + '_> = match algo {
+            CompressionAlgo::None => Box::new(&mut src),
+            CompressionAlgo::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(&mut src)),
+            CompressionAlgo::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(&mut src)),
+            CompressionAlgo::Zstd => Box::new(zstd::Decoder::with_buffer(&mut src)?),
+            CompressionAlgo::Lz4 => {
+                // the decoder stops at the end of each frame
+                while !src.fill_buf()?.is_empty() {
+                    io::copy(&mut lz4_flex::frame::FrameDecoder::new(&mut src), out)?;
+                }
+                return Ok(());
+            }
+        };
+        io::copy(&mut dec, out)?;
+        Ok(())
+    })();
+    let at = offset + src.get_ref().n - src.buffer().len() as u64;
+    Ok(res.err().map(|e| (at, e.to_string())))
+}
+
+struct Counted<R> {
+    inner: R,
+    n: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.n += n as u64;
+        Ok(n)
+    }
+}
+
+/// Bytes every independently decodable unit of `algo` starts with.
+fn frame_magic(algo: &CompressionAlgo) -> Option<&'static [u8]> {
+    match algo {
+        CompressionAlgo::None => None,
+        CompressionAlgo::Gzip => Some(&[0x1f, 0x8b, 0x08]),
+        CompressionAlgo::Bzip2 => Some(b"BZh"),
+        CompressionAlgo::Lz4 => Some(&[0x04, 0x22, 0x4d, 0x18]),
+        CompressionAlgo::Zstd => Some(&[0x28, 0xb5, 0x2f, 0xfd]),
+    }
+}
+
+/// Offset of the first `magic` in `path` at or after `from`.
+fn find_magic(store: &Store, path: &Path, from: u64, magic: &[u8]) -> Result<Option<u64>> {
+    let mut r = store.reader(path, from, None)?;
+    let mut buf = vec![0u8; SCAN_CHUNK];
+    let (mut base, mut kept) = (from, 0usize);
+    loop {
+        let n = r.read(&mut buf[kept..])?;
+        if n == 0 {
+            return Ok(None);
+        }
+        let filled = kept + n;
+        if let Some(i) = buf[..filled].windows(magic.len()).position(|w| w == magic) {
+            return Ok(Some(base + i as u64));
+        }
+        // a magic may straddle two reads
+        let keep = (magic.len() - 1).min(filled);
+        buf.copy_within(filled - keep..filled, 0);
+        base += (filled - keep) as u64;
+        kept = keep;
+    }
+}
+
+/// Walk tar members from `at` until `end`, the end of the archive or an
+/// error. Returns where the last good member ended and the error, if any.
+fn read_members(
+    scratch: &Path,
+    at: u64,
+    end: u64,
+    members: &mut Vec<Member>,
+) -> Result<(u64, Option<String>)> {
+    let mut archive = Archive::new(Window::open(scratch, at, end)?);
+    archive.set_ignore_zeros(true);
+    let mut last = at;
+    let entries = match archive.entries_with_seek() {
+        Ok(e) => e,
+        Err(_) => return Ok((last, Some(BAD_HEADER.into()))),
+    };
+    for item in entries {
+        let mut item = match item {
+            Ok(i) => i,
+            // the crate's message quotes the raw field bytes
+            Err(_) => return Ok((last, Some(BAD_HEADER.into()))),
+        };
+        let data = at + item.raw_file_position();
+        let size = item.size();
+        last = (data + size.div_ceil(BLOCK) * BLOCK).min(end);
+        let path = match item.path() {
+            Ok(p) => p.into_owned(),
+            Err(_) => continue,
+        };
+        let (mut copies, mut manifest) = (vec![], false);
+        if let Ok(Some(pax)) = item.pax_extensions() {
+            for ext in pax.flatten() {
+                match ext.key() {
+                    Ok(PAX_COPY) => copies.push(path_from_bytes(ext.value_bytes())),
+                    Ok(PAX_MANIFEST) => manifest = true,
+                    _ => {}
+                }
+            }
+        }
+        members.push(Member {
+            path,
+            header: item.header().clone(),
+            copies,
+            manifest,
+            complete: data + size <= end,
+            data,
+            size,
+        });
+    }
+    Ok((last, None))
+}
+
+/// The first block at or after `from` that is a ustar header with a valid
+/// checksum. Headers are only block-aligned relative to the start of the
+/// tar stream, and a decoding gap loses that, so every offset is tried.
+fn next_header(scratch: &Path, from: u64, end: u64) -> Result<Option<u64>> {
+    let mut f = File::open(scratch)?;
+    let mut buf = vec![0u8; SCAN_CHUNK + BLOCK as usize];
+    let mut pos = from;
+    while pos + BLOCK <= end {
+        let len = (end - pos).min(buf.len() as u64) as usize;
+        f.seek(SeekFrom::Start(pos))?;
+        f.read_exact(&mut buf[..len])?;
+        for i in 0..=len - BLOCK as usize {
+            let block = &buf[i..i + BLOCK as usize];
+            if &block[257..262] == b"ustar" && is_header(block) {
+                return Ok(Some(pos + i as u64));
+            }
+        }
+        pos += (len + 1 - BLOCK as usize) as u64;
+    }
+    Ok(None)
+}
+
+fn is_header(block: &[u8]) -> bool {
+    let Ok(stored) = tar::Header::from_byte_slice(block).cksum() else {
+        return false;
+    };
+    // the checksum field itself counts as spaces
+    let sum: u32 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                b as u32
+            }
+        })
+        .sum();
+    stored == sum
+}
+
+/// Bytes `start..end` of a file, seekable within that range.
+struct Window {
+    file: File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Window {
+    fn open(path: &Path, start: u64, end: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            file,
+            start,
+            len: end - start,
+            pos: 0,
+        })
+    }
+}
+
+impl Read for Window {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.len.saturating_sub(self.pos);
+        let want = buf.len().min(left as usize);
+        let n = self.file.read(&mut buf[..want])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Window {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        let pos = match to {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+            SeekFrom::End(d) => self.len.checked_add_signed(d),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before start"))?;
+        self.file.seek(SeekFrom::Start(self.start + pos))?;
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+/// The `--salvage` report: damage found per part and every file that could
+/// not be recovered.
+#[derive(Default, Serialize)]
+pub struct Report {
+    pub parts: Vec<PartReport>,
+    pub lost: Vec<Lost>,
+}
+
+#[derive(Serialize)]
+pub struct PartReport {
+    pub part: String,
+    pub recovered: usize,
+    pub damage: Vec<Damage>,
+}
+
+#[derive(Serialize)]
+pub struct Lost {
+    #[serde(with = "raw_path")]
+    pub path: PathBuf,
+    pub part: String,
+    pub reason: String,
+}
+
+impl Report {
+    /// Record what salvaging `part` found; parts that read cleanly are
+    /// listed too, with no damage.
+    pub fn part(&mut self, part: String, s: &Salvaged, out: &OutputCtx) {
+        for d in &s.damage {
+            out.eprintln(&format!(
+                "  {} {} damage at byte {}: {}",
+                "salvage:".yellow(),
+                d.layer,
+                d.offset,
+                d.error
+            ));
+        }
+        self.parts.push(PartReport {
+            part,
+            recovered: s.complete(),
+            damage: s.damage.clone(),
+        });
+    }
+
+    pub fn lose(&mut self, path: &Path, part: &str, reason: &str) {
+        self.lost.push(Lost {
+            path: path.to_path_buf(),
+            part: part.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Print the outcome and write the report to `path`.
+    pub fn finish(&mut self, path: &Path, out: &OutputCtx) -> Result<()> {
+        self.lost.sort_by(|a, b| a.path.cmp(&b.path));
+        let damaged = self.parts.iter().filter(|p| !p.damage.is_empty()).count();
+        out.println("");
+        out.println(&format!(
+            "  {} {} of {} part(s) damaged, {} file(s) lost",
+            "Salvage:".cyan().bold(),
+            damaged,
+            self.parts.len(),
+            self.lost.len()
+        ));
+        for l in &self.lost {
+            out.println(&format!(
+                "    {} {} ({})",
+                "lost".red(),
+                l.path.display(),
+                l.reason
+            ));
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Cannot write report {}", path.display()))?;
+        out.println(&format!("  Report written to {}", path.display()));
+        Ok(())
+    }
+}
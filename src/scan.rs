@@ -16,11 +16,13 @@
  */
 
 use anyhow::Result;
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::Gitignore;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+
+use crate::selection::{IgnoreStack, Selection};
 
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
@@ -31,6 +33,30 @@ pub enum EntryType {
     File,
     Directory,
     Symlink,
+    /// character device node; major/minor live on the entry
+    #[serde(rename = "chardevice")]
+    CharDevice,
+    #[serde(rename = "blockdevice")]
+    BlockDevice,
+    Fifo,
+}
+
+impl EntryType {
+    /// Device nodes and FIFOs: no content, recreated from metadata alone.
+    pub fn is_special(&self) -> bool {
+        matches!(self, Self::CharDevice | Self::BlockDevice | Self::Fifo)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Directory => "dir",
+            Self::Symlink => "symlink",
+            Self::CharDevice => "chardev",
+            Self::BlockDevice => "blockdev",
+            Self::Fifo => "fifo",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,18 +67,140 @@ pub struct ScanEntry {
     pub mtime: Option<u64>,
     pub unix_mode: Option<u32>,
     pub symlink_target: Option<PathBuf>,
+    pub dev_major: Option<u32>,
+    pub dev_minor: Option<u32>,
+}
+
+/// A path that could not be scanned or archived. Recorded in the index's
+/// `errors` section instead of aborting the run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    #[serde(with = "crate::index::raw_path")]
+    pub path: PathBuf,
+    pub stage: ErrorStage,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorStage {
+    Scan,
+    Archive,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    PermissionDenied,
+    Vanished,
+    Io,
+}
+
+impl ErrorKind {
+    pub fn from_io(kind: std::io::ErrorKind) -> Self {
+        match kind {
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            std::io::ErrorKind::NotFound => Self::Vanished,
+            _ => Self::Io,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PermissionDenied => "permission denied",
+            Self::Vanished => "vanished",
+            Self::Io => "I/O error",
+        }
+    }
+}
+
+impl ScanError {
+    pub fn new(path: PathBuf, stage: ErrorStage, err: &std::io::Error) -> Self {
+        Self {
+            path,
+            stage,
+            kind: ErrorKind::from_io(err.kind()),
+            message: err.to_string(),
+        }
+    }
 }
 
-pub fn scan_directory(root: &Path, excludes: &[String]) -> Result<Vec<ScanEntry>> {
-    let excludeset = build_globset(excludes)?;
+/// Returned when a run finished but skipped some paths; `main` maps it to
+/// its own exit code so scheduled jobs can tell it apart from a failure.
+#[derive(Debug)]
+pub struct PartialSuccess(pub usize);
+
+impl std::fmt::Display for PartialSuccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} path(s) were skipped; see the `errors` section of the index",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for PartialSuccess {}
+
+/// What a scan found besides its entries.
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    /// unreadable or vanished paths; the walk carries on past them
+    pub errors: Vec<ScanError>,
+    /// ignore files that were applied, relative to the root
+    pub ignore_sources: Vec<PathBuf>,
+}
+
+/// Walk `root`, keeping what `selection` puts in scope.
+pub fn scan_directory(
+    root: &Path,
+    selection: &Selection,
+    report: &mut ScanReport,
+) -> Result<Vec<ScanEntry>> {
     let mut out = Vec::new();
+    let errors = &mut report.errors;
 
-    for entry in WalkDir::new(root)
-        .follow_links(false)
-        .sort_by_file_name()
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
+    let mut ignores = IgnoreStack::default();
+    let mut ignore_sources = vec![];
+    let mut ignore_errors = vec![];
+
+    let walk = selection.walker(root).into_iter().filter_entry(|e| {
+        let is_dir = e.file_type().is_dir();
+        if is_dir && e.depth() > 0 && selection.prunes_dir(e.path()) {
+            return false;
+        }
+        if !selection.uses_ignore_files() {
+            return true;
+        }
+        ignores.enter(e.depth());
+        if e.depth() > 0 && ignores.is_ignored(e.path(), is_dir) {
+            return false;
+        }
+        if is_dir {
+            if let Some(gi) =
+                selection.load_ignores(e.path(), &mut ignore_sources, &mut ignore_errors)
+            {
+                ignores.push(e.depth(), gi);
+            }
+        }
+        true
+    });
+
+    for entry in walk {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                let path = e.path().unwrap_or(root);
+                let rel = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+                let io = e
+                    .io_error()
+                    .map(|io| std::io::Error::new(io.kind(), io.to_string()))
+                    .unwrap_or_else(|| std::io::Error::other(e.to_string()));
+                errors.push(ScanError::new(rel, ErrorStage::Scan, &io));
+                continue;
+            }
+        };
         let path = entry.path();
         let rel = path.strip_prefix(root)?.to_path_buf();
 
@@ -60,63 +208,267 @@ pub fn scan_directory(root: &Path, excludes: &[String]) -> Result<Vec<ScanEntry>
             continue;
         }
 
-        if excludeset.is_match(&rel) {
-            continue;
+        if let Some(se) = scan_path(path, rel, selection, errors) {
+            out.push(se);
+        }
+    }
+
+    record_ignores(root, ignore_sources, ignore_errors, report);
+    Ok(out)
+}
+
+/// Replays the pruning a walk of `root` does, for paths taken from a file
+/// list: a listed path is kept only if `scan_directory` would reach it.
+pub struct WalkRules<'a> {
+    root: &'a Path,
+    selection: &'a Selection,
+    /// ignore matcher of each directory looked at so far
+    ignores: HashMap<PathBuf, Option<Gitignore>>,
+    ignore_sources: Vec<PathBuf>,
+    ignore_errors: Vec<(PathBuf, ignore::Error)>,
+    #[cfg(unix)]
+    root_dev: Option<u64>,
+}
+
+impl<'a> WalkRules<'a> {
+    pub fn new(root: &'a Path, selection: &'a Selection) -> Self {
+        Self {
+            root,
+            selection,
+            ignores: HashMap::new(),
+            ignore_sources: vec![],
+            ignore_errors: vec![],
+            #[cfg(unix)]
+            root_dev: fs::metadata(root).ok().map(|m| m.dev()),
+        }
+    }
+
+    /// Whether the walk would yield `rel`: within the depth limit, not in
+    /// or under a tagged or ignored directory, and not below a mount point
+    /// with `one_file_system`. A path that cannot be looked at is let
+    /// through so `scan_path` records the error.
+    pub fn reaches(&mut self, rel: &Path) -> bool {
+        let comps: Vec<_> = rel.components().collect();
+        if !self.selection.within_depth(comps.len()) {
+            return false;
+        }
+
+        let mut stack = IgnoreStack::default();
+        let mut path = self.root.to_path_buf();
+        self.push_ignores(&mut stack, 0, &path);
+        for (i, c) in comps.iter().enumerate() {
+            path.push(c);
+            let depth = i + 1;
+            let Ok(meta) = fs::symlink_metadata(&path) else {
+                return true;
+            };
+            let is_dir = meta.is_dir();
+            if is_dir && self.selection.prunes_dir(&path) {
+                return false;
+            }
+            if stack.is_ignored(&path, is_dir) {
+                return false;
+            }
+            if depth == comps.len() {
+                break;
+            }
+            if !self.same_file_system(&meta) {
+                return false;
+            }
+            self.push_ignores(&mut stack, depth, &path);
+        }
+        true
+    }
+
+    /// Record the ignore files read and any that failed, as a walk does.
+    pub fn finish(self, report: &mut ScanReport) {
+        record_ignores(self.root, self.ignore_sources, self.ignore_errors, report);
+    }
+
+    fn push_ignores(&mut self, stack: &mut IgnoreStack, depth: usize, dir: &Path) {
+        if !self.selection.uses_ignore_files() {
+            return;
+        }
+        let gi = self.ignores.entry(dir.to_path_buf()).or_insert_with(|| {
+            self.selection
+                .load_ignores(dir, &mut self.ignore_sources, &mut self.ignore_errors)
+        });
+        if let Some(gi) = gi {
+            stack.push(depth, gi.clone());
         }
+    }
 
-        let meta = fs::symlink_metadata(path)?;
+    #[cfg(unix)]
+    fn same_file_system(&self, meta: &fs::Metadata) -> bool {
+        !self.selection.one_file_system() || self.root_dev.is_none_or(|dev| dev == meta.dev())
+    }
 
-        #[cfg(unix)]
-        let (mtime, mode) = (Some(meta.mtime() as u64), Some(meta.mode()));
+    #[cfg(not(unix))]
+    fn same_file_system(&self, _meta: &fs::Metadata) -> bool {
+        true
+    }
+}
 
-        #[cfg(not(unix))]
-        let (mtime, mode) = {
-            let m = meta.modified().ok().and_then(|t| {
-                t.duration_since(std::time::UNIX_EPOCH)
-                    .ok()
-                    .map(|d| d.as_secs())
-            });
-            (m, None)
+/// Add the ignore files a scan applied to `report`, relative to `root`.
+fn record_ignores(
+    root: &Path,
+    sources: Vec<PathBuf>,
+    errors: Vec<(PathBuf, ignore::Error)>,
+    report: &mut ScanReport,
+) {
+    // an ignore file that could not be read or parsed means paths it should
+    // have hidden may be in the archive; record it like any other failure
+    for (path, err) in errors {
+        let rel = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        let io = match err.io_error() {
+            Some(io) => std::io::Error::new(io.kind(), err.to_string()),
+            None => std::io::Error::other(format!("invalid ignore file: {err}")),
         };
+        report
+            .errors
+            .push(ScanError::new(rel, ErrorStage::Scan, &io));
+    }
+    report.ignore_sources.extend(
+        sources
+            .into_iter()
+            .map(|p| p.strip_prefix(root).map(Path::to_path_buf).unwrap_or(p)),
+    );
+}
 
-        if meta.is_symlink() {
-            let target = fs::read_link(path).ok();
-            out.push(ScanEntry {
-                relative_path: rel,
-                entry_type: EntryType::Symlink,
-                size: 0,
-                mtime,
-                unix_mode: mode,
-                symlink_target: target,
-            });
-        } else if meta.is_dir() {
-            out.push(ScanEntry {
-                relative_path: rel,
-                entry_type: EntryType::Directory,
-                size: 0,
-                mtime,
-                unix_mode: mode,
-                symlink_target: None,
-            });
-        } else if meta.is_file() {
-            out.push(ScanEntry {
-                relative_path: rel,
-                entry_type: EntryType::File,
-                size: meta.len(),
-                mtime,
-                unix_mode: mode,
-                symlink_target: None,
-            });
+/// Stat one path and turn it into an entry if `selection` keeps it. Paths
+/// that cannot be stat'ed go to `errors`.
+pub fn scan_path(
+    path: &Path,
+    rel: PathBuf,
+    selection: &Selection,
+    errors: &mut Vec<ScanError>,
+) -> Option<ScanEntry> {
+    if selection.is_excluded(&rel) {
+        return None;
+    }
+
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(e) => {
+            errors.push(ScanError::new(rel, ErrorStage::Scan, &e));
+            return None;
         }
+    };
+
+    let mtime = mtime_of(&meta);
+
+    #[cfg(unix)]
+    let mode = Some(meta.mode());
+
+    #[cfg(not(unix))]
+    let mode = None;
+
+    if !meta.is_dir() && !selection.is_included(&rel) {
+        return None;
+    }
+    if meta.is_file() && !selection.file_in_range(meta.len(), mtime) {
+        return None;
     }
 
-    Ok(out)
+    if meta.is_symlink() {
+        let target = fs::read_link(path).ok();
+        Some(ScanEntry {
+            relative_path: rel,
+            entry_type: EntryType::Symlink,
+            size: 0,
+            mtime,
+            unix_mode: mode,
+            symlink_target: target,
+            dev_major: None,
+            dev_minor: None,
+        })
+    } else if meta.is_dir() {
+        Some(ScanEntry {
+            relative_path: rel,
+            entry_type: EntryType::Directory,
+            size: 0,
+            mtime,
+            unix_mode: mode,
+            symlink_target: None,
+            dev_major: None,
+            dev_minor: None,
+        })
+    } else if meta.is_file() {
+        Some(ScanEntry {
+            relative_path: rel,
+            entry_type: EntryType::File,
+            size: meta.len(),
+            mtime,
+            unix_mode: mode,
+            symlink_target: None,
+            dev_major: None,
+            dev_minor: None,
+        })
+    } else if let Some((entry_type, major, minor)) = special_kind(&meta) {
+        Some(ScanEntry {
+            relative_path: rel,
+            entry_type,
+            size: 0,
+            mtime,
+            unix_mode: mode,
+            symlink_target: None,
+            dev_major: major,
+            dev_minor: minor,
+        })
+    } else {
+        // sockets are skipped: they only exist while their owner is listening
+        None
+    }
+}
+
+/// What a scan would record `meta` as; `None` for sockets.
+pub fn kind_of(meta: &fs::Metadata) -> Option<EntryType> {
+    if meta.is_symlink() {
+        Some(EntryType::Symlink)
+    } else if meta.is_dir() {
+        Some(EntryType::Directory)
+    } else if meta.is_file() {
+        Some(EntryType::File)
+    } else {
+        special_kind(meta).map(|(kind, ..)| kind)
+    }
 }
 
-fn build_globset(patterns: &[String]) -> Result<GlobSet> {
-    let mut builder = GlobSetBuilder::new();
-    for p in patterns {
-        builder.add(Glob::new(p)?);
+/// Modification time in whole seconds, as stored in the index.
+pub fn mtime_of(meta: &fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        Some(meta.mtime() as u64)
+    }
+
+    #[cfg(not(unix))]
+    {
+        meta.modified().ok().and_then(|t| {
+            t.duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs())
+        })
     }
-    Ok(builder.build()?)
+}
+
+#[cfg(unix)]
+fn special_kind(meta: &fs::Metadata) -> Option<(EntryType, Option<u32>, Option<u32>)> {
+    use std::os::unix::fs::FileTypeExt;
+    let ft = meta.file_type();
+    let rdev = meta.rdev() as libc::dev_t;
+    let (major, minor) = (libc::major(rdev) as u32, libc::minor(rdev) as u32);
+    if ft.is_char_device() {
+        Some((EntryType::CharDevice, Some(major), Some(minor)))
+    } else if ft.is_block_device() {
+        Some((EntryType::BlockDevice, Some(major), Some(minor)))
+    } else if ft.is_fifo() {
+        Some((EntryType::Fifo, None, None))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_meta: &fs::Metadata) -> Option<(EntryType, Option<u32>, Option<u32>)> {
+    None
 }
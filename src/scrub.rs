@@ -0,0 +1,300 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Scrub history: `verify --journal` appends one line per run to
+// scrub.arc.jsonl next to the index, and `scrub-report` reads them back to
+// show how an archive has held up and when each damaged part first failed.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::output::OutputCtx;
+use crate::store::Store;
+use crate::utils::{fmt_time, now};
+
+pub const JOURNAL: &str = "scrub.arc.jsonl";
+
+/// One `verify` run, as journaled.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+    pub started_at_unix: u64,
+    pub started_at_human: String,
+    pub duration_secs: f64,
+    /// "full", or "quick" for `verify --quick`, whose counts are of parts
+    pub mode: String,
+    pub passed: bool,
+    /// false when the run stopped at its first problem
+    pub complete: bool,
+    pub ok: usize,
+    pub corrupt: usize,
+    pub missing: usize,
+    /// parts that have lost data, named relative to the index
+    pub damaged_parts: Vec<String>,
+    /// entries and parts with problems
+    pub failing: Vec<String>,
+}
+
+impl Run {
+    pub fn new<'a>(
+        mode: &str,
+        started: Instant,
+        complete: bool,
+        (ok, corrupt, missing): (usize, usize, usize),
+        damaged_parts: Vec<String>,
+        failing: impl Iterator<Item = &'a str>,
+    ) -> Self {
+        let mut seen = BTreeSet::new();
+        let failing: Vec<String> = failing
+            .filter(|f| seen.insert(*f))
+            .map(String::from)
+            .collect();
+        let started_at_unix = now().saturating_sub(started.elapsed().as_secs());
+        Self {
+            started_at_unix,
+            started_at_human: fmt_time(started_at_unix),
+            duration_secs: (started.elapsed().as_secs_f64() * 1000.0).round() / 1000.0,
+            mode: mode.into(),
+            passed: complete && failing.is_empty() && damaged_parts.is_empty(),
+            complete,
+            ok,
+            corrupt,
+            missing,
+            damaged_parts,
+            failing,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match (self.passed, self.complete) {
+            (true, _) => "PASS",
+            (false, true) => "FAIL",
+            (false, false) => "STOPPED",
+        }
+    }
+}
+
+fn journal_path(index_path: &Path) -> PathBuf {
+    index_path.with_file_name(JOURNAL)
+}
+
+/// The runs journaled next to `index_path`, oldest first, and how many
+/// lines could not be read (a write cut short, say).
+fn read(store: &Store, index_path: &Path) -> Result<(Vec<Run>, usize)> {
+    let path = journal_path(index_path);
+    if !store.exists(&path)? {
+        return Ok((vec![], 0));
+    }
+    let mut text = String::new();
+    store
+        .reader(&path, 0, None)?
+        .read_to_string(&mut text)
+        .with_context(|| format!("Cannot read {}", store.location(&path)))?;
+    let (mut runs, mut bad) = (vec![], 0);
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(run) => runs.push(run),
+            Err(_) => bad += 1,
+        }
+    }
+    Ok((runs, bad))
+}
+
+/// Add `run` to the journal next to `index_path`. Stores cannot append,
+/// so the journal is rewritten whole, through a temporary name.
+pub fn append(store: &Store, index_path: &Path, run: &Run, out: &OutputCtx) -> Result<()> {
+    let path = journal_path(index_path);
+    if out.dry_run {
+        out.dry(&format!(
+            "would journal the run in {}",
+            store.location(&path)
+        ));
+        return Ok(());
+    }
+    let mut text = if store.exists(&path)? {
+        let mut s = String::new();
+        store.reader(&path, 0, None)?.read_to_string(&mut s)?;
+        s
+    } else {
+        String::new()
+    };
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&serde_json::to_string(run)?);
+    text.push('\n');
+    store.write_file(&path, &mut text.as_bytes(), text.len() as u64)?;
+    out.println(&format!("  {} {}", "Run journaled in".dimmed(), JOURNAL));
+    Ok(())
+}
+
+/// When a part's damage showed up, as far as the journal tells.
+#[derive(Serialize)]
+struct PartHistory<'a> {
+    part: &'a str,
+    /// the first run that found it damaged
+    first_damaged: &'a str,
+    /// the last complete run before that which found it intact
+    last_clean: Option<&'a str>,
+    /// whether it is damaged as of the latest run: found so by that run, or
+    /// else by the newest complete one; unknown without a complete run
+    damaged_now: Option<bool>,
+}
+
+/// `scrub-report`: the journaled runs, newest `last` of them, with what
+/// each found newly damaged, and the history of every damaged part.
+pub fn report(store: &Store, index_path: &Path, last: usize, out: &OutputCtx) -> Result<()> {
+    let (runs, bad) = read(store, index_path)?;
+    if runs.is_empty() {
+        anyhow::bail!(
+            "No scrub runs journaled for {} (run `archivum verify --journal` first)",
+            store.location(index_path)
+        );
+    }
+
+    // a part is new damage when the complete run before did not find it
+    // damaged; a stopped run may simply not have reached it
+    let mut newly: Vec<Vec<&str>> = vec![];
+    for (i, run) in runs.iter().enumerate() {
+        let before: BTreeSet<&str> = runs[..i]
+            .iter()
+            .rev()
+            .find(|r| r.complete)
+            .map(|r| r.damaged_parts.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+        newly.push(
+            run.damaged_parts
+                .iter()
+                .map(String::as_str)
+                .filter(|p| !before.contains(p))
+                .collect(),
+        );
+    }
+
+    let latest = runs.last().unwrap();
+    let latest_complete = runs.iter().rev().find(|r| r.complete);
+    let mut parts: BTreeMap<&str, PartHistory> = BTreeMap::new();
+    for (i, run) in runs.iter().enumerate() {
+        for p in &run.damaged_parts {
+            parts.entry(p).or_insert_with(|| PartHistory {
+                part: p,
+                first_damaged: &run.started_at_human,
+                last_clean: runs[..i]
+                    .iter()
+                    .rev()
+                    .find(|r| r.complete && !r.damaged_parts.contains(p))
+                    .map(|r| r.started_at_human.as_str()),
+                damaged_now: if latest.damaged_parts.contains(p) {
+                    Some(true)
+                } else {
+                    latest_complete.map(|r| r.damaged_parts.contains(p))
+                },
+            });
+        }
+    }
+
+    let shown = runs.len().saturating_sub(last.max(1));
+    if out.json {
+        let listed: Vec<serde_json::Value> = runs
+            .iter()
+            .zip(&newly)
+            .skip(shown)
+            .map(|(run, new)| {
+                let mut v = serde_json::to_value(run).unwrap();
+                v["newly_damaged"] = serde_json::json!(new);
+                v
+            })
+            .collect();
+        let result = serde_json::json!({
+            "journal": store.location(&journal_path(index_path)),
+            "runs_total": runs.len(),
+            "unreadable_lines": bad,
+            "runs": listed,
+            "damaged_parts": parts.values().collect::<Vec<_>>(),
+        });
+        out.raw(&serde_json::to_string_pretty(&result).unwrap());
+        out.raw("\n");
+        return Ok(());
+    }
+
+    out.println(&format!(
+        "{} {}",
+        "Scrub history:".cyan().bold(),
+        store.location(index_path).yellow()
+    ));
+    out.println(&format!(
+        "  {} run(s) journaled, {} to {}",
+        runs.len(),
+        runs[0].started_at_human.dimmed(),
+        latest.started_at_human.dimmed()
+    ));
+    if bad > 0 {
+        out.println(&format!(
+            "  {} {} unreadable journal line(s) skipped",
+            "warning:".yellow(),
+            bad
+        ));
+    }
+    out.println("");
+    out.println(&format!(
+        "  {:<23}  {:<5}  {:>8}  {:<7}  {:>8}  {:>7}  {:>7}",
+        "STARTED", "MODE", "TOOK", "STATUS", "OK", "CORRUPT", "MISSING"
+    ));
+    for (run, new) in runs.iter().zip(&newly).skip(shown) {
+        let status = match run.status() {
+            "PASS" => "PASS   ".green(),
+            s => format!("{:<7}", s).red(),
+        };
+        out.println(&format!(
+            "  {:<23}  {:<5}  {:>7.1}s  {}  {:>8}  {:>7}  {:>7}",
+            run.started_at_human,
+            run.mode,
+            run.duration_secs,
+            status,
+            run.ok,
+            run.corrupt,
+            run.missing
+        ));
+        for p in new {
+            out.println(&format!("      {} {}", "newly damaged:".red(), p));
+        }
+    }
+
+    if !parts.is_empty() {
+        out.println("");
+        out.println(&"Damaged parts:".cyan().bold().to_string());
+        for h in parts.values() {
+            let state = match h.damaged_now {
+                Some(true) => "damaged".red().to_string(),
+                Some(false) => "intact now".green().to_string(),
+                None => "unknown".yellow().to_string(),
+            };
+            out.println(&format!(
+                "  {}  {}; first failed {}, last clean {}",
+                h.part.yellow(),
+                state,
+                h.first_damaged,
+                h.last_clean.unwrap_or("not journaled")
+            ));
+        }
+    }
+    Ok(())
+}
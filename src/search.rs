@@ -20,12 +20,22 @@ use colored::Colorize;
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use std::path::Path;
 
-use crate::index::ArchivumIndex;
+use crate::index::raw_path::RawPath;
+use crate::index::{ArchivumIndex, IndexEntry};
 use crate::output::OutputCtx;
 use crate::utils::human;
 
-pub fn search(index_path: &Path, pattern: &str, out: &OutputCtx) -> Result<()> {
+/// Entries whose path matches `pattern` and, with `digest`, whose content
+/// has that digest under any algorithm the index records.
+pub fn search(
+    index_path: &Path,
+    pattern: Option<&str>,
+    digest: Option<&str>,
+    out: &OutputCtx,
+) -> Result<()> {
     let idx = ArchivumIndex::read(index_path)?;
+    let pattern = pattern.unwrap_or("");
+    let digest = digest.map(str::to_lowercase);
 
     // glob if it has metacharacters, substring otherwise
     let is_glob = pattern.contains('*') || pattern.contains('?') || pattern.contains('[');
@@ -39,15 +49,28 @@ pub fn search(index_path: &Path, pattern: &str, out: &OutputCtx) -> Result<()> {
         None
     };
 
-    let matches: Vec<&crate::index::IndexEntry> = idx
+    // the algorithm a digest search matched under, by entry
+    let matched_by = |e: &IndexEntry| -> Option<String> {
+        let digest = digest.as_deref()?;
+        if e.sha256.as_deref() == Some(digest) {
+            return Some("sha256".into());
+        }
+        e.hashes
+            .iter()
+            .find(|(_, hex)| hex.as_str() == digest)
+            .map(|(name, _)| name.clone())
+    };
+
+    let matches: Vec<&IndexEntry> = idx
         .entries
         .iter()
         .filter(|e| {
             let path_str = e.path.to_string_lossy();
-            match &globset {
+            let path_matches = match &globset {
                 Some(gs) => gs.is_match(&e.path),
                 None => path_str.to_lowercase().contains(&pattern.to_lowercase()),
-            }
+            };
+            path_matches && (digest.is_none() || matched_by(e).is_some())
         })
         .collect();
 
@@ -55,13 +78,18 @@ pub fn search(index_path: &Path, pattern: &str, out: &OutputCtx) -> Result<()> {
         let json_matches: Vec<serde_json::Value> = matches
             .iter()
             .map(|e| {
-                serde_json::json!({
-                    "path": e.path,
+                let mut m = serde_json::json!({
+                    "path": RawPath(&e.path),
                     "size": e.size,
                     "sha256": e.sha256,
+                    "hashes": e.hashes,
                     "tar_part": e.tar_part,
                     "mtime": e.mtime
-                })
+                });
+                if let Some(algo) = matched_by(e) {
+                    m["matched_hash"] = algo.into();
+                }
+                m
             })
             .collect();
         out.raw(&serde_json::to_string_pretty(&json_matches).unwrap());
@@ -69,10 +97,15 @@ pub fn search(index_path: &Path, pattern: &str, out: &OutputCtx) -> Result<()> {
         return Ok(());
     }
 
+    let what = match &digest {
+        Some(d) if pattern.is_empty() => format!("digest {}", d),
+        Some(d) => format!("'{}' with digest {}", pattern, d),
+        None => format!("'{}'", pattern),
+    };
     out.println(&format!(
-        "{} '{}' in {} - {} match(es)",
+        "{} {} in {} - {} match(es)",
         "Search:".cyan().bold(),
-        pattern.yellow(),
+        what.yellow(),
         index_path.display().to_string().dimmed(),
         matches.len().to_string().green()
     ));
@@ -86,17 +119,20 @@ pub fn search(index_path: &Path, pattern: &str, out: &OutputCtx) -> Result<()> {
     out.println(&"─".repeat(65).dimmed().to_string());
 
     for e in &matches {
-        let dedup_tag = if e.dedup_of.is_some() {
+        let mut tags = if e.dedup_of.is_some() {
             " [dedup]".dimmed().to_string()
         } else {
             String::new()
         };
+        if let Some(algo) = matched_by(e) {
+            tags.push_str(&format!(" [{}]", algo).dimmed().to_string());
+        }
         out.println(&format!(
             "  {:<8} {:<12} {}{}",
             format!("part{:03}", e.tar_part),
             human(e.size),
             e.path.display(),
-            dedup_tag
+            tags
         ));
     }
 
@@ -0,0 +1,482 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Context, Result, bail};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::RegexSet;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::utils::now;
+
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Ignore file names enabled by `--ignore-files`, lowest precedence first.
+pub const DEFAULT_IGNORE_FILES: &[&str] = &[".gitignore", ".ignore", ".archivumignore"];
+
+/// Which paths are in scope for `create`, `update` and `diff`. Plain data
+/// gathered from the CLI and config; `compile` turns it into a `Selection`.
+#[derive(Debug, Clone, Default)]
+pub struct SelectionRules {
+    /// glob excludes, matched against the path relative to the source root
+    pub excludes: Vec<String>,
+    /// when non-empty, non-directory entries must match one of these globs
+    pub includes: Vec<String>,
+    pub exclude_regex: Vec<String>,
+    pub max_file_size: Option<u64>,
+    pub min_file_size: Option<u64>,
+    /// unix seconds; files modified before this are skipped
+    pub newer_than: Option<u64>,
+    /// unix seconds; files modified at or after this are skipped
+    pub older_than: Option<u64>,
+    pub one_file_system: bool,
+    pub max_depth: Option<usize>,
+    /// skip any directory containing a file with one of these names
+    pub exclude_if_present: Vec<String>,
+    /// skip directories tagged with a valid CACHEDIR.TAG
+    pub exclude_caches: bool,
+    /// per-directory ignore files with gitignore semantics; within one
+    /// directory, later names override earlier ones
+    pub ignore_files: Vec<String>,
+}
+
+pub struct Selection {
+    excludes: GlobSet,
+    includes: Option<GlobSet>,
+    exclude_regex: Option<RegexSet>,
+    max_file_size: Option<u64>,
+    min_file_size: Option<u64>,
+    newer_than: Option<u64>,
+    older_than: Option<u64>,
+    one_file_system: bool,
+    max_depth: Option<usize>,
+    exclude_if_present: Vec<String>,
+    exclude_caches: bool,
+    ignore_files: Vec<String>,
+}
+
+impl SelectionRules {
+    pub fn compile(&self) -> Result<Selection> {
+        let includes = if self.includes.is_empty() {
+            None
+        } else {
+            Some(build_globset(&self.includes)?)
+        };
+        let exclude_regex = if self.exclude_regex.is_empty() {
+            None
+        } else {
+            Some(RegexSet::new(&self.exclude_regex).context("Invalid --exclude-regex")?)
+        };
+        Ok(Selection {
+            excludes: build_globset(&self.excludes)?,
+            includes,
+            exclude_regex,
+            max_file_size: self.max_file_size,
+            min_file_size: self.min_file_size,
+            newer_than: self.newer_than,
+            older_than: self.older_than,
+            one_file_system: self.one_file_system,
+            max_depth: self.max_depth,
+            exclude_if_present: self.exclude_if_present.clone(),
+            exclude_caches: self.exclude_caches,
+            ignore_files: self.ignore_files.clone(),
+        })
+    }
+}
+
+impl Selection {
+    /// WalkDir configured with the traversal limits (depth, file system).
+    pub fn walker(&self, root: &Path) -> WalkDir {
+        let mut walk = WalkDir::new(root)
+            .follow_links(false)
+            .sort_by_file_name()
+            .same_file_system(self.one_file_system);
+        if let Some(depth) = self.max_depth {
+            walk = walk.max_depth(depth);
+        }
+        walk
+    }
+
+    /// Whether the walker would yield an entry `depth` levels below the root.
+    pub fn within_depth(&self, depth: usize) -> bool {
+        self.max_depth.is_none_or(|max| depth <= max)
+    }
+
+    pub fn one_file_system(&self) -> bool {
+        self.one_file_system
+    }
+
+    /// Path-level excludes (globs and regexes); apply to every entry type.
+    pub fn is_excluded(&self, rel: &Path) -> bool {
+        if self.excludes.is_match(rel) {
+            return true;
+        }
+        match &self.exclude_regex {
+            Some(rs) => rs.is_match(&rel.to_string_lossy()),
+            None => false,
+        }
+    }
+
+    /// Include globs for non-directory entries.
+    pub fn is_included(&self, rel: &Path) -> bool {
+        match &self.includes {
+            Some(gs) => gs.is_match(rel),
+            None => true,
+        }
+    }
+
+    /// Size and age limits; regular files only.
+    pub fn file_in_range(&self, size: u64, mtime: Option<u64>) -> bool {
+        if self.max_file_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        if self.min_file_size.is_some_and(|min| size < min) {
+            return false;
+        }
+        if let Some(m) = mtime {
+            if self.newer_than.is_some_and(|t| m < t) {
+                return false;
+            }
+            if self.older_than.is_some_and(|t| m >= t) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Whether a directory (and everything below it) is skipped because of a
+    /// tag file. The walk prunes these instead of descending.
+    pub fn prunes_dir(&self, dir: &Path) -> bool {
+        if self
+            .exclude_if_present
+            .iter()
+            .any(|name| dir.join(name).symlink_metadata().is_ok())
+        {
+            return true;
+        }
+        self.exclude_caches && is_cache_dir(dir)
+    }
+
+    pub fn uses_ignore_files(&self) -> bool {
+        !self.ignore_files.is_empty()
+    }
+
+    /// Matcher for the ignore files found directly in `dir`, or `None` if
+    /// there are none. Paths of the files read are pushed to `sources`; a
+    /// bad line is reported in `errors` without dropping the rest of the file.
+    pub fn load_ignores(
+        &self,
+        dir: &Path,
+        sources: &mut Vec<PathBuf>,
+        errors: &mut Vec<(PathBuf, ignore::Error)>,
+    ) -> Option<Gitignore> {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in &self.ignore_files {
+            let file = dir.join(name);
+            if !file.is_file() {
+                continue;
+            }
+            found = true;
+            sources.push(file.clone());
+            if let Some(err) = builder.add(&file) {
+                errors.push((file, err));
+            }
+        }
+        if !found {
+            return None;
+        }
+        match builder.build() {
+            Ok(gi) => Some(gi),
+            Err(err) => {
+                errors.push((dir.to_path_buf(), err));
+                None
+            }
+        }
+    }
+}
+
+/// Ignore matchers of the directories above the entry being walked,
+/// outermost first. Walks are depth-first, so on reaching an entry at
+/// depth `d` everything pushed at depth `>= d` belongs to a finished
+/// subtree and is dropped.
+#[derive(Default)]
+pub struct IgnoreStack {
+    levels: Vec<(usize, Gitignore)>,
+}
+
+impl IgnoreStack {
+    pub fn enter(&mut self, depth: usize) {
+        while self.levels.last().is_some_and(|(d, _)| *d >= depth) {
+            self.levels.pop();
+        }
+    }
+
+    pub fn push(&mut self, depth: usize, gi: Gitignore) {
+        self.levels.push((depth, gi));
+    }
+
+    /// The deepest ignore file with an opinion wins, so a nested `!pattern`
+    /// can re-include what a parent ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for (_, gi) in self.levels.iter().rev() {
+            match gi.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+}
+
+// https://bford.info/cachedir/
+fn is_cache_dir(dir: &Path) -> bool {
+    let mut buf = [0u8; CACHEDIR_SIGNATURE.len()];
+    fs::File::open(dir.join(CACHEDIR_TAG))
+        .and_then(|mut f| f.read_exact(&mut buf))
+        .map(|_| buf == CACHEDIR_SIGNATURE)
+        .unwrap_or(false)
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for p in patterns {
+        builder.add(Glob::new(p)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// `1048576`, `512K`, `10M`, `1.5G`, `2T` (binary units, optional `B`/`iB`).
+pub fn parse_size(s: &str) -> Result<u64> {
+    let t = s.trim();
+    let t = t
+        .strip_suffix("iB")
+        .or_else(|| t.strip_suffix('B'))
+        .unwrap_or(t);
+    let (num, mult) = match t.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let mult: u64 = match c.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                'T' => 1 << 40,
+                _ => bail!("Unknown size unit in '{s}' (use K, M, G or T)"),
+            };
+            (&t[..i], mult)
+        }
+        _ => (t, 1),
+    };
+    let n: f64 = num
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid size: '{s}'"))?;
+    if n < 0.0 {
+        bail!("Size cannot be negative: '{s}'");
+    }
+    Ok((n * mult as f64) as u64)
+}
+
+/// A unix timestamp, a `YYYY-MM-DD` date (UTC midnight), or an age relative
+/// to now such as `30m`, `12h`, `7d` or `2w`.
+pub fn parse_time(s: &str) -> Result<u64> {
+    let t = s.trim();
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(t, "%Y-%m-%d") {
+        let ts = date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+        return Ok(ts.max(0) as u64);
+    }
+    if let Ok(ts) = t.parse::<u64>() {
+        return Ok(ts);
+    }
+    let Some((i, unit)) = t.char_indices().last() else {
+        bail!("Empty time value");
+    };
+    let secs: u64 = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        _ => bail!("Invalid time '{s}': use a unix timestamp, YYYY-MM-DD, or an age like 7d"),
+    };
+    let n: u64 = t[..i]
+        .parse()
+        .with_context(|| format!("Invalid age: '{s}'"))?;
+    Ok(now().saturating_sub(n * secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> SelectionRules {
+        SelectionRules::default()
+    }
+
+    #[test]
+    fn sizes_use_binary_units() {
+        assert_eq!(parse_size("1048576").unwrap(), 1 << 20);
+        assert_eq!(parse_size("512K").unwrap(), 512 << 10);
+        assert_eq!(parse_size("10MiB").unwrap(), 10 << 20);
+        assert_eq!(parse_size("1.5G").unwrap(), 3 << 29);
+        assert_eq!(parse_size("2TB").unwrap(), 2 << 40);
+        assert!(parse_size("5X").is_err());
+        assert!(parse_size("-1K").is_err());
+        assert!(parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn times_accept_timestamps_dates_and_ages() {
+        assert_eq!(parse_time("1700000000").unwrap(), 1_700_000_000);
+        assert_eq!(parse_time("2024-01-01").unwrap(), 1_704_067_200);
+        let week_ago = parse_time("1w").unwrap();
+        let expected = now() - 7 * 86400;
+        assert!(week_ago.abs_diff(expected) <= 1);
+        assert!(parse_time("7y").is_err());
+        assert!(parse_time("").is_err());
+    }
+
+    #[test]
+    fn excludes_match_globs_and_regexes() {
+        let sel = SelectionRules {
+            excludes: vec!["*.tmp".into(), "cache/**".into()],
+            exclude_regex: vec![r"^build/.*\.o$".into()],
+            ..rules()
+        }
+        .compile()
+        .unwrap();
+        assert!(sel.is_excluded(Path::new("a.tmp")));
+        assert!(sel.is_excluded(Path::new("cache/x/y")));
+        assert!(sel.is_excluded(Path::new("build/main.o")));
+        assert!(!sel.is_excluded(Path::new("src/main.o")));
+        assert!(!sel.is_excluded(Path::new("notes.txt")));
+    }
+
+    #[test]
+    fn includes_default_to_everything() {
+        assert!(rules().compile().unwrap().is_included(Path::new("any")));
+        let sel = SelectionRules {
+            includes: vec!["*.rs".into()],
+            ..rules()
+        }
+        .compile()
+        .unwrap();
+        assert!(sel.is_included(Path::new("src/main.rs")));
+        assert!(!sel.is_included(Path::new("README.md")));
+    }
+
+    #[test]
+    fn size_and_age_limits_bound_files() {
+        let sel = SelectionRules {
+            min_file_size: Some(10),
+            max_file_size: Some(100),
+            newer_than: Some(1000),
+            older_than: Some(2000),
+            ..rules()
+        }
+        .compile()
+        .unwrap();
+        assert!(sel.file_in_range(10, Some(1000)));
+        assert!(sel.file_in_range(100, Some(1999)));
+        assert!(!sel.file_in_range(9, Some(1500)));
+        assert!(!sel.file_in_range(101, Some(1500)));
+        assert!(!sel.file_in_range(50, Some(999)));
+        assert!(!sel.file_in_range(50, Some(2000)));
+        // no mtime: only the size limits apply
+        assert!(sel.file_in_range(50, None));
+    }
+
+    #[test]
+    fn bad_patterns_fail_to_compile() {
+        let bad_glob = SelectionRules {
+            excludes: vec!["a[".into()],
+            ..rules()
+        };
+        assert!(bad_glob.compile().is_err());
+        let bad_regex = SelectionRules {
+            exclude_regex: vec!["(".into()],
+            ..rules()
+        };
+        assert!(bad_regex.compile().is_err());
+    }
+
+    #[test]
+    fn tagged_directories_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cache, fake, marked) = (
+            dir.path().join("cache"),
+            dir.path().join("fake"),
+            dir.path().join("marked"),
+        );
+        for d in [&cache, &fake, &marked] {
+            fs::create_dir(d).unwrap();
+        }
+        fs::write(cache.join(CACHEDIR_TAG), CACHEDIR_SIGNATURE).unwrap();
+        fs::write(fake.join(CACHEDIR_TAG), b"not the signature").unwrap();
+        fs::write(marked.join(".nobackup"), b"").unwrap();
+
+        let sel = SelectionRules {
+            exclude_caches: true,
+            exclude_if_present: vec![".nobackup".into()],
+            ..rules()
+        }
+        .compile()
+        .unwrap();
+        assert!(sel.prunes_dir(&cache));
+        assert!(!sel.prunes_dir(&fake));
+        assert!(sel.prunes_dir(&marked));
+        assert!(!rules().compile().unwrap().prunes_dir(&cache));
+    }
+
+    #[test]
+    fn nested_ignore_files_can_reinclude() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        fs::write(sub.join(".gitignore"), "!keep.log\n").unwrap();
+
+        let sel = SelectionRules {
+            ignore_files: vec![".gitignore".into()],
+            ..rules()
+        }
+        .compile()
+        .unwrap();
+        let (mut read, mut errors) = (vec![], vec![]);
+        let mut stack = IgnoreStack::default();
+        stack.push(
+            0,
+            sel.load_ignores(dir.path(), &mut read, &mut errors)
+                .unwrap(),
+        );
+        stack.enter(1);
+        stack.push(1, sel.load_ignores(&sub, &mut read, &mut errors).unwrap());
+        assert!(errors.is_empty());
+        assert_eq!(read.len(), 2);
+
+        assert!(stack.is_ignored(&sub.join("drop.log"), false));
+        assert!(!stack.is_ignored(&sub.join("keep.log"), false));
+        // leaving `sub` drops its re-include
+        stack.enter(1);
+        assert!(stack.is_ignored(&dir.path().join("keep.log"), false));
+    }
+}
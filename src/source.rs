@@ -0,0 +1,374 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use crate::scan::{
+    EntryType, ScanEntry, ScanReport, WalkRules, mtime_of, scan_directory, scan_path,
+};
+use crate::selection::Selection;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+/// A directory tree feeding the archive. With a prefix its entries live
+/// under `<prefix>/` in the index; without one they sit at the top level.
+#[derive(Debug, Clone)]
+pub struct SourceRoot {
+    pub prefix: Option<String>,
+    pub path: PathBuf,
+}
+
+/// The roots of one create/update/diff run, plus an optional explicit file
+/// list that replaces the directory walk.
+#[derive(Debug, Clone)]
+pub struct Sources {
+    pub roots: Vec<SourceRoot>,
+    files_from: Option<Vec<PathBuf>>,
+}
+
+impl Sources {
+    /// `SOURCE` is either a directory or `PREFIX=DIR`; `extra` holds the
+    /// `--root PREFIX=DIR` values. Several roots all need a prefix.
+    pub fn new(source: &Path, extra: &[String]) -> Result<Self> {
+        let mut roots = vec![parse_root(source.as_os_str().to_owned(), false)?];
+        for r in extra {
+            roots.push(parse_root(OsString::from(r), true)?);
+        }
+
+        if roots.len() > 1 {
+            let mut seen = std::collections::HashSet::new();
+            for r in &roots {
+                let Some(prefix) = &r.prefix else {
+                    bail!(
+                        "{} needs a prefix (PREFIX=DIR) when archiving several roots",
+                        r.path.display()
+                    );
+                };
+                if !seen.insert(prefix.clone()) {
+                    bail!("Root prefix '{prefix}' is used more than once");
+                }
+            }
+        }
+        Ok(Self {
+            roots,
+            files_from: None,
+        })
+    }
+
+    /// No roots at all: `create - OUT --stdin-name NAME` archives only the
+    /// stream.
+    pub fn none() -> Self {
+        Self {
+            roots: vec![],
+            files_from: Some(vec![]),
+        }
+    }
+
+    /// Archive only the paths listed in `list` (a file, or `-` for stdin).
+    /// Entries are NUL separated if the list contains a NUL, else newline
+    /// separated.
+    pub fn with_files_from(mut self, list: &Path) -> Result<Self> {
+        let mut raw = vec![];
+        if list == Path::new("-") {
+            std::io::stdin()
+                .read_to_end(&mut raw)
+                .context("Cannot read file list from stdin")?;
+        } else {
+            raw = fs::read(list)
+                .with_context(|| format!("Cannot read file list {}", list.display()))?;
+        }
+        let sep = if raw.contains(&0) { 0 } else { b'\n' };
+        let paths = raw
+            .split(|&b| b == sep)
+            .map(|l| {
+                if sep == b'\n' {
+                    l.strip_suffix(b"\r").unwrap_or(l)
+                } else {
+                    l
+                }
+            })
+            .filter(|l| !l.is_empty())
+            .map(path_from_bytes)
+            .collect();
+        self.files_from = Some(paths);
+        Ok(self)
+    }
+
+    /// Prefixes recorded in the index header; empty for a plain archive.
+    pub fn prefixes(&self) -> Vec<String> {
+        self.roots.iter().filter_map(|r| r.prefix.clone()).collect()
+    }
+
+    pub fn describe(&self) -> String {
+        if self.roots.is_empty() {
+            return "-".into();
+        }
+        self.roots
+            .iter()
+            .map(|r| match &r.prefix {
+                Some(p) => format!("{}/={}", p, r.path.display()),
+                None => r.path.display().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn check_exist(&self) -> Result<()> {
+        for r in &self.roots {
+            if !r.path.is_dir() {
+                bail!("Source directory does not exist: {}", r.path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// On-disk location of an index path.
+    pub fn resolve(&self, index_path: &Path) -> PathBuf {
+        match self.split(index_path) {
+            Some((root, rest)) => root.path.join(rest),
+            None => self.roots[0].path.join(index_path),
+        }
+    }
+
+    /// Whether an index path is still in the source, whether or not the
+    /// selection or a scan error kept it out of the scan.
+    pub fn still_has(&self, index_path: &Path) -> bool {
+        self.resolve(index_path).symlink_metadata().is_ok()
+    }
+
+    /// Scan every root (or just the listed files), returning entries with
+    /// their index paths.
+    pub fn scan(&self, selection: &Selection, report: &mut ScanReport) -> Result<Vec<ScanEntry>> {
+        if let Some(list) = &self.files_from {
+            return self.scan_list(list, selection, report);
+        }
+
+        let mut out = vec![];
+        for root in &self.roots {
+            let Some(prefix) = &root.prefix else {
+                return scan_directory(&root.path, selection, report)
+                    .with_context(|| format!("Failed to scan {}", root.path.display()));
+            };
+            let mut sub = ScanReport::default();
+            let entries = scan_directory(&root.path, selection, &mut sub)
+                .with_context(|| format!("Failed to scan {}", root.path.display()))?;
+
+            out.push(root_entry(root, prefix)?);
+            out.extend(entries.into_iter().map(|mut e| {
+                e.relative_path = Path::new(prefix).join(&e.relative_path);
+                e
+            }));
+            report.errors.extend(sub.errors.into_iter().map(|mut e| {
+                e.path = Path::new(prefix).join(&e.path);
+                e
+            }));
+            report
+                .ignore_sources
+                .extend(sub.ignore_sources.iter().map(|p| Path::new(prefix).join(p)));
+        }
+        Ok(out)
+    }
+
+    /// Listed paths are taken as they are: directories become entries but
+    /// are not descended into, so `find` or `git ls-files` output maps one
+    /// line to one entry. Otherwise the walk's rules apply, so a listed path
+    /// is only archived if scanning its root would have found it.
+    fn scan_list(
+        &self,
+        list: &[PathBuf],
+        selection: &Selection,
+        report: &mut ScanReport,
+    ) -> Result<Vec<ScanEntry>> {
+        let mut wanted: BTreeMap<PathBuf, (usize, PathBuf)> = BTreeMap::new();
+        for p in list {
+            let (root, rest) = self.locate(p)?;
+            let index_path = match &self.roots[root].prefix {
+                Some(prefix) => Path::new(prefix).join(&rest),
+                None => rest.clone(),
+            };
+            wanted.insert(index_path, (root, rest));
+        }
+
+        let mut out = vec![];
+        let mut rules = vec![];
+        for root in &self.roots {
+            if let Some(prefix) = &root.prefix {
+                out.push(root_entry(root, prefix)?);
+            }
+            rules.push(WalkRules::new(&root.path, selection));
+        }
+        let mut subs: Vec<ScanReport> = self.roots.iter().map(|_| Default::default()).collect();
+        for (index_path, (root, rel)) in wanted {
+            if !rules[root].reaches(&rel) {
+                continue;
+            }
+            // selection rules see root-relative paths, as in a walk
+            let disk = self.roots[root].path.join(&rel);
+            if let Some(mut se) = scan_path(&disk, rel, selection, &mut subs[root].errors) {
+                se.relative_path = index_path;
+                out.push(se);
+            }
+        }
+        for ((root, rules), mut sub) in self.roots.iter().zip(rules).zip(subs) {
+            rules.finish(&mut sub);
+            let prefix = Path::new(root.prefix.as_deref().unwrap_or(""));
+            report.errors.extend(sub.errors.into_iter().map(|mut e| {
+                e.path = prefix.join(&e.path);
+                e
+            }));
+            report
+                .ignore_sources
+                .extend(sub.ignore_sources.iter().map(|p| prefix.join(p)));
+        }
+        // index path order, which is also the order a walk produces
+        out.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(out)
+    }
+
+    /// Map a `--files-from` line to (root, path below it). Relative lines
+    /// are taken from the current directory, as with `tar -T`; either way
+    /// the path must lie under a root. `..` is resolved lexically.
+    fn locate(&self, p: &Path) -> Result<(usize, PathBuf)> {
+        let abs = std::path::absolute(p)
+            .map(|a| normalize(&a))
+            .with_context(|| format!("Cannot resolve {} (from --files-from)", p.display()))?;
+        let best = self
+            .roots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| {
+                let base = normalize(&std::path::absolute(&r.path).ok()?);
+                let rest = abs.strip_prefix(&base).ok()?;
+                Some((i, base.components().count(), rest.to_path_buf()))
+            })
+            .max_by_key(|(_, depth, _)| *depth);
+        let Some((root, _, rest)) = best else {
+            bail!(
+                "{} (from --files-from) is not under any source root",
+                p.display()
+            );
+        };
+        if rest.as_os_str().is_empty() {
+            bail!(
+                "{} (from --files-from) is a source root itself",
+                p.display()
+            );
+        }
+        Ok((root, rest))
+    }
+
+    /// The prefixed root an index path belongs to, and the rest of the path.
+    fn split<'a>(&self, index_path: &'a Path) -> Option<(&SourceRoot, &'a Path)> {
+        let mut comps = index_path.components();
+        let first = comps.next()?.as_os_str();
+        let root = self
+            .roots
+            .iter()
+            .find(|r| r.prefix.as_deref().is_some_and(|p| first == p))?;
+        Some((root, comps.as_path()))
+    }
+}
+
+fn parse_root(arg: OsString, need_prefix: bool) -> Result<SourceRoot> {
+    let as_path = PathBuf::from(&arg);
+    let text = arg.to_string_lossy();
+    // an existing directory wins over PREFIX=DIR, so names with '=' still work
+    let split = if !need_prefix && as_path.exists() {
+        None
+    } else {
+        text.split_once('=')
+    };
+    let Some((prefix, dir)) = split else {
+        if need_prefix {
+            bail!("Expected PREFIX=DIR, got '{text}'");
+        }
+        return Ok(SourceRoot {
+            prefix: None,
+            path: as_path,
+        });
+    };
+
+    let prefix = prefix.trim_end_matches('/');
+    let mut comps = Path::new(prefix).components();
+    if !matches!(
+        (comps.next(), comps.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        bail!("Root prefix must be a single path component, got '{prefix}'");
+    }
+    Ok(SourceRoot {
+        prefix: Some(prefix.to_string()),
+        path: PathBuf::from(dir),
+    })
+}
+
+/// The directory entry standing for a prefixed root in the index.
+fn root_entry(root: &SourceRoot, prefix: &str) -> Result<ScanEntry> {
+    let meta = fs::metadata(&root.path)
+        .with_context(|| format!("Cannot read source root {}", root.path.display()))?;
+
+    #[cfg(unix)]
+    let mode = Some(meta.mode());
+
+    #[cfg(not(unix))]
+    let mode = None;
+
+    Ok(ScanEntry {
+        relative_path: PathBuf::from(prefix),
+        entry_type: EntryType::Directory,
+        size: 0,
+        mtime: mtime_of(&meta),
+        unix_mode: mode,
+        symlink_target: None,
+        dev_major: None,
+        dev_minor: None,
+    })
+}
+
+/// Drop `.` components and let `..` remove the one before it, without
+/// looking at the file system.
+fn normalize(p: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in p.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Raw path bytes as written by `as_encoded_bytes` (or read from a list).
+#[cfg(unix)]
+pub fn path_from_bytes(b: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(b.to_vec()))
+}
+
+#[cfg(not(unix))]
+pub fn path_from_bytes(b: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(b).into_owned())
+}
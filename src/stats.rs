@@ -21,6 +21,7 @@ use std::collections::HashMap;
 use std::path::Path;
 
 use crate::index::ArchivumIndex;
+use crate::merkle;
 use crate::output::OutputCtx;
 use crate::scan::EntryType;
 use crate::utils::human;
@@ -91,6 +92,7 @@ pub fn stats(index_path: &Path, out: &OutputCtx) -> Result<()> {
                 "total_size_human": human(h.total_size),
                 "total_parts": h.total_parts,
                 "compression": h.compression.name(),
+                "merkle_root": h.merkle_root.clone().unwrap_or_else(|| merkle::root(&idx.entries)),
             },
             "on_disk_bytes": total_on_disk,
             "on_disk_human": human(total_on_disk),
@@ -98,6 +100,7 @@ pub fn stats(index_path: &Path, out: &OutputCtx) -> Result<()> {
             "saving_percent": saving_pct,
             "dedup_files": dedup_count,
             "dedup_bytes": dedup_bytes,
+            "skipped": idx.errors,
             "parts": part_sizes.iter().map(|(p, s)| serde_json::json!({"part": p, "size": s})).collect::<Vec<_>>(),
             "by_extension": ext_vec.iter().take(20).map(|(e, c, b)| {
                 serde_json::json!({"ext": e, "count": c, "bytes": b})
@@ -124,6 +127,10 @@ pub fn stats(index_path: &Path, out: &OutputCtx) -> Result<()> {
     ));
     out.println(&format!("  Source size: {}", human(h.total_size).cyan()));
     out.println(&format!("  On-disk    : {}", human(total_on_disk).cyan()));
+    out.println(&format!(
+        "  Merkle root: {}",
+        idx.merkle_root_label().dimmed()
+    ));
     out.println(&format!(
         "  Ratio      : {:.2}x  (saving: {:.1}%)",
         ratio, saving_pct
@@ -136,6 +143,22 @@ pub fn stats(index_path: &Path, out: &OutputCtx) -> Result<()> {
         ));
     }
 
+    if !idx.errors.is_empty() {
+        out.println("");
+        out.println(&format!(
+            "  {} ({} paths)",
+            "Skipped:".red().bold(),
+            idx.errors.len()
+        ));
+        for err in &idx.errors {
+            out.println(&format!(
+                "    {:<18} {}",
+                err.kind.name().yellow(),
+                err.path.display()
+            ));
+        }
+    }
+
     out.println("");
     out.println(&format!(
         "  {} ({} parts)",
@@ -0,0 +1,278 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Where an archive's files live. Paths handed to a store are on its side:
+// a local path, a path on a `serve` peer, or an object key in a bucket.
+// `IndexEntry::part_path` builds them the same way for all three.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::compress::CompressionAlgo;
+use crate::config::Config;
+use crate::index::{ArchivumIndex, seal_path};
+use crate::remote::{self, Remote};
+use crate::s3::{self, S3};
+
+/// Ranged reads fetch at least this much, so walking tar headers does not
+/// cost a round trip per member.
+const READ_AHEAD: u64 = 1 << 20;
+
+pub enum Store {
+    Local,
+    Remote(String, Mutex<Remote>),
+    S3(S3),
+}
+
+impl Store {
+    /// Resolve an INDEX or OUTPUT argument: `s3://BUCKET/KEY` goes to the
+    /// bucket, `HOST:PATH` connects to HOST, anything else is local.
+    /// Returns the path on that side.
+    pub fn connect(arg: &Path, cfg: &Config) -> Result<(Self, PathBuf)> {
+        if let Some((bucket, key)) = s3::parse_url(arg) {
+            return Ok((Self::S3(S3::connect(&bucket, &cfg.s3)?), key));
+        }
+        match remote::parse_remote(arg) {
+            Some((host, path)) => Ok((
+                Self::Remote(
+                    host.clone(),
+                    Mutex::new(Remote::connect(&host, &cfg.remote)?),
+                ),
+                path,
+            )),
+            None => Ok((Self::Local, arg.to_path_buf())),
+        }
+    }
+
+    pub fn is_remote(&self) -> bool {
+        !matches!(self, Self::Local)
+    }
+
+    /// `path` as the user would write it on the command line.
+    pub fn location(&self, path: &Path) -> String {
+        match self {
+            Self::Local => path.display().to_string(),
+            Self::Remote(host, _) => format!("{}:{}", host, path.display()),
+            Self::S3(s3) => format!("s3://{}/{}", s3.bucket(), path.display()),
+        }
+    }
+
+    /// Size of a regular file, or `None` if it does not exist.
+    pub fn size(&self, path: &Path) -> Result<Option<u64>> {
+        match self {
+            Self::Local => Ok(fs::metadata(path)
+                .ok()
+                .filter(|m| m.is_file())
+                .map(|m| m.len())),
+            Self::Remote(_, r) => r.lock().unwrap().stat(path),
+            Self::S3(s3) => s3.head(path),
+        }
+    }
+
+    pub fn exists(&self, path: &Path) -> Result<bool> {
+        match self {
+            Self::Local => Ok(path.exists()),
+            _ => Ok(self.size(path)?.is_some()),
+        }
+    }
+
+    /// Buckets have no directories; keys are created with their objects.
+    pub fn mkdir(&self, path: &Path) -> Result<()> {
+        match self {
+            Self::Local => fs::create_dir_all(path)
+                .with_context(|| format!("Failed to create output dir {}", path.display())),
+            Self::Remote(_, r) => r.lock().unwrap().mkdir(path),
+            Self::S3(_) => Ok(()),
+        }
+    }
+
+    /// Read a whole file, or part of it: `len` bytes from `offset`.
+    pub fn reader(&self, path: &Path, offset: u64, len: Option<u64>) -> Result<Box<dyn Read + '_>> {
+        match self {
+            Self::Local => {
+                let (f, size) = open_range(path, offset, len)
+                    .with_context(|| format!("Cannot open {}", path.display()))?;
+                Ok(Box::new(f.take(size)))
+            }
+            Self::Remote(_, r) => remote::get(r.lock().unwrap(), path, offset, len),
+            Self::S3(s3) => Ok(s3.get(path, offset, len)?.0),
+        }
+    }
+
+    /// Decompressing reader over a tar part.
+    pub fn open_part(&self, path: &Path, algo: &CompressionAlgo) -> Result<Box<dyn Read + '_>> {
+        algo.wrap_read(self.reader(path, 0, None)?)
+            .with_context(|| format!("Cannot open part {}", path.display()))
+    }
+
+    /// Seekable reader over a file, fetched with ranged reads as it is
+    /// read. Seeking past data never transfers it.
+    pub fn open_seekable(&self, path: &Path) -> Result<RangeReader<'_>> {
+        let len = self
+            .size(path)?
+            .with_context(|| format!("Cannot open part {}", self.location(path)))?;
+        Ok(RangeReader {
+            store: self,
+            path: path.to_path_buf(),
+            len,
+            pos: 0,
+            buf: vec![],
+            buf_start: 0,
+        })
+    }
+
+    /// Read the index at `path`, checking its BLAKE3 seal if present.
+    pub fn read_index(&self, path: &Path) -> Result<ArchivumIndex> {
+        if let Self::Local = self {
+            return ArchivumIndex::read(path);
+        }
+        let mut json = vec![];
+        self.reader(path, 0, None)?.read_to_end(&mut json)?;
+        let b3 = seal_path(path);
+        let seal = if self.exists(&b3)? {
+            let mut s = String::new();
+            self.reader(&b3, 0, None)?.read_to_string(&mut s)?;
+            Some(s)
+        } else {
+            None
+        };
+        ArchivumIndex::from_bytes(json, seal.as_deref(), path)
+    }
+
+    pub fn write_index(&self, idx: &mut ArchivumIndex, path: &Path) -> Result<()> {
+        idx.finish_entries();
+        match self {
+            Self::Local => idx.write(path),
+            Self::Remote(_, r) => {
+                let (json, seal) = idx.to_bytes()?;
+                let mut r = r.lock().unwrap();
+                r.put(&mut json.as_slice(), path)?;
+                r.put(&mut seal.as_bytes(), &seal_path(path))?;
+                Ok(())
+            }
+            Self::S3(s3) => {
+                let (json, seal) = idx.to_bytes()?;
+                s3.put(path, &json)?;
+                s3.put(&seal_path(path), seal.as_bytes())
+            }
+        }
+    }
+
+    /// Store `size` bytes from `src` as `path`, returning the count
+    /// written. Local files go through a temporary name, as remote and S3
+    /// uploads do, so a failed copy never leaves a truncated file behind.
+    pub fn write_file(&self, path: &Path, src: &mut dyn Read, size: u64) -> Result<u64> {
+        match self {
+            Self::Local => {
+                let mut tmp = path.as_os_str().to_owned();
+                tmp.push(".upload");
+                let tmp = PathBuf::from(tmp);
+                let result = File::create(&tmp).and_then(|mut f| {
+                    let n = io::copy(src, &mut f)?;
+                    f.sync_all()?;
+                    fs::rename(&tmp, path)?;
+                    Ok(n)
+                });
+                if result.is_err() {
+                    let _ = fs::remove_file(&tmp);
+                }
+                result.with_context(|| format!("Cannot write {}", path.display()))
+            }
+            Self::Remote(_, r) => r.lock().unwrap().put(src, path),
+            Self::S3(s3) => s3.upload(path, src, size),
+        }
+    }
+
+    /// Hand a finished part over to the store. Local archives are written
+    /// in place, so this is a no-op; otherwise the part is uploaded into
+    /// `dir` and the local copy removed.
+    pub fn upload_part(&self, part: &Path, dir: &Path) -> Result<()> {
+        if let Self::Local = self {
+            return Ok(());
+        }
+        let name = part.file_name().context("part has no file name")?;
+        let mut f = File::open(part).with_context(|| format!("Cannot open {}", part.display()))?;
+        let size = f.metadata()?.len();
+        self.write_file(&dir.join(name), &mut f, size)?;
+        fs::remove_file(part).with_context(|| format!("Cannot remove {}", part.display()))?;
+        Ok(())
+    }
+}
+
+/// A file and the `len` bytes from `offset` (clamped to its end), ready
+/// to be read from there.
+pub fn open_range(path: &Path, offset: u64, len: Option<u64>) -> io::Result<(File, u64)> {
+    let mut f = File::open(path)?;
+    let total = f.metadata()?.len();
+    let start = offset.min(total);
+    f.seek(SeekFrom::Start(start))?;
+    let size = len.map_or(total - start, |l| l.min(total - start));
+    Ok((f, size))
+}
+
+/// See `Store::open_seekable`.
+pub struct RangeReader<'a> {
+    store: &'a Store,
+    path: PathBuf,
+    len: u64,
+    pos: u64,
+    buf: Vec<u8>,
+    buf_start: u64,
+}
+
+impl Read for RangeReader<'_> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || out.is_empty() {
+            return Ok(0);
+        }
+        let buf_end = self.buf_start + self.buf.len() as u64;
+        if self.pos < self.buf_start || self.pos >= buf_end {
+            let want = READ_AHEAD.max(out.len() as u64).min(self.len - self.pos);
+            self.buf.clear();
+            self.store
+                .reader(&self.path, self.pos, Some(want))
+                .and_then(|mut r| Ok(r.read_to_end(&mut self.buf)?))
+                .map_err(io::Error::other)?;
+            self.buf_start = self.pos;
+            if self.buf.is_empty() {
+                return Ok(0);
+            }
+        }
+        let at = (self.pos - self.buf_start) as usize;
+        let n = out.len().min(self.buf.len() - at);
+        out[..n].copy_from_slice(&self.buf[at..at + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RangeReader<'_> {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        let pos = match to {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(d) => self.len.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of file")
+        })?;
+        Ok(self.pos)
+    }
+}
@@ -0,0 +1,295 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::{Context, Result, bail};
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path};
+use tar::Builder;
+
+use crate::checksum::{HashAlgo, MultiHasher};
+use crate::compress::CompressionAlgo;
+use crate::index::{ArchivumIndex, IndexEntry, PartHash, Piece};
+use crate::output::OutputCtx;
+use crate::scan::EntryType;
+use crate::tar_writer::{append_manifest_entries, hash_part};
+use crate::utils::{human, now};
+
+const BLOCK: u64 = 512;
+
+/// Stream `input` into new parts from `first_part` on, as the member
+/// `name`. A part is closed once it holds `split_bytes` of tar data and the
+/// stream carries on in the next one, so one entry can span several parts;
+/// `on_part` gets each part as soon as it is complete.
+///
+/// The length is not known up front. Each part's member header therefore
+/// goes out as a stored (uncompressed) frame of its own, ahead of the
+/// compressed data, and is overwritten with the real size once the piece
+/// ends. The frame keeps its length, so nothing after it moves and the
+/// stream is never copied. bzip2 has no stored frames and is refused.
+///
+/// The last part ends with a manifest holding the finished entry, so that
+/// `repair` gives it back as streamed.
+///
+/// Returns the index entry (size, digests and pieces filled in) and the
+/// hashes of the parts written.
+pub fn write_stream_parts(
+    input: &mut dyn Read,
+    name: &Path,
+    out_dir: &Path,
+    first_part: u32,
+    idx: &ArchivumIndex,
+    split_bytes: u64,
+    on_part: &mut dyn FnMut(&Path) -> Result<()>,
+) -> Result<(IndexEntry, Vec<PartHash>)> {
+    let h = &idx.header;
+    if h.compression == CompressionAlgo::Bzip2 {
+        bail!("stdin cannot be streamed into bzip2 parts; use another --compress");
+    }
+    let algos = h.hash_algos();
+
+    let mtime = if h.reproducible {
+        h.source_date_epoch.unwrap_or(0)
+    } else {
+        now()
+    };
+    let mode = 0o644;
+
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(0);
+    header.set_mode(mode);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(mtime);
+    let mut blocks = header_blocks(&mut header, name)?;
+
+    // what is left of a part for data after the header and the two end
+    // blocks, in whole blocks
+    let room =
+        (split_bytes.saturating_sub(blocks.len() as u64 + 2 * BLOCK) / BLOCK * BLOCK).max(BLOCK);
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("  {spinner:.cyan} Streaming  {bytes}  {bytes_per_sec}")
+            .unwrap(),
+    );
+    let mut input = BufReader::with_capacity(1 << 20, input);
+    let mut whole = MultiHasher::with_sha256(&algos);
+    let mut pieces = vec![];
+    let mut part_hashes = vec![];
+    let mut part = first_part;
+    loop {
+        let part_path = out_dir.join(format!("data.part{:03}{}", part, h.compression.extension()));
+        // read-write so the header frame can be overwritten in place
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&part_path)
+            .with_context(|| format!("Cannot create {}", part_path.display()))?;
+        file.write_all(&h.compression.stored_frame(&blocks)?)?;
+
+        let mut piece = MultiHasher::with_sha256(&algos);
+        let mut w = h.compression.wrap_writer(file.try_clone()?, h.zstd_level)?;
+        let mut size = 0u64;
+        while size < room {
+            let buf = input
+                .fill_buf()
+                .with_context(|| format!("Failed to stream {}", name.display()))?;
+            if buf.is_empty() {
+                break;
+            }
+            let n = buf.len().min((room - size) as usize);
+            w.write_all(&buf[..n])?;
+            whole.update(&buf[..n]);
+            piece.update(&buf[..n]);
+            input.consume(n);
+            size += n as u64;
+            pb.inc(n as u64);
+        }
+        let last = input
+            .fill_buf()
+            .with_context(|| format!("Failed to stream {}", name.display()))?
+            .is_empty();
+        w.write_all(&vec![0; (size.div_ceil(BLOCK) * BLOCK - size) as usize])?;
+
+        pieces.push(Piece {
+            size,
+            sha256: None,
+            hashes: BTreeMap::new(),
+        });
+        let current = pieces.last_mut().unwrap();
+        for (algo, hex) in piece.finish() {
+            match algo {
+                HashAlgo::Sha256 => current.sha256 = Some(hex),
+                _ => {
+                    current.hashes.insert(algo.name().to_string(), hex);
+                }
+            }
+        }
+
+        // the last part ends with the manifest, as the whole entry is only
+        // known there; its builder writes the end-of-archive marker
+        let done = if last {
+            let mut entry = IndexEntry {
+                path: name.to_path_buf(),
+                entry_type: EntryType::File,
+                size: pieces.iter().map(|p| p.size).sum(),
+                mtime: Some(mtime),
+                unix_mode: Some(0o100000 | mode),
+                sha256: None,
+                hashes: BTreeMap::new(),
+                tar_part: first_part,
+                symlink_target: None,
+                tar_base: None,
+                dedup_of: None,
+                dev_major: None,
+                dev_minor: None,
+                changed_during_backup: false,
+                from_stdin: true,
+                pieces: if pieces.len() == 1 {
+                    vec![]
+                } else {
+                    std::mem::take(&mut pieces)
+                },
+            };
+            entry.set_digests(std::mem::replace(&mut whole, MultiHasher::new(&[])).finish());
+            let mut builder = Builder::new(&mut w);
+            append_manifest_entries(
+                &mut builder,
+                idx,
+                part,
+                std::slice::from_ref(&entry),
+                &[idx.entries.len()],
+            )?;
+            builder.finish()?;
+            drop(builder);
+            Some(entry)
+        } else {
+            w.write_all(&[0; 2 * BLOCK as usize])?;
+            None
+        };
+        w.flush()?;
+        drop(w);
+
+        header.set_size(size);
+        header.set_cksum();
+        let at = blocks.len() - BLOCK as usize;
+        blocks[at..].copy_from_slice(header.as_bytes());
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&h.compression.stored_frame(&blocks)?)?;
+        drop(file);
+
+        part_hashes.push(hash_part(&part_path, None, part)?);
+        on_part(&part_path)?;
+
+        if let Some(entry) = done {
+            pb.finish_and_clear();
+            return Ok((entry, part_hashes));
+        }
+        part += 1;
+    }
+}
+
+/// The blocks a member named `name` starts with: any GNU long-name blocks
+/// and its header, with the path and checksum set in `header` too.
+fn header_blocks(header: &mut tar::Header, name: &Path) -> Result<Vec<u8>> {
+    let mut builder = Builder::new(Vec::new());
+    builder
+        .append_data(header, name, io::empty())
+        .with_context(|| format!("Cannot store {} in a tar header", name.display()))?;
+    Ok(builder.get_ref().clone())
+}
+
+/// Add `entry` and its parts (written by `write_stream_parts`) to the index.
+pub fn push_entry(idx: &mut ArchivumIndex, entry: IndexEntry, part_hashes: Vec<PartHash>) {
+    idx.header.part_hashes.extend(part_hashes);
+    idx.header.total_files += 1;
+    idx.header.total_size += entry.size;
+    idx.header.total_parts = idx.header.total_parts.max(entry.last_part() + 1);
+    idx.entries.push(entry);
+}
+
+/// `add`: stream stdin into a new part of an existing archive.
+pub fn add(index_path: &Path, name: &Path, split_bytes: u64, out: &OutputCtx) -> Result<()> {
+    check_name(name)?;
+    let mut idx = ArchivumIndex::read(index_path)
+        .with_context(|| format!("Cannot read index: {}", index_path.display()))?;
+    if idx.entries.iter().any(|e| e.path == name) {
+        bail!("{} is already in the archive", name.display());
+    }
+    let index_dir = index_path.parent().unwrap_or(Path::new("."));
+    let part = idx.header.total_parts;
+
+    out.println(&format!(
+        "{} stdin -> {} ({})",
+        "Adding:".cyan().bold(),
+        name.display().to_string().yellow(),
+        index_path.display()
+    ));
+    if out.dry_run {
+        out.dry(&format!(
+            "would stream stdin into part {:03} as {}",
+            part,
+            name.display()
+        ));
+        return Ok(());
+    }
+
+    let (entry, part_hashes) = write_stream_parts(
+        &mut io::stdin().lock(),
+        name,
+        index_dir,
+        part,
+        &idx,
+        split_bytes,
+        &mut |_| Ok(()),
+    )?;
+    let parts = if entry.last_part() == part {
+        format!("part {part:03}")
+    } else {
+        format!("parts {:03}-{:03}", part, entry.last_part())
+    };
+    out.println(&format!(
+        "  {} {} ({}) in {}",
+        "Stored".green(),
+        name.display(),
+        human(entry.size),
+        parts
+    ));
+    push_entry(&mut idx, entry, part_hashes);
+    idx.write(index_path)?;
+    Ok(())
+}
+
+/// Stream names become index paths, so they get the same rules restore
+/// enforces: relative, no `..`, not empty.
+pub fn check_name(name: &Path) -> Result<()> {
+    if name.as_os_str().is_empty() || !name.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!(
+            "Invalid stream name '{}': use a relative path without '.' or '..'",
+            name.display()
+        );
+    }
+    Ok(())
+}
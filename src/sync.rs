@@ -0,0 +1,374 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Replicas: `sync` copies an archive to a second directory or backend and
+// records the copy in replicas.arc.json next to the source index, which
+// `verify --replicas` walks.
+//
+// A replica is self-contained: parts that an `update` left in older
+// archive directories are copied into `baseN/` subdirectories and the
+// replica's index points there instead.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::checksum::HashAlgo;
+use crate::config::Config;
+use crate::index::seal_path;
+use crate::output::OutputCtx;
+use crate::store::Store;
+use crate::utils::{human, now};
+use crate::verify;
+
+pub const MANIFEST: &str = "replicas.arc.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub replicas: Vec<Replica>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Replica {
+    /// archive directory as `sync` was given it; local paths are absolute
+    pub location: String,
+    pub synced_at_unix: u64,
+    /// BLAKE3 of the replica's index.arc.json
+    pub index_blake3: String,
+    pub parts: Vec<PartRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartRecord {
+    /// relative to the replica directory
+    pub name: String,
+    pub size: u64,
+    pub blake3: String,
+}
+
+impl Manifest {
+    /// The manifest next to `index_path`, or an empty one.
+    pub fn read(store: &Store, index_path: &Path) -> Result<Self> {
+        let path = manifest_path(index_path);
+        if !store.exists(&path)? {
+            return Ok(Self::default());
+        }
+        let mut json = vec![];
+        store.reader(&path, 0, None)?.read_to_end(&mut json)?;
+        serde_json::from_slice(&json)
+            .with_context(|| format!("Invalid replica manifest {}", store.location(&path)))
+    }
+
+    fn write(&self, store: &Store, index_path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        store.write_file(
+            &manifest_path(index_path),
+            &mut json.as_slice(),
+            json.len() as u64,
+        )?;
+        Ok(())
+    }
+}
+
+fn manifest_path(index_path: &Path) -> PathBuf {
+    index_path.with_file_name(MANIFEST)
+}
+
+/// Part file name relative to a replica: base 0 at the top, older bases
+/// in `baseN/`.
+fn replica_name(base: u32, part: u32, ext: &str) -> String {
+    let name = format!("data.part{:03}{}", part, ext);
+    if base == 0 {
+        name
+    } else {
+        format!("base{base}/{name}")
+    }
+}
+
+pub fn sync(
+    src: &Store,
+    index_path: &Path,
+    dest: &Path,
+    cfg: &Config,
+    out: &OutputCtx,
+) -> Result<()> {
+    let mut idx = src
+        .read_index(index_path)
+        .with_context(|| format!("Cannot read index: {}", src.location(index_path)))?;
+    let index_dir = index_path.parent().unwrap_or(Path::new("."));
+    let (dst, dest_dir) = Store::connect(dest, cfg)?;
+    let location = if let Store::Local = dst {
+        std::path::absolute(&dest_dir)?.display().to_string()
+    } else {
+        dst.location(&dest_dir)
+    };
+
+    out.println(&format!(
+        "{} {} {} {}",
+        "Syncing archive:".cyan().bold(),
+        src.location(index_path).yellow(),
+        "->".dimmed(),
+        location.yellow()
+    ));
+    out.println("");
+
+    // every part an entry points at, plus the ones verify expects
+    let mut parts: BTreeSet<(u32, u32)> = (0..idx.header.total_parts).map(|p| (0, p)).collect();
+    for e in &idx.entries {
+        for part in e.tar_part..=e.last_part() {
+            parts.insert((e.tar_base.unwrap_or(0), part));
+        }
+    }
+
+    let ext = idx.header.compression.extension();
+    let bases: Vec<String> = (0..idx.header.part_bases.len().max(1))
+        .map(|b| {
+            if b == 0 {
+                String::new()
+            } else {
+                format!("base{b}")
+            }
+        })
+        .collect();
+    let rebased = bases != idx.header.part_bases;
+
+    if out.dry_run {
+        for &(base, part) in &parts {
+            out.dry(&format!("would sync: {}", replica_name(base, part, ext)));
+        }
+        out.dry(&format!("would record replica in: {}", MANIFEST));
+        return Ok(());
+    }
+
+    dst.mkdir(&dest_dir)?;
+    for base in bases.iter().filter(|b| !b.is_empty()) {
+        dst.mkdir(&dest_dir.join(base))?;
+    }
+
+    let mut records = vec![];
+    let (mut copied, mut present, mut copied_bytes) = (0usize, 0usize, 0u64);
+    for &(base, part) in &parts {
+        let from = idx.header.part_file(index_dir, base, part);
+        let rel = replica_name(base, part, ext);
+        let to = dest_dir.join(&rel);
+
+        let size = src
+            .size(&from)?
+            .with_context(|| format!("Part missing from source: {}", src.location(&from)))?;
+
+        // already there: same size and same content
+        if dst.size(&to)? == Some(size) {
+            let want = hash(src, &from)?;
+            if hash(&dst, &to)? == want {
+                out.println(&format!("  {} {}", "present".dimmed(), rel));
+                records.push(PartRecord {
+                    name: rel,
+                    size,
+                    blake3: want,
+                });
+                present += 1;
+                continue;
+            }
+        }
+
+        let mut reader = HashingReader::new(src.reader(&from, 0, None)?);
+        dst.write_file(&to, &mut reader, size)?;
+        let (got, want) = (reader.count, reader.hasher.finalize().to_hex().to_string());
+        if got != size {
+            anyhow::bail!(
+                "{} changed while copying ({} of {} bytes)",
+                src.location(&from),
+                got,
+                size
+            );
+        }
+        if hash(&dst, &to)? != want {
+            anyhow::bail!("Copy of {} does not match the source", dst.location(&to));
+        }
+        out.println(&format!("  {} {} ({})", "copied".green(), rel, human(size)));
+        records.push(PartRecord {
+            name: rel,
+            size,
+            blake3: want,
+        });
+        copied += 1;
+        copied_bytes += size;
+    }
+
+    // the index goes last, so a replica with an index is a complete one
+    let to_index = dest_dir.join("index.arc.json");
+    let index_blake3 = if rebased {
+        idx.header.part_bases = bases;
+        dst.write_index(&mut idx, &to_index)?;
+        idx.to_bytes()?.1
+    } else {
+        let mut json = vec![];
+        src.reader(index_path, 0, None)?.read_to_end(&mut json)?;
+        let seal = blake3::hash(&json).to_hex().to_string();
+        dst.write_file(&to_index, &mut json.as_slice(), json.len() as u64)?;
+        dst.write_file(
+            &seal_path(&to_index),
+            &mut seal.as_bytes(),
+            seal.len() as u64,
+        )?;
+        seal
+    };
+    dst.read_index(&to_index).with_context(|| {
+        format!(
+            "Copied index does not read back: {}",
+            dst.location(&to_index)
+        )
+    })?;
+    out.println(&format!(
+        "  {} index.arc.json{}",
+        "copied".green(),
+        if rebased {
+            " (part_bases rewritten)"
+        } else {
+            ""
+        }
+    ));
+
+    let mut manifest = Manifest::read(src, index_path)?;
+    manifest.replicas.retain(|r| r.location != location);
+    manifest.replicas.push(Replica {
+        location,
+        synced_at_unix: now(),
+        index_blake3,
+        parts: records,
+    });
+    manifest.write(src, index_path)?;
+
+    out.println("");
+    out.println(&format!(
+        "{} {} part(s) copied ({}), {} already present; replica recorded in {}",
+        "Synced.".green().bold(),
+        copied,
+        human(copied_bytes),
+        present,
+        MANIFEST
+    ));
+    Ok(())
+}
+
+/// `verify` each replica recorded next to `index_path`, after checking its
+/// index is the one `sync` wrote. Fails if any replica does.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_replicas(
+    src: &Store,
+    index_path: &Path,
+    continue_on_error: bool,
+    quick: bool,
+    threads: usize,
+    algos: &[HashAlgo],
+    journal: bool,
+    cfg: &Config,
+    out: &OutputCtx,
+) -> Result<()> {
+    let manifest = Manifest::read(src, index_path)?;
+    if manifest.replicas.is_empty() {
+        anyhow::bail!(
+            "No replicas recorded for {} (run `archivum sync` first)",
+            src.location(index_path)
+        );
+    }
+
+    let mut failed = 0;
+    for replica in &manifest.replicas {
+        out.println("");
+        let result = (|| -> Result<()> {
+            let (store, dir) = Store::connect(Path::new(&replica.location), cfg)?;
+            let replica_index = dir.join("index.arc.json");
+            let got = hash(&store, &replica_index)?;
+            if got != replica.index_blake3 {
+                anyhow::bail!(
+                    "index of replica {} is not the one synced on {}",
+                    replica.location,
+                    crate::utils::fmt_time(replica.synced_at_unix)
+                );
+            }
+            if quick {
+                verify::verify_quick(&store, &replica_index, continue_on_error, journal, out)
+            } else {
+                verify::verify(
+                    &store,
+                    &replica_index,
+                    continue_on_error,
+                    threads,
+                    None,
+                    algos,
+                    journal,
+                    out,
+                )
+            }
+        })();
+        if let Err(e) = result {
+            out.eprintln(&format!(
+                "{} replica {}: {:#}",
+                "FAIL".red().bold(),
+                replica.location,
+                e
+            ));
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!(
+            "{} of {} replica(s) failed verification",
+            failed,
+            manifest.replicas.len()
+        );
+    }
+    Ok(())
+}
+
+/// BLAKE3 (hex) of a whole file.
+fn hash(store: &Store, path: &Path) -> Result<String> {
+    let mut reader = HashingReader::new(store.reader(path, 0, None)?);
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.hasher.finalize().to_hex().to_string())
+}
+
+/// Hashes and counts what passes through.
+struct HashingReader<R: Read> {
+    inner: R,
+    hasher: blake3::Hasher,
+    count: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: blake3::Hasher::new(),
+            count: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.count += n as u64;
+        Ok(n)
+    }
+}
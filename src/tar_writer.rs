@@ -18,24 +18,34 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
-use tar::Builder;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tar::{Builder, HeaderMode};
 
+use crate::checksum::{Digests, HashAlgo, MultiHasher, blake3_reader};
 use crate::compress::CompressionAlgo;
-use crate::index::ArchivumIndex;
-use crate::scan::EntryType;
-use crate::utils::human;
+use crate::index::{ArchivumIndex, IndexEntry, PartHash};
+use crate::output::OutputCtx;
+use crate::scan::{EntryType, ErrorStage, ScanError, mtime_of};
+use crate::source::Sources;
+use crate::utils::{TempPath, human};
 
+/// Assign files to parts and write them; `on_part` gets each part's path as
+/// soon as it is complete.
+#[allow(clippy::too_many_arguments)]
 pub fn write_archive(
-    root: &Path,
+    sources: &Sources,
     out_dir: &Path,
     idx: &mut ArchivumIndex,
     split_bytes: u64,
     split_files: usize, // 0 = disabled
     algo: &CompressionAlgo,
     zstd_level: i32,
+    retries: u32,
+    on_part: &mut dyn FnMut(&Path) -> Result<()>,
 ) -> Result<()> {
     let total_bytes: u64 = idx.header.total_size;
     let ext = algo.extension();
@@ -71,8 +81,9 @@ pub fn write_archive(
         current_file_count += 1;
     }
 
+    let has_meta = idx.entries.iter().any(|e| e.entry_type != EntryType::File);
     let total_parts = if file_indices.is_empty() {
-        0
+        u32::from(has_meta)
     } else {
         current_part + 1
     };
@@ -92,10 +103,21 @@ pub fn write_archive(
         .progress_chars("=> "),
     );
 
+    let mut outcome = PartOutcome::default();
+    idx.header.part_hashes.clear();
     for part in 0..total_parts {
         let part_path = out_dir.join(format!("data.part{:03}{}", part, ext));
-        write_part(root, idx, part, &part_path, algo, zstd_level, &pb)?;
+        let o = write_part(
+            sources, idx, part, &part_path, algo, zstd_level, retries, &pb,
+        )?;
+        idx.header
+            .part_hashes
+            .push(hash_part(&part_path, None, part)?);
+        on_part(&part_path)?;
+        outcome.rewritten.extend(o.rewritten);
+        outcome.failed.extend(o.failed);
     }
+    apply_outcome(idx, outcome);
 
     pb.finish_with_message(format!(
         "{}  ({} parts, {})",
@@ -107,37 +129,829 @@ pub fn write_archive(
     Ok(())
 }
 
+/// Write the whole archive as one compressed tar stream into `w` (used by
+/// `create --stdout`). Every file lands in part 0, and each carries its
+/// SHA-256 in a PAX record so a reader can check it on the fly.
+pub fn write_single_stream<W: Write + 'static>(
+    sources: &Sources,
+    idx: &mut ArchivumIndex,
+    w: W,
+    algo: &CompressionAlgo,
+    zstd_level: i32,
+    retries: u32,
+) -> Result<()> {
+    for e in idx.entries.iter_mut() {
+        e.tar_part = 0;
+    }
+    idx.header.total_parts = 1;
+
+    let pb = ProgressBar::new(idx.header.total_size);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "  {spinner:.cyan} Streaming  [{bar:40.cyan/blue}] {bytes}/{total_bytes}  ETA {eta}",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+
+    let mut writer = algo.wrap_writer(w, zstd_level)?;
+    let mut builder = Builder::new(&mut writer);
+    let outcome = write_members(&mut builder, sources, idx, 0, retries, &pb, true)?;
+    builder.finish().context("Failed to finalize tar stream")?;
+    drop(builder);
+    writer.flush()?;
+    drop(writer);
+    apply_outcome(idx, outcome);
+
+    pb.finish_and_clear();
+    Ok(())
+}
+
+fn apply_outcome(idx: &mut ArchivumIndex, outcome: PartOutcome) {
+    let rewrites = outcome.by_path(idx);
+    if !rewrites.is_empty() {
+        for e in idx.entries.iter_mut() {
+            let origin = e.dedup_of.clone().unwrap_or_else(|| e.path.clone());
+            if let Some(r) = rewrites.get(origin.as_path()) {
+                r.apply(e);
+            }
+        }
+        idx.header.total_size = idx
+            .entries
+            .iter()
+            .filter(|e| e.entry_type == EntryType::File)
+            .map(|e| e.size)
+            .sum();
+    }
+    if !outcome.failed.is_empty() {
+        drop_unarchived(idx, outcome.failed);
+    }
+}
+
+/// Report files flagged as changed during backup; an error under `fail`.
+/// Only `idx`'s own entries count, so pass the index this run wrote.
+pub fn check_changed(idx: &ArchivumIndex, fail: bool, out: &OutputCtx) -> Result<()> {
+    let changed = idx.changed_during_backup();
+    if changed.is_empty() {
+        return Ok(());
+    }
+    out.eprintln(&format!(
+        "  {} {} file(s) changed while being archived (flagged in the index)",
+        "warning:".yellow(),
+        changed.len()
+    ));
+    if fail {
+        anyhow::bail!(
+            "{} file(s) changed during backup, first: {}",
+            changed.len(),
+            changed[0].path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Size and BLAKE3 of a finished part file, as `verify --quick` checks it.
+pub fn hash_part(path: &Path, base: Option<u32>, part: u32) -> Result<PartHash> {
+    let mut f = File::open(path).with_context(|| format!("Cannot open {}", path.display()))?;
+    let (size, blake3) = blake3_reader(&mut f)?;
+    Ok(PartHash {
+        base,
+        part,
+        size,
+        blake3,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
 fn write_part(
-    root: &Path,
+    sources: &Sources,
     idx: &ArchivumIndex,
     part: u32,
     part_path: &Path,
     algo: &CompressionAlgo,
     zstd_level: i32,
+    retries: u32,
     pb: &ProgressBar,
-) -> Result<()> {
+) -> Result<PartOutcome> {
     let file = File::create(part_path)
         .with_context(|| format!("Cannot create {}", part_path.display()))?;
 
     let mut writer: Box<dyn Write> = algo.wrap_writer(file, zstd_level)?;
     let mut builder = Builder::new(&mut writer);
+    let outcome = write_members(&mut builder, sources, idx, part, retries, pb, false)?;
+    append_manifest(&mut builder, idx, part, &outcome)?;
+
+    builder.finish().context("Failed to finalize tar part")?;
+    drop(builder);
+    drop(writer);
+
+    Ok(outcome)
+}
+
+/// Append the members of `part`: non-file entries first (part 0 only), then
+/// the files assigned to it. A file with dedup aliases is preceded by a PAX
+/// record naming them; with `pax_sha`, every file gets one carrying the
+/// SHA-256 computed at scan time as well.
+fn write_members<W: Write>(
+    builder: &mut Builder<W>,
+    sources: &Sources,
+    idx: &ArchivumIndex,
+    part: u32,
+    retries: u32,
+    pb: &ProgressBar,
+    pax_sha: bool,
+) -> Result<PartOutcome> {
+    let repro = idx.header.reproducible;
+    let clamp = |mtime: u64| idx.header.clamp_mtime(Some(mtime)).unwrap_or(0);
 
-    for entry in idx
+    // directories, symlinks and special files carry no data; they go at the
+    // head of part 000 in index order so the parts form a standalone tarball
+    if part == 0 {
+        for entry in idx
+            .entries
+            .iter()
+            .filter(|e| e.entry_type != EntryType::File)
+        {
+            append_meta(builder, entry, clamp(entry.mtime.unwrap_or(0)))
+                .with_context(|| format!("Failed to append {}", entry.path.display()))?;
+        }
+    }
+
+    // dedup aliases ride along with their origin, so a stream reader knows
+    // every destination before the index arrives and `repair` can rebuild
+    // them without one
+    let mut aliases: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for e in &idx.entries {
+        if let Some(orig) = &e.dedup_of {
+            aliases.entry(orig.as_path()).or_default().push(&e.path);
+        }
+    }
+
+    let mut outcome = PartOutcome::default();
+    let extras = idx.header.hash_algos();
+
+    // with retries, a file that moves is copied to a spool until one read
+    // starts and ends on the same size and mtime, and the member is that copy
+    let spool = (retries > 0).then(|| {
+        TempPath::new(std::env::temp_dir().join(format!("archivum-retry-{}", std::process::id())))
+    });
+
+    for (ei, entry) in idx.entries.iter().enumerate().filter(|(_, e)| {
+        e.entry_type == EntryType::File && e.tar_part == part && e.dedup_of.is_none()
+    }) {
+        let full = sources.resolve(&entry.path);
+        let mut f = match File::open(&full) {
+            Ok(f) => f,
+            Err(e) => {
+                pb.suspend(|| {
+                    eprintln!(
+                        "  {} {}: {}",
+                        "SKIPPED".red().bold(),
+                        entry.path.display(),
+                        e
+                    )
+                });
+                let err = ScanError::new(entry.path.clone(), ErrorStage::Archive, &e);
+                outcome.failed.push((ei, err));
+                pb.inc(entry.size);
+                continue;
+            }
+        };
+        let settled = match &spool {
+            Some(spool) => Some(read_settled(
+                &mut f,
+                &full,
+                spool.path(),
+                &extras,
+                retries,
+                &entry.path,
+                pb,
+            )?),
+            None => None,
+        };
+
+        // without a spool the header size comes from the scan, not a fresh
+        // stat, so it always agrees with the part assignment and with what
+        // ExactReader emits
+        let (size, mtime) = settled
+            .as_ref()
+            .map_or((entry.size, entry.mtime), |s| (s.size, s.mtime));
+        let mut header = tar::Header::new_gnu();
+        if repro {
+            // fixed owner, no user/group names, clamped mtime; mode is kept
+            header.set_metadata_in_mode(&f.metadata()?, HeaderMode::Deterministic);
+            if let Some(mode) = entry.unix_mode {
+                header.set_mode(mode & 0o7777);
+            }
+            header.set_mtime(clamp(mtime.unwrap_or(0)));
+        } else {
+            header.set_metadata_in_mode(&f.metadata()?, HeaderMode::Complete);
+        }
+        header.set_size(size);
+
+        let mut records: Vec<(&str, &[u8])> = vec![];
+        let sha = match &settled {
+            Some(s) => Some(s.digests[0].1.as_str()),
+            None => entry.sha256.as_deref(),
+        };
+        if let Some(sha) = sha.filter(|_| pax_sha) {
+            records.push((PAX_SHA256, sha.as_bytes()));
+        }
+        for alias in aliases.get(entry.path.as_path()).into_iter().flatten() {
+            records.push((PAX_COPY, alias.as_os_str().as_encoded_bytes()));
+        }
+        append_pax(builder, &entry.path, header.mtime()?, &records)?;
+
+        let rewrite = match settled {
+            Some(mut s) => {
+                builder
+                    .append_data(&mut header, &entry.path, &mut s.file)
+                    .with_context(|| format!("Failed to append {}", entry.path.display()))?;
+                let same = s.size == entry.size
+                    && s.mtime == entry.mtime
+                    && entry.sha256.as_deref() == Some(s.digests[0].1.as_str());
+                (s.torn || !same).then_some(Rewrite {
+                    size: s.size,
+                    mtime: s.mtime,
+                    digests: s.digests,
+                    torn: s.torn,
+                })
+            }
+            None => {
+                let mut data = ExactReader::new(&mut f, entry.size, &extras);
+                builder
+                    .append_data(&mut header, &entry.path, &mut data)
+                    .with_context(|| format!("Failed to append {}", entry.path.display()))?;
+                let (short, digests) = data.finish();
+                let sha = &digests[0].1;
+
+                // re-stat and compare with the scan
+                let grew = !short && f.read(&mut [0u8; 1])? > 0;
+                let after = std::fs::symlink_metadata(&full).ok();
+                let moved = after
+                    .as_ref()
+                    .is_none_or(|m| m.len() != entry.size || mtime_of(m) != entry.mtime);
+                let rehashed = entry.sha256.as_ref().is_some_and(|s| s != sha);
+                (short || grew || moved || rehashed).then_some(Rewrite {
+                    size: entry.size,
+                    mtime: entry.mtime,
+                    digests,
+                    torn: true,
+                })
+            }
+        };
+
+        if let Some(r) = rewrite {
+            if r.torn {
+                pb.suspend(|| {
+                    eprintln!(
+                        "  {} {}",
+                        "CHANGED during backup:".yellow().bold(),
+                        entry.path.display()
+                    )
+                });
+            }
+            outcome.rewritten.push((ei, r));
+        }
+        pb.inc(entry.size);
+    }
+
+    Ok(outcome)
+}
+
+/// PAX keyword for the per-file checksum in single-stream archives.
+pub const PAX_SHA256: &str = "ARCHIVUM.sha256";
+/// PAX keyword naming a dedup alias that takes this member's content, in
+/// streams and parts alike.
+pub const PAX_COPY: &str = "ARCHIVUM.copy";
+
+/// PAX keyword marking the manifest member (its value is the part number).
+pub const PAX_MANIFEST: &str = "ARCHIVUM.manifest";
+/// Name of the manifest member that ends every part.
+pub const MANIFEST_NAME: &str = ".archivum/manifest.json";
+
+/// Whether `item` is a part's manifest rather than an archived entry.
+pub fn is_manifest<R: Read>(item: &mut tar::Entry<R>) -> Result<bool> {
+    Ok(item
+        .pax_extensions()?
+        .is_some_and(|mut pax| pax.any(|ext| ext.is_ok_and(|e| e.key() == Ok(PAX_MANIFEST)))))
+}
+
+/// End the part with its own slice of the index: the header as it stands,
+/// the entries stored in this part (with what writing it found out) and
+/// where each sits in the index, and the scan errors. `repair` rebuilds
+/// the index from these when the JSON file is gone.
+fn append_manifest<W: Write>(
+    builder: &mut Builder<W>,
+    idx: &ArchivumIndex,
+    part: u32,
+    outcome: &PartOutcome,
+) -> Result<()> {
+    let rewrites = outcome.by_path(idx);
+    let failed: HashSet<usize> = outcome.failed.iter().map(|(i, _)| *i).collect();
+    let stored: HashMap<&Path, usize> = idx
         .entries
         .iter()
-        .filter(|e| e.entry_type == EntryType::File && e.tar_part == part && e.dedup_of.is_none())
-    {
-        let full = root.join(&entry.path);
-        let mut f = File::open(&full).with_context(|| format!("Cannot open {}", full.display()))?;
-        builder
-            .append_file(&entry.path, &mut f)
-            .with_context(|| format!("Failed to append {}", entry.path.display()))?;
-        pb.inc(entry.size);
+        .enumerate()
+        .filter(|(i, e)| {
+            e.entry_type == EntryType::File
+                && e.dedup_of.is_none()
+                && e.tar_part == part
+                && !failed.contains(i)
+        })
+        .map(|(i, e)| (e.path.as_path(), i))
+        .collect();
+
+    let (mut entries, mut positions) = (vec![], vec![]);
+    for (i, e) in idx.entries.iter().enumerate() {
+        let here = match (&e.dedup_of, &e.entry_type) {
+            (Some(orig), _) => stored.contains_key(orig.as_path()),
+            (None, EntryType::File) => stored.contains_key(e.path.as_path()),
+            (None, _) => part == 0,
+        };
+        if !here {
+            continue;
+        }
+        let mut e = e.clone();
+        if let Some(r) = rewrites.get(e.dedup_of.as_deref().unwrap_or(&e.path)) {
+            r.apply(&mut e);
+        }
+        entries.push(e);
+        positions.push(i);
     }
+    append_manifest_entries(builder, idx, part, &entries, &positions)
+}
 
-    builder.finish().context("Failed to finalize tar part")?;
-    drop(builder);
-    drop(writer);
+/// Append the manifest member of `part`, holding `entries`, which sit at
+/// `positions` in `idx`. The header and scan errors come from `idx`.
+pub fn append_manifest_entries<W: Write>(
+    builder: &mut Builder<W>,
+    idx: &ArchivumIndex,
+    part: u32,
+    entries: &[IndexEntry],
+    positions: &[usize],
+) -> Result<()> {
+    // mtimes as the index will record them
+    let entries: Vec<IndexEntry> = entries
+        .iter()
+        .map(|e| IndexEntry {
+            mtime: idx.header.clamp_mtime(e.mtime),
+            ..e.clone()
+        })
+        .collect();
+    let data = serde_json::to_vec(&serde_json::json!({
+        "header": &idx.header,
+        "entries": entries,
+        "errors": &idx.errors,
+        "positions": positions,
+    }))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(idx.header.created_at_unix);
+    let path = Path::new(MANIFEST_NAME);
+    append_pax(
+        builder,
+        path,
+        idx.header.created_at_unix,
+        &[(PAX_MANIFEST, part.to_string().as_bytes())],
+    )?;
+    builder
+        .append_data(&mut header, path, data.as_slice())
+        .context("Failed to append the part manifest")?;
+    Ok(())
+}
+
+/// A PAX extended header ('x') applying to the member that follows, with
+/// that member's mtime and the same fixed owner as reproducible members.
+fn append_pax<W: Write>(
+    builder: &mut Builder<W>,
+    path: &Path,
+    mtime: u64,
+    records: &[(&str, &[u8])],
+) -> Result<()> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let mut data = Vec::new();
+    for (key, value) in records {
+        // "<len> <key>=<value>\n", where <len> counts its own digits
+        let body = key.len() + value.len() + 3;
+        let mut len = body + 1;
+        while len.to_string().len() + body != len {
+            len += 1;
+        }
+        data.extend_from_slice(format!("{len} {key}=").as_bytes());
+        data.extend_from_slice(value);
+        data.push(b'\n');
+    }
+    let mut header = tar::Header::new_ustar();
+    let name = Path::new("PaxHeaders").join(path.file_name().unwrap_or(path.as_os_str()));
+    // the name is informational; a long one is cut rather than failing
+    if header.set_path(&name).is_err() {
+        header.set_path("PaxHeaders/entry")?;
+    }
+    header.set_entry_type(tar::EntryType::XHeader);
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    builder.append(&header, data.as_slice())?;
+    Ok(())
+}
+
+/// Archived bytes that differ from what the scan recorded for a file.
+struct Rewrite {
+    size: u64,
+    mtime: Option<u64>,
+    digests: Digests,
+    /// the file moved while it was read, so the member may mix old and new
+    /// bytes; otherwise it is a settled copy taken after the scan
+    torn: bool,
+}
+
+impl Rewrite {
+    /// Bring `e` in line with what was archived. `e` is the rewritten file
+    /// or one of its dedup aliases, which restore as copies of these bytes
+    /// and so are flagged: their own paths never held them as far as we know.
+    fn apply(&self, e: &mut IndexEntry) {
+        e.size = self.size;
+        e.set_digests(self.digests.clone());
+        if e.dedup_of.is_some() {
+            e.changed_during_backup = true;
+        } else {
+            e.mtime = self.mtime;
+            e.changed_during_backup |= self.torn;
+        }
+    }
+}
+
+/// What happened to a part's files beyond a clean write.
+#[derive(Default)]
+struct PartOutcome {
+    /// (entry index, what was archived) for files that moved under us
+    rewritten: Vec<(usize, Rewrite)>,
+    /// files that could not be opened; they are not in the tar
+    failed: Vec<(usize, ScanError)>,
+}
+
+impl PartOutcome {
+    /// Rewrites keyed by the rewritten file's path, which is also the
+    /// `dedup_of` of its aliases.
+    fn by_path(&self, idx: &ArchivumIndex) -> HashMap<PathBuf, &Rewrite> {
+        self.rewritten
+            .iter()
+            .map(|(i, r)| (idx.entries[*i].path.clone(), r))
+            .collect()
+    }
+}
+
+/// A spooled copy of a file, taken by `read_settled`.
+struct Settled {
+    file: File,
+    size: u64,
+    mtime: Option<u64>,
+    digests: Digests,
+    /// the last attempt still saw the file move
+    torn: bool,
+}
+
+/// Copy the open file `f` (at `full`) into `spool`, and again from a fresh
+/// open while a copy does not start and end on the same size and mtime, at
+/// most `1 + retries` times. The spool is left rewound for reading.
+#[allow(clippy::too_many_arguments)]
+fn read_settled(
+    f: &mut File,
+    full: &Path,
+    spool: &Path,
+    extras: &[HashAlgo],
+    retries: u32,
+    rel: &Path,
+    pb: &ProgressBar,
+) -> Result<Settled> {
+    let mut attempt = 0;
+    loop {
+        let before = f.metadata()?;
+        let mut copy = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(spool)
+            .with_context(|| format!("Cannot create spool {}", spool.display()))?;
+        let mut hasher = MultiHasher::with_sha256(extras);
+        let mut buf = vec![0u8; 256 * 1024];
+        let mut size = 0u64;
+        loop {
+            let n = f.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            copy.write_all(&buf[..n])?;
+            size += n as u64;
+        }
+
+        let mtime = mtime_of(&before);
+        let after = std::fs::symlink_metadata(full).ok();
+        let torn = before.len() != size
+            || after
+                .as_ref()
+                .is_none_or(|m| m.len() != size || mtime_of(m) != mtime);
+        if !torn || attempt >= retries {
+            copy.seek(SeekFrom::Start(0))?;
+            return Ok(Settled {
+                file: copy,
+                size,
+                mtime,
+                digests: hasher.finish(),
+                torn,
+            });
+        }
+
+        attempt += 1;
+        pb.suspend(|| {
+            eprintln!(
+                "  {} {} (retry {}/{})",
+                "changed while reading:".yellow(),
+                rel.display(),
+                attempt,
+                retries
+            )
+        });
+        std::thread::sleep(Duration::from_millis(100 * u64::from(attempt)));
+        // reopen: the file may have been replaced rather than rewritten
+        *f = File::open(full).with_context(|| format!("Cannot reopen {}", full.display()))?;
+    }
+}
+
+/// Remove entries that never made it into a part (plus dedup aliases that
+/// point at them), record why, and fix up the header totals.
+fn drop_unarchived(idx: &mut ArchivumIndex, failed: Vec<(usize, ScanError)>) {
+    let gone: HashSet<usize> = failed.iter().map(|(i, _)| *i).collect();
+    let gone_paths: HashSet<std::path::PathBuf> =
+        gone.iter().map(|&i| idx.entries[i].path.clone()).collect();
+    idx.errors.extend(failed.into_iter().map(|(_, e)| e));
+
+    let mut kept = Vec::with_capacity(idx.entries.len());
+    for (i, e) in idx.entries.drain(..).enumerate() {
+        if gone.contains(&i) {
+            continue;
+        }
+        if let Some(orig) = e.dedup_of.as_ref().filter(|o| gone_paths.contains(*o)) {
+            idx.errors.push(ScanError {
+                message: format!("dedup origin {} could not be archived", orig.display()),
+                path: e.path,
+                stage: ErrorStage::Archive,
+                kind: crate::scan::ErrorKind::Io,
+            });
+            continue;
+        }
+        kept.push(e);
+    }
+    idx.entries = kept;
+
+    let files = idx
+        .entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File);
+    idx.header.total_files = files.clone().count() as u64;
+    idx.header.total_size = files.map(|e| e.size).sum();
+}
+
+/// Hands the tar builder exactly `remaining` bytes whatever the file does in
+/// the meantime: a short file is zero-padded and growth is cut off, so the
+/// member always matches its header. Hashes everything it hands out, with
+/// SHA-256 and the archive's extra algorithms.
+struct ExactReader<'a> {
+    file: &'a mut File,
+    remaining: u64,
+    short: bool,
+    hasher: MultiHasher,
+}
+
+impl<'a> ExactReader<'a> {
+    fn new(file: &'a mut File, size: u64, extras: &[HashAlgo]) -> Self {
+        Self {
+            file,
+            remaining: size,
+            short: false,
+            hasher: MultiHasher::with_sha256(extras),
+        }
+    }
 
+    /// (file ended early, digests of the emitted bytes, SHA-256 first)
+    fn finish(self) -> (bool, Digests) {
+        (self.short, self.hasher.finish())
+    }
+}
+
+impl Read for ExactReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let want = self.remaining.min(buf.len() as u64) as usize;
+        let mut n = if self.short {
+            0
+        } else {
+            self.file.read(&mut buf[..want])?
+        };
+        if n == 0 {
+            self.short = true;
+            buf[..want].fill(0);
+            n = want;
+        }
+        self.hasher.update(&buf[..n]);
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn append_meta<W: Write>(builder: &mut Builder<W>, entry: &IndexEntry, mtime: u64) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    let (kind, default_mode) = match entry.entry_type {
+        EntryType::Directory => (tar::EntryType::Directory, 0o755),
+        EntryType::Symlink => (tar::EntryType::Symlink, 0o777),
+        EntryType::CharDevice => (tar::EntryType::Char, 0o644),
+        EntryType::BlockDevice => (tar::EntryType::Block, 0o644),
+        EntryType::Fifo => (tar::EntryType::Fifo, 0o644),
+        EntryType::File => unreachable!("regular files are appended with their content"),
+    };
+    header.set_entry_type(kind);
+    header.set_size(0);
+    header.set_mode(entry.unix_mode.unwrap_or(default_mode) & 0o7777);
+    header.set_mtime(mtime);
+    // ownership is not recorded in the index; leaving the fields blank makes
+    // strict readers reject the header
+    header.set_uid(0);
+    header.set_gid(0);
+
+    match entry.entry_type {
+        EntryType::Symlink => {
+            // a symlink whose target could not be read is kept as an empty link
+            let target = entry.symlink_target.clone().unwrap_or_default();
+            builder.append_link(&mut header, &entry.path, target)?;
+        }
+        _ => {
+            if entry.entry_type.is_special() {
+                header.set_device_major(entry.dev_major.unwrap_or(0))?;
+                header.set_device_minor(entry.dev_minor.unwrap_or(0))?;
+            }
+            builder.append_data(&mut header, &entry.path, std::io::empty())?;
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::hash_reader;
+    use crate::scan::ScanEntry;
+
+    fn scanned(path: &str, size: u64) -> ScanEntry {
+        ScanEntry {
+            relative_path: path.into(),
+            entry_type: EntryType::File,
+            size,
+            mtime: Some(1),
+            unix_mode: Some(0o644),
+            symlink_target: None,
+            dev_major: None,
+            dev_minor: None,
+        }
+    }
+
+    /// `a.txt` and its dedup alias `b.txt`, as a scan that read `seen` saw them.
+    fn aliased_index(seen: &[u8]) -> ArchivumIndex {
+        let size = seen.len() as u64;
+        let mut idx = ArchivumIndex::build(
+            vec![scanned("a.txt", size), scanned("b.txt", size)],
+            CompressionAlgo::None,
+            0,
+        );
+        let sha = hash_reader(&mut &seen[..]).unwrap();
+        for e in &mut idx.entries {
+            e.sha256 = Some(sha.clone());
+        }
+        idx.entries[1].dedup_of = Some("a.txt".into());
+        idx
+    }
+
+    fn write(idx: &ArchivumIndex, dir: &Path, retries: u32) -> (Vec<u8>, PartOutcome) {
+        let sources = Sources::new(dir, &[]).unwrap();
+        let mut builder = Builder::new(Vec::new());
+        let pb = ProgressBar::hidden();
+        let outcome = write_members(&mut builder, &sources, idx, 0, retries, &pb, false).unwrap();
+        (builder.into_inner().unwrap(), outcome)
+    }
+
+    fn member(tar: &[u8], name: &str) -> Vec<u8> {
+        let mut ar = tar::Archive::new(tar);
+        for item in ar.entries().unwrap() {
+            let mut item = item.unwrap();
+            if item.path().unwrap() == Path::new(name) {
+                let mut data = vec![];
+                item.read_to_end(&mut data).unwrap();
+                return data;
+            }
+        }
+        panic!("no member {name}");
+    }
+
+    #[test]
+    fn file_that_moved_is_flagged_with_what_was_written() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), b"grown since the scan").unwrap();
+        let mut idx = aliased_index(b"old bytes");
+
+        let (tar, outcome) = write(&idx, dir.path(), 0);
+        assert_eq!(member(&tar, "a.txt"), b"grown sin");
+        apply_outcome(&mut idx, outcome);
+
+        let written = hash_reader(&mut &b"grown sin"[..]).unwrap();
+        let a = &idx.entries[0];
+        assert!(a.changed_during_backup);
+        assert_eq!(a.size, 9);
+        assert_eq!(a.sha256.as_deref(), Some(written.as_str()));
+    }
+
+    #[test]
+    fn alias_takes_over_its_origins_new_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), b"new bytes").unwrap();
+        let mut idx = aliased_index(b"old bytes");
+
+        let (_, outcome) = write(&idx, dir.path(), 0);
+        apply_outcome(&mut idx, outcome);
+
+        let (a, b) = (&idx.entries[0], &idx.entries[1]);
+        assert_eq!(b.dedup_of.as_deref(), Some(Path::new("a.txt")));
+        assert_eq!(b.sha256, a.sha256);
+        assert!(b.changed_during_backup);
+    }
+
+    #[test]
+    fn retry_archives_a_settled_copy_without_flagging_it() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), b"rewritten after the scan").unwrap();
+        let mut idx = aliased_index(b"old bytes");
+
+        let (tar, outcome) = write(&idx, dir.path(), 2);
+        assert_eq!(member(&tar, "a.txt"), b"rewritten after the scan");
+        apply_outcome(&mut idx, outcome);
+
+        let a = &idx.entries[0];
+        assert!(!a.changed_during_backup);
+        assert_eq!(a.size, 24);
+        assert_eq!(idx.header.total_size, 48);
+        // the alias still differs from what its own path held at scan time
+        assert!(idx.entries[1].changed_during_backup);
+        assert_eq!(idx.entries[1].sha256, a.sha256);
+    }
+
+    #[test]
+    fn vanished_origin_is_dropped_with_its_aliases_and_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut idx = aliased_index(b"gone before the write");
+
+        let (_, outcome) = write(&idx, dir.path(), 0);
+        apply_outcome(&mut idx, outcome);
+
+        assert!(idx.entries.is_empty());
+        assert_eq!(idx.header.total_files, 0);
+        assert_eq!(idx.header.total_size, 0);
+        let errors: Vec<_> = idx
+            .errors
+            .iter()
+            .map(|e| (e.path.clone(), e.kind))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (PathBuf::from("a.txt"), crate::scan::ErrorKind::Vanished),
+                (PathBuf::from("b.txt"), crate::scan::ErrorKind::Io),
+            ]
+        );
+        assert!(idx.errors.iter().all(|e| e.stage == ErrorStage::Archive));
+    }
+
+    #[test]
+    fn unchanged_file_has_no_rewrite_with_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, b"steady").unwrap();
+        let mut idx = aliased_index(b"steady");
+        idx.entries[0].mtime = mtime_of(&std::fs::metadata(&path).unwrap());
+
+        let (tar, outcome) = write(&idx, dir.path(), 3);
+        assert!(outcome.rewritten.is_empty());
+        assert_eq!(member(&tar, "a.txt"), b"steady");
+    }
+}
@@ -18,35 +18,40 @@
 use anyhow::{Context, Result};
 use colored::Colorize;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use crate::checksum::{compute_checksums, hash_file};
 use crate::compress::CompressionAlgo;
-use crate::index::{ArchivumIndex, INDEX_VERSION, IndexEntry, IndexHeader};
+use crate::index::{ArchivumIndex, INDEX_VERSION, IndexEntry, IndexHeader, PartHash};
 use crate::output::OutputCtx;
-use crate::scan::{EntryType, scan_directory};
-use crate::tar_writer::write_archive;
-use crate::utils::{fmt_time, human, now};
+use crate::scan::{EntryType, PartialSuccess, ScanReport};
+use crate::selection::Selection;
+use crate::source::Sources;
+use crate::tar_writer::{check_changed, write_archive};
+use crate::utils::{fmt_time, human, now, source_date_epoch};
 
 #[allow(clippy::too_many_arguments)]
 pub fn update(
     old_index_path: &Path,
-    source: &Path,
+    sources: &Sources,
     output_dir: &Path,
     split_bytes: u64,
     split_files: usize,
     algo: &CompressionAlgo,
     zstd_level: i32,
     threads: usize,
-    exclude: &[String],
+    selection: &Selection,
     use_checksum: bool,
+    reproducible: bool,
+    fail_on_change: bool,
+    retries: u32,
     out: &OutputCtx,
 ) -> Result<()> {
     out.println(&format!(
         "{} {} -> {}",
         "Incremental update:".cyan().bold(),
-        source.display().to_string().yellow(),
+        sources.describe().yellow(),
         output_dir.display().to_string().yellow()
     ));
     out.println("");
@@ -62,8 +67,8 @@ pub fn update(
         .map(|e| (e.path.as_path(), e))
         .collect();
 
-    let scan = scan_directory(source, exclude)
-        .with_context(|| format!("Failed to scan {}", source.display()))?;
+    let mut report = ScanReport::default();
+    let scan = sources.scan(selection, &mut report)?;
 
     let mut unchanged: Vec<IndexEntry> = vec![];
     let mut changed_paths: Vec<PathBuf> = vec![];
@@ -75,14 +80,14 @@ pub fn update(
         }
         if let Some(old_entry) = old_map.get(se.relative_path.as_path()) {
             let size_match = se.size == old_entry.size;
-            let mtime_match = se.mtime == old_entry.mtime;
+            let mtime_match = old_idx.header.clamp_mtime(se.mtime) == old_entry.mtime;
 
             let is_unchanged = if use_checksum && old_entry.sha256.is_some() {
                 if size_match && mtime_match {
                     // size+mtime match: assume unchanged, skip the expensive hash
                     true
                 } else {
-                    let actual = hash_file(&source.join(&se.relative_path)).unwrap_or_default();
+                    let actual = hash_file(&sources.resolve(&se.relative_path)).unwrap_or_default();
                     actual == old_entry.sha256.as_deref().unwrap_or("")
                 }
             } else {
@@ -99,15 +104,40 @@ pub fn update(
         }
     }
 
+    // the selection only decides what is looked at again: files it leaves
+    // out are still part of the source and are carried over as they are,
+    // and only files gone from the source are dropped. Streamed entries
+    // have no source file and are kept unless one of that name turned up.
+    let same = unchanged.len();
+    let scanned: HashSet<&Path> = scan.iter().map(|e| e.relative_path.as_path()).collect();
+    let mut kept = 0usize;
+    for e in &old_idx.entries {
+        if e.entry_type != EntryType::File || scanned.contains(e.path.as_path()) {
+            continue;
+        }
+        if e.from_stdin || sources.still_has(&e.path) {
+            unchanged.push(e.clone());
+            if !e.from_stdin {
+                kept += 1;
+            }
+        }
+    }
+
     out.println(&format!(
         "  Unchanged: {}  Changed: {}  New: {}  (source total: {} files)",
-        unchanged.len().to_string().green(),
+        same.to_string().green(),
         changed_paths.len().to_string().yellow(),
         new_paths.len().to_string().cyan(),
         scan.iter()
             .filter(|e| e.entry_type == EntryType::File)
             .count()
     ));
+    if kept > 0 {
+        out.println(&format!(
+            "  Kept outside the selection: {}",
+            kept.to_string().dimmed()
+        ));
+    }
     out.println("");
 
     if out.dry_run {
@@ -123,7 +153,7 @@ pub fn update(
     std::fs::create_dir_all(output_dir)
         .with_context(|| format!("Cannot create output dir {}", output_dir.display()))?;
 
-    let need_rearchive: std::collections::HashSet<&Path> = changed_paths
+    let need_rearchive: HashSet<&Path> = changed_paths
         .iter()
         .chain(new_paths.iter())
         .map(|p| p.as_path())
@@ -147,41 +177,65 @@ pub fn update(
     ));
 
     let mut delta_idx = ArchivumIndex::build(delta_scan, algo.clone(), zstd_level);
-    compute_checksums(source, &mut delta_idx, threads)?;
+    delta_idx.record_scan(report);
+    if reproducible {
+        delta_idx.make_reproducible(source_date_epoch());
+    }
+    // new files get the same digests as the ones carried over
+    delta_idx.header.hashes = old_idx.header.hashes.clone();
+    compute_checksums(sources, &mut delta_idx, threads)?;
 
     write_archive(
-        source,
+        sources,
         output_dir,
         &mut delta_idx,
         split_bytes,
         split_files,
         algo,
         zstd_level,
+        retries,
+        &mut |_| Ok(()),
     )?;
 
-    // part_bases[0] = output_dir (new parts), part_bases[1] = old dir (old parts)
-    let old_rel = relative_path(output_dir, old_index_dir);
+    // part_bases[0] = output_dir (new parts); every base of the old archive
+    // moves up one, so its own parts become base 1 and the bases it took
+    // over from earlier archives follow, made relative to output_dir
+    let mut part_bases = vec![String::new()];
+    for b in 0..old_idx.header.part_bases.len().max(1) {
+        let dir = match old_idx.header.part_bases.get(b).map(String::as_str) {
+            None | Some("") => old_index_dir.to_path_buf(),
+            Some(rel) => old_index_dir.join(rel),
+        };
+        part_bases.push(
+            relative_path(output_dir, &dir)
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    let rebase = |base: Option<u32>| Some(base.unwrap_or(0) + 1);
 
     let mut all_entries: Vec<IndexEntry> = vec![];
 
     for mut e in unchanged {
-        e.tar_base = Some(1);
+        e.tar_base = rebase(e.tar_base);
         all_entries.push(e);
     }
 
-    for e in delta_idx.entries {
-        all_entries.push(e);
-    }
+    all_entries.extend(delta_idx.entries.iter().cloned());
 
     for e in &old_idx.entries {
         if e.entry_type != EntryType::File {
-            all_entries.push(e.clone());
+            // non-file entries stay in whichever old part holds them
+            let mut e = e.clone();
+            e.tar_base = rebase(e.tar_base);
+            all_entries.push(e);
         }
     }
 
     let mut total_files = 0u64;
     let mut total_dirs = 0u64;
     let mut total_symlinks = 0u64;
+    let mut total_special = 0u64;
     let mut total_size = 0u64;
     for e in &all_entries {
         match e.entry_type {
@@ -191,11 +245,22 @@ pub fn update(
             }
             EntryType::Directory => total_dirs += 1,
             EntryType::Symlink => total_symlinks += 1,
+            EntryType::CharDevice | EntryType::BlockDevice | EntryType::Fifo => total_special += 1,
         }
     }
 
-    let ts = now();
-    let new_idx = ArchivumIndex {
+    let mut part_hashes = delta_idx.header.part_hashes.clone();
+    part_hashes.extend(old_idx.header.part_hashes.iter().map(|h| PartHash {
+        base: rebase(h.base),
+        ..h.clone()
+    }));
+
+    let ts = if reproducible {
+        delta_idx.header.created_at_unix
+    } else {
+        now()
+    };
+    let mut new_idx = ArchivumIndex {
         header: IndexHeader {
             version: INDEX_VERSION,
             created_at_unix: ts,
@@ -203,15 +268,24 @@ pub fn update(
             total_files,
             total_dirs,
             total_symlinks,
+            total_special,
             total_size,
             total_parts: delta_idx.header.total_parts,
             compression: algo.clone(),
             zstd_level,
             notes: format!("Incremental update from {}", old_index_path.display()),
-            part_bases: vec![String::new(), old_rel.to_string_lossy().into_owned()],
+            part_bases,
+            reproducible,
+            source_date_epoch: delta_idx.header.source_date_epoch,
+            ignore_sources: delta_idx.header.ignore_sources.clone(),
+            roots: sources.prefixes(),
+            part_hashes,
+            merkle_root: None,
+            hashes: delta_idx.header.hashes.clone(),
             _integrity: None,
         },
         entries: all_entries,
+        errors: delta_idx.errors.clone(),
     };
 
     let new_index_path = output_dir.join("index.arc.json");
@@ -228,10 +302,16 @@ pub fn update(
         old_index_dir.display().to_string().dimmed()
     ));
 
+    // carried-over entries keep their flags, but those were reported (and
+    // failed on) by the run that wrote them
+    check_changed(&delta_idx, fail_on_change, out)?;
+    if !new_idx.errors.is_empty() {
+        return Err(PartialSuccess(new_idx.errors.len()).into());
+    }
     Ok(())
 }
 
-fn relative_path(base: &Path, target: &Path) -> PathBuf {
+pub fn relative_path(base: &Path, target: &Path) -> PathBuf {
     let base_abs = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
     let target_abs = target
         .canonicalize()
@@ -35,6 +35,13 @@ pub fn now() -> u64 {
         .as_secs()
 }
 
+/// `SOURCE_DATE_EPOCH` from the environment, if set to a valid timestamp.
+pub fn source_date_epoch() -> Option<u64> {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+}
+
 pub fn print_banner(out: &crate::output::OutputCtx) {
     use colored::Colorize;
     out.println(
@@ -49,3 +56,27 @@ pub fn print_banner(out: &crate::output::OutputCtx) {
     );
     out.println("");
 }
+
+/// A scratch file or directory that is deleted when this is dropped, so it
+/// goes away whether the work using it finished or bailed out.
+pub struct TempPath(std::path::PathBuf);
+
+impl TempPath {
+    pub fn new(path: std::path::PathBuf) -> Self {
+        Self(path)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        } else {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
@@ -15,63 +15,874 @@
  * limitations under the License.
  */
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashMap;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
 use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
-use crate::checksum::hash_reader;
-use crate::index::ArchivumIndex;
+use crate::checksum::{Digests, HashAlgo, MultiHasher, blake3_reader, digest_reader, hash_file_as};
+use crate::index::{ArchivumIndex, IndexEntry, IndexHeader};
+use crate::merkle;
 use crate::output::OutputCtx;
-use crate::scan::EntryType;
+use crate::scan::{EntryType, kind_of, mtime_of};
+use crate::scrub;
+use crate::source::Sources;
+use crate::store::Store;
+use crate::tar_writer::is_manifest;
 
-pub fn verify(index_path: &Path, continue_on_error: bool, out: &OutputCtx) -> Result<()> {
-    let idx =
-        ArchivumIndex::read(index_path).map_err(|e| anyhow::anyhow!("Cannot read index: {}", e))?;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+/// One problem `verify` found. The JSON report lists them with the kind
+/// under `type`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Finding {
+    /// a header total disagrees with the entries
+    HeaderMismatch {
+        field: &'static str,
+        recorded: u64,
+        actual: u64,
+    },
+    /// the recorded Merkle root is not the entries' root
+    MerkleMismatch {
+        recorded: String,
+        actual: String,
+    },
+    /// an entry's tar_base has no slot in part_bases
+    UnknownBase {
+        path: String,
+        base: u32,
+    },
+    DedupTargetMissing {
+        path: String,
+        target: String,
+    },
+    /// the target exists but cannot stand in for the alias
+    DedupTargetInvalid {
+        path: String,
+        target: String,
+        reason: &'static str,
+    },
+    MissingPart {
+        part: String,
+    },
+    /// the part could not be read to the end
+    UnreadablePart {
+        part: String,
+        error: String,
+    },
+    /// a part file past total_parts that no entry points at
+    OrphanPart {
+        part: String,
+    },
+    /// an indexed entry its part does not contain
+    MissingMember {
+        path: String,
+        part: String,
+    },
+    /// a member of a part that no entry points at
+    UnindexedMember {
+        path: String,
+        part: String,
+    },
+    Corrupt {
+        path: String,
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// `--against`: the entry has no counterpart in the source
+    SourceMissing {
+        path: String,
+    },
+    SourceType {
+        path: String,
+        archived: &'static str,
+        source: &'static str,
+    },
+    SourceMetadata {
+        path: String,
+        field: &'static str,
+        archived: String,
+        source: String,
+    },
+    SourceContent {
+        path: String,
+        detail: String,
+    },
+}
+
+impl Finding {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::HeaderMismatch { .. } => "header_mismatch",
+            Self::MerkleMismatch { .. } => "merkle_mismatch",
+            Self::UnknownBase { .. } => "unknown_base",
+            Self::DedupTargetMissing { .. } => "dedup_target_missing",
+            Self::DedupTargetInvalid { .. } => "dedup_target_invalid",
+            Self::MissingPart { .. } => "missing_part",
+            Self::UnreadablePart { .. } => "unreadable_part",
+            Self::OrphanPart { .. } => "orphan_part",
+            Self::MissingMember { .. } => "missing_member",
+            Self::UnindexedMember { .. } => "unindexed_member",
+            Self::Corrupt { .. } => "corrupt",
+            Self::SourceMissing { .. } => "source_missing",
+            Self::SourceType { .. } => "source_type",
+            Self::SourceMetadata { .. } => "source_metadata",
+            Self::SourceContent { .. } => "source_content",
+        }
+    }
+
+    /// The entry path or part file the finding is about, as journaled.
+    fn subject(&self) -> &str {
+        match self {
+            Self::HeaderMismatch { .. } | Self::MerkleMismatch { .. } => "index.arc.json",
+            Self::MissingPart { part }
+            | Self::UnreadablePart { part, .. }
+            | Self::OrphanPart { part } => part,
+            Self::UnknownBase { path, .. }
+            | Self::DedupTargetMissing { path, .. }
+            | Self::DedupTargetInvalid { path, .. }
+            | Self::MissingMember { path, .. }
+            | Self::UnindexedMember { path, .. }
+            | Self::Corrupt { path, .. }
+            | Self::SourceMissing { path }
+            | Self::SourceType { path, .. }
+            | Self::SourceMetadata { path, .. }
+            | Self::SourceContent { path, .. } => path,
+        }
+    }
+
+    /// Whether the part holding the finding has lost data since it was
+    /// written, as opposed to the index or the source disagreeing.
+    fn damages_part(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingPart { .. }
+                | Self::UnreadablePart { .. }
+                | Self::MissingMember { .. }
+                | Self::Corrupt { .. }
+        )
+    }
+
+    /// Label and details, as printed.
+    fn describe(&self) -> (&'static str, String) {
+        match self {
+            Self::HeaderMismatch {
+                field,
+                recorded,
+                actual,
+            } => (
+                "HEADER",
+                format!("{} is {} but the entries give {}", field, recorded, actual),
+            ),
+            Self::MerkleMismatch { recorded, actual } => (
+                "MERKLE",
+                format!(
+                    "root is {}... but the entries give {}...",
+                    &recorded[..recorded.len().min(12)],
+                    &actual[..12]
+                ),
+            ),
+            Self::UnknownBase { path, base } => (
+                "BAD BASE",
+                format!("{} (tar_base {} is not in part_bases)", path, base),
+            ),
+            Self::DedupTargetMissing { path, target } => (
+                "BAD DEDUP",
+                format!("{} -> {} (target not in index)", path, target),
+            ),
+            Self::DedupTargetInvalid {
+                path,
+                target,
+                reason,
+            } => ("BAD DEDUP", format!("{} -> {} ({})", path, target, reason)),
+            Self::MissingPart { part } => ("MISSING", part.clone()),
+            Self::UnreadablePart { part, error } => ("UNREADABLE", format!("{}: {}", part, error)),
+            Self::OrphanPart { part } => ("ORPHAN", format!("{} (not in index)", part)),
+            Self::MissingMember { path, part } => {
+                ("MISSING", format!("{} (not found in {})", path, part))
+            }
+            Self::UnindexedMember { path, part } => {
+                ("UNINDEXED", format!("{} (in {}, not in index)", path, part))
+            }
+            Self::Corrupt {
+                path,
+                algorithm,
+                expected,
+                actual,
+            } => (
+                "CORRUPT",
+                format!(
+                    "{} ({} expected {}... got {}...)",
+                    path,
+                    algorithm,
+                    &expected[..12],
+                    &actual[..12]
+                ),
+            ),
+            Self::SourceMissing { path } => ("NOT IN SOURCE", path.clone()),
+            Self::SourceType {
+                path,
+                archived,
+                source,
+            } => (
+                "TYPE",
+                format!("{} ({} in archive, {} in source)", path, archived, source),
+            ),
+            Self::SourceMetadata {
+                path,
+                field,
+                archived,
+                source,
+            } => (
+                "METADATA",
+                format!(
+                    "{} ({} {} in archive, {} in source)",
+                    path, field, archived, source
+                ),
+            ),
+            Self::SourceContent { path, detail } => ("DIFFERS", format!("{} ({})", path, detail)),
+        }
+    }
+}
+
+/// `verify --against`: the source tree an archive is compared with.
+pub struct Against {
+    pub sources: Sources,
+    /// compare digests (the first algorithm verified) rather than bytes
+    pub by_hash: bool,
+}
+
+/// Compare an entry with its counterpart under `--against`: type, mode,
+/// mtime, symlink target and content. `member` is the entry's content as
+/// it streams out of its part; its digests under `algos` are returned so
+/// the caller need not read it twice. Dedup aliases have no member and are
+/// compared with the recorded digest.
+fn check_source(
+    against: &Against,
+    header: &IndexHeader,
+    entry: &IndexEntry,
+    member: Option<&mut dyn Read>,
+    algos: &[HashAlgo],
+) -> Result<(Option<Digests>, Vec<Finding>)> {
+    let path = entry.path.display().to_string();
+    let full = against.sources.resolve(&entry.path);
+    let mut findings = vec![];
+
+    let meta = match fs::symlink_metadata(&full) {
+        Ok(m) => m,
+        Err(e) => {
+            let digests = member
+                .map(|mut m| digest_reader(&mut m, algos))
+                .transpose()?;
+            let gone = matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::NotADirectory
+            );
+            findings.push(if gone {
+                Finding::SourceMissing { path }
+            } else {
+                Finding::SourceContent {
+                    path,
+                    detail: format!("unreadable: {}", e),
+                }
+            });
+            return Ok((digests, findings));
+        }
+    };
+    let kind = kind_of(&meta);
+    if kind.as_ref() != Some(&entry.entry_type) {
+        let digests = member
+            .map(|mut m| digest_reader(&mut m, algos))
+            .transpose()?;
+        findings.push(Finding::SourceType {
+            path,
+            archived: entry.entry_type.name(),
+            source: kind.map_or("socket", |k| k.name()),
+        });
+        return Ok((digests, findings));
+    }
+
+    #[cfg(unix)]
+    if let Some(mode) = entry.unix_mode.map(|m| m & 0o7777) {
+        let on_disk = meta.mode() & 0o7777;
+        if mode != on_disk {
+            findings.push(Finding::SourceMetadata {
+                path: path.clone(),
+                field: "mode",
+                archived: format!("{:o}", mode),
+                source: format!("{:o}", on_disk),
+            });
+        }
+    }
+    let mtime = header.clamp_mtime(mtime_of(&meta));
+    if entry.mtime.is_some() && entry.mtime != mtime {
+        let show = |t: Option<u64>| t.map_or("none".into(), |t| t.to_string());
+        findings.push(Finding::SourceMetadata {
+            path: path.clone(),
+            field: "mtime",
+            archived: show(entry.mtime),
+            source: show(mtime),
+        });
+    }
+    if entry.entry_type == EntryType::Symlink {
+        let target = fs::read_link(&full).ok();
+        if target != entry.symlink_target {
+            let show = |t: &Option<std::path::PathBuf>| {
+                t.as_ref()
+                    .map_or("none".into(), |t| t.display().to_string())
+            };
+            findings.push(Finding::SourceMetadata {
+                path: path.clone(),
+                field: "target",
+                archived: show(&entry.symlink_target),
+                source: show(&target),
+            });
+        }
+    }
+    if entry.entry_type != EntryType::File {
+        return Ok((None, findings));
+    }
+
+    let mut differs = |detail: String| {
+        findings.push(Finding::SourceContent {
+            path: path.clone(),
+            detail,
+        })
+    };
+    let digests = match member {
+        Some(m) if !against.by_hash => {
+            let (digests, diff) = compare_bytes(m, &full, algos)?;
+            if let Some(detail) = diff {
+                differs(detail);
+            }
+            Some(digests)
+        }
+        member => {
+            let digests = member
+                .map(|mut m| digest_reader(&mut m, algos))
+                .transpose()?;
+            let algo = algos[0];
+            let want = match &digests {
+                Some(d) => Some(d[0].1.as_str()),
+                None => entry.digest(algo),
+            };
+            match (want, hash_file_as(&full, algo)) {
+                (Some(want), Ok(got)) if got != want => differs(format!(
+                    "{} {}... in archive, {}... in source",
+                    algo.label(),
+                    &want[..12],
+                    &got[..12]
+                )),
+                (Some(_), Err(e)) => differs(format!("{:#}", e)),
+                _ => {}
+            }
+            digests
+        }
+    };
+    Ok((digests, findings))
+}
+
+/// Stream `member` against the file at `path`, returning the member's
+/// digests under `algos` and where the two first differ, if they do.
+fn compare_bytes(
+    member: &mut dyn Read,
+    path: &Path,
+    algos: &[HashAlgo],
+) -> Result<(Digests, Option<String>)> {
+    let mut file = File::open(path).map(BufReader::new);
+    let mut diff = file.as_ref().err().map(|e| format!("unreadable: {}", e));
+    let mut hasher = MultiHasher::new(algos);
+    let (mut buf, mut other) = (vec![0u8; 131072], vec![0u8; 131072]);
+    let mut pos = 0u64;
+    loop {
+        let n = member.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        if let (None, Ok(f)) = (&diff, &mut file) {
+            let m = read_full(f, &mut other[..n])?;
+            if let Some(i) = (0..m).find(|&i| buf[i] != other[i]) {
+                diff = Some(format!("first difference at byte {}", pos + i as u64));
+            } else if m < n {
+                diff = Some(format!("source ends at byte {}", pos + m as u64));
+            }
+        }
+        pos += n as u64;
+    }
+    if let (None, Ok(f)) = (&diff, &mut file) {
+        if f.read(&mut [0u8; 1])? > 0 {
+            diff = Some(format!("source is longer than the archived {} bytes", pos));
+        }
+    }
+    Ok((hasher.finish(), diff))
+}
+
+/// Fill `buf` unless the reader ends first; returns the bytes read.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..])? {
+            0 => break,
+            k => n += k,
+        }
+    }
+    Ok(n)
+}
+
+/// Findings so far; the first one ends the run unless --continue.
+struct Findings<'a> {
+    list: Vec<Finding>,
+    continue_on_error: bool,
+    out: &'a OutputCtx,
+}
+
+impl Findings<'_> {
+    fn push(&mut self, finding: Finding, pb: &ProgressBar) -> Result<()> {
+        let (label, detail) = finding.describe();
+        pb.suspend(|| {
+            self.out
+                .println(&format!("  {} {}", label.red().bold(), detail))
+        });
+        self.list.push(finding);
+        if !self.continue_on_error {
+            pb.finish_and_clear();
+            anyhow::bail!("Verification failed: {} {}", label, detail);
+        }
+        Ok(())
+    }
+}
+
+/// Checks that need only the index: header totals and the Merkle root
+/// against the entries, part bases, and that every dedup alias points at a
+/// stored file.
+fn check_structure(idx: &ArchivumIndex, findings: &mut Findings, pb: &ProgressBar) -> Result<()> {
+    let h = &idx.header;
+    let count = |f: fn(&IndexEntry) -> bool| idx.entries.iter().filter(|e| f(e)).count() as u64;
+    let files = count(|e| e.entry_type == EntryType::File);
+    let dirs = count(|e| e.entry_type == EntryType::Directory);
+    let symlinks = count(|e| e.entry_type == EntryType::Symlink);
+    let special = count(|e| e.entry_type.is_special());
+    let size: u64 = idx
+        .entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File)
+        .map(|e| e.size)
+        .sum();
+    // an entry in base 0 past total_parts; trailing parts may be empty
+    let parts = idx
+        .entries
+        .iter()
+        .filter(|e| e.tar_base.unwrap_or(0) == 0 && e.dedup_of.is_none())
+        .map(|e| e.last_part() + 1)
+        .max()
+        .unwrap_or(0)
+        .max(h.total_parts);
+
+    for (field, recorded, actual) in [
+        ("total_files", h.total_files, files),
+        ("total_dirs", h.total_dirs, dirs),
+        ("total_symlinks", h.total_symlinks, symlinks),
+        ("total_special", h.total_special, special),
+        ("total_size", h.total_size, size),
+        ("total_parts", h.total_parts as u64, parts as u64),
+    ] {
+        if recorded != actual {
+            findings.push(
+                Finding::HeaderMismatch {
+                    field,
+                    recorded,
+                    actual,
+                },
+                pb,
+            )?;
+        }
+    }
+
+    if let Some(recorded) = &h.merkle_root {
+        let actual = merkle::root(&idx.entries);
+        if *recorded != actual {
+            findings.push(
+                Finding::MerkleMismatch {
+                    recorded: recorded.clone(),
+                    actual,
+                },
+                pb,
+            )?;
+        }
+    }
+
+    let bases = h.part_bases.len().max(1) as u32;
+    for e in &idx.entries {
+        if let Some(base) = e.tar_base.filter(|&b| b >= bases) {
+            findings.push(
+                Finding::UnknownBase {
+                    path: e.path.display().to_string(),
+                    base,
+                },
+                pb,
+            )?;
+        }
+    }
+
+    let by_path: HashMap<&Path, &IndexEntry> =
+        idx.entries.iter().map(|e| (e.path.as_path(), e)).collect();
+    for e in &idx.entries {
+        let Some(target) = &e.dedup_of else {
+            continue;
+        };
+        let (path, target_str) = (e.path.display().to_string(), target.display().to_string());
+        let Some(t) = by_path.get(target.as_path()) else {
+            findings.push(
+                Finding::DedupTargetMissing {
+                    path,
+                    target: target_str,
+                },
+                pb,
+            )?;
+            continue;
+        };
+        let reason = if t.entry_type != EntryType::File {
+            Some("target is not a regular file")
+        } else if t.dedup_of.is_some() {
+            Some("target is itself an alias")
+        } else if t.size != e.size {
+            Some("sizes differ")
+        } else if matches!((&t.sha256, &e.sha256), (Some(a), Some(b)) if a != b) {
+            Some("checksums differ")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            findings.push(
+                Finding::DedupTargetInvalid {
+                    path,
+                    target: target_str,
+                    reason,
+                },
+                pb,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// What checking one part turned up.
+#[derive(Default)]
+struct PartResult {
+    present: bool,
+    ok: usize,
+    bad: usize,
+    missing: usize,
+    /// entries that match the source
+    same: usize,
+    findings: Vec<Finding>,
+    /// cut short by a problem found in an earlier part
+    stopped: bool,
+}
+
+/// Parts checked so far, waiting to be printed in order.
+struct Report<'a, 'o> {
+    next: usize,
+    done: BTreeMap<usize, (String, Result<PartResult>)>,
+    findings: &'a mut Findings<'o>,
+    totals: &'a mut PartResult,
+    /// parts with lost data, in part order
+    damaged: &'a mut Vec<String>,
+    error: Option<anyhow::Error>,
+}
+
+impl Report<'_, '_> {
+    /// Print every finished part that no unfinished one precedes. A part
+    /// that could not be checked fails the run when its turn comes, so the
+    /// error reported does not depend on which worker got there first.
+    fn flush(&mut self, pb: &ProgressBar) -> Result<()> {
+        while let Some((name, r)) = self.done.remove(&self.next) {
+            self.next += 1;
+            let r = r?;
+            if r.present {
+                let out = self.findings.out;
+                pb.suspend(|| out.println(&format!("  {}  {}", "OK".green(), name)));
+            }
+            self.totals.ok += r.ok;
+            self.totals.bad += r.bad;
+            self.totals.missing += r.missing;
+            self.totals.same += r.same;
+            if r.findings.iter().any(Finding::damages_part) {
+                self.damaged.push(name);
+            }
+            for f in r.findings {
+                self.findings.push(f, pb)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read one part through, matching each member against `entries` and
+/// re-hashing files under `algos`. Without --continue, a problem in part
+/// `i` lowers `stop` to `i`, and workers on later parts give up early;
+/// earlier ones carry on, so the problem reported is always the first.
+#[allow(clippy::too_many_arguments)]
+fn check_part(
+    store: &Store,
+    idx: &ArchivumIndex,
+    path: &Path,
+    name: &str,
+    base: u32,
+    entries: &[&IndexEntry],
+    i: usize,
+    pb: &ProgressBar,
+    stop: &AtomicUsize,
+    continue_on_error: bool,
+    against: Option<&Against>,
+    algos: &[HashAlgo],
+) -> Result<PartResult> {
+    let mut r = PartResult::default();
+    let found = |r: &mut PartResult, f: Finding| {
+        r.findings.push(f);
+        if !continue_on_error {
+            stop.fetch_min(i, Ordering::Relaxed);
+        }
+    };
+    let files = entries
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File)
+        .count();
+
+    if !store.exists(path)? {
+        r.missing = files;
+        pb.inc(entries.iter().map(|e| e.size).sum());
+        found(&mut r, Finding::MissingPart { part: name.into() });
+        return Ok(r);
+    }
+    r.present = true;
+
+    let expected: HashMap<&Path, &IndexEntry> =
+        entries.iter().map(|e| (e.path.as_path(), *e)).collect();
+    let mut seen: HashSet<&Path> = HashSet::new();
+
+    // parts inherited from an older archive by `update` still hold the
+    // members it superseded, so only base 0 must be fully indexed
+    let walked = (|| -> Result<()> {
+        let reader = store.open_part(path, &idx.header.compression)?;
+        let mut archive = tar::Archive::new(reader);
+        for item in archive.entries()? {
+            if stop.load(Ordering::Relaxed) < i {
+                r.stopped = true;
+                return Ok(());
+            }
+            let mut item = item?;
+            if item.header().entry_type().is_pax_global_extensions() || is_manifest(&mut item)? {
+                continue;
+            }
+            let item_path = item.path()?.into_owned();
+            let Some(&entry) = expected.get(item_path.as_path()) else {
+                if base == 0 {
+                    found(
+                        &mut r,
+                        Finding::UnindexedMember {
+                            path: item_path.display().to_string(),
+                            part: name.into(),
+                        },
+                    );
+                }
+                continue;
+            };
+            if !seen.insert(entry.path.as_path()) {
+                continue;
+            }
+            // streamed entries have no source file
+            let mut member_digests = None;
+            if let Some(a) = against.filter(|_| !entry.from_stdin) {
+                let member =
+                    (entry.entry_type == EntryType::File).then_some(&mut item as &mut dyn Read);
+                let (digests, diffs) = check_source(a, &idx.header, entry, member, algos)?;
+                member_digests = digests;
+                if diffs.is_empty() {
+                    r.same += 1;
+                }
+                for f in diffs {
+                    found(&mut r, f);
+                }
+            }
+            // every selected digest the entry has, from one read
+            let wanted: Vec<(HashAlgo, &str)> = algos
+                .iter()
+                .filter_map(|a| entry.digest(*a).map(|d| (*a, d)))
+                .filter(|_| entry.entry_type == EntryType::File)
+                .collect();
+            if !wanted.is_empty() {
+                let digests = match member_digests {
+                    Some(d) => d,
+                    None => digest_reader(&mut item, algos)?,
+                };
+                let mut intact = true;
+                for (algo, want) in wanted {
+                    let actual = digests
+                        .iter()
+                        .find(|(a, _)| *a == algo)
+                        .map(|(_, d)| d.clone())
+                        .unwrap_or_default();
+                    if actual != want {
+                        intact = false;
+                        found(
+                            &mut r,
+                            Finding::Corrupt {
+                                path: item_path.display().to_string(),
+                                algorithm: algo.name(),
+                                expected: want.to_string(),
+                                actual,
+                            },
+                        );
+                    }
+                }
+                if intact {
+                    r.ok += 1;
+                } else {
+                    r.bad += 1;
+                }
+            }
+            pb.inc(entry.size);
+        }
+        Ok(())
+    })();
+
+    if r.stopped || (!continue_on_error && !r.findings.is_empty()) {
+        return Ok(r);
+    }
+
+    let unseen: Vec<&&IndexEntry> = entries
+        .iter()
+        .filter(|e| !seen.contains(e.path.as_path()))
+        .collect();
+    r.missing += unseen
+        .iter()
+        .filter(|e| e.entry_type == EntryType::File)
+        .count();
+    pb.inc(unseen.iter().map(|e| e.size).sum());
+
+    match walked {
+        Err(e) => found(
+            &mut r,
+            Finding::UnreadablePart {
+                part: name.into(),
+                error: format!("{:#}", e),
+            },
+        ),
+        Ok(()) => {
+            for e in unseen {
+                found(
+                    &mut r,
+                    Finding::MissingMember {
+                        path: e.path.display().to_string(),
+                        part: name.into(),
+                    },
+                );
+            }
+        }
+    }
+    Ok(r)
+}
+
+/// A part file as reports name it: relative to the index where it can be.
+fn part_name(idx: &ArchivumIndex, index_dir: &Path, base: u32, part: u32) -> String {
+    let path = idx.header.part_file(index_dir, base, part);
+    path.strip_prefix(index_dir)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| path.display().to_string())
+}
+
+/// Full verify: the structural checks above, then every part is read
+/// through and each member matched against the entries that point there.
+/// Files are re-hashed under `algos` (SHA-256 unless asked otherwise) and
+/// compared with the digests recorded for them. With `against`, every
+/// entry is also compared with the source tree. With `journal`, the run
+/// is appended to the scrub journal, whether or not it passes.
+#[allow(clippy::too_many_arguments)]
+pub fn verify(
+    store: &Store,
+    index_path: &Path,
+    continue_on_error: bool,
+    threads: usize,
+    against: Option<&Against>,
+    algos: &[HashAlgo],
+    journal: bool,
+    out: &OutputCtx,
+) -> Result<()> {
+    let idx = store
+        .read_index(index_path)
+        .map_err(|e| anyhow::anyhow!("Cannot read index: {}", e))?;
     let index_dir = index_path.parent().unwrap_or(Path::new("."));
+    for algo in algos {
+        idx.require_hash(*algo)?;
+    }
 
     out.println(&format!(
         "{} {}",
         "Verifying archive:".cyan().bold(),
-        index_path.display().to_string().yellow()
+        store.location(index_path).yellow()
     ));
-    out.println("");
-
-    // check all tar parts exist
-    let ext = idx.header.compression.extension();
-    let mut all_parts_ok = true;
-    for part in 0..idx.header.total_parts {
-        let path = index_dir.join(format!("data.part{:03}{}", part, ext));
-        if !path.exists() {
-            let msg = format!("  {} {}", "MISSING".red().bold(), path.display());
-            out.println(&msg);
-            all_parts_ok = false;
-            if !continue_on_error {
-                anyhow::bail!("Missing tar part: {}", path.display());
+    if let Some(a) = against {
+        out.println(&format!(
+            "  {} {}{}",
+            "against source".dimmed(),
+            a.sources.describe().yellow(),
+            if a.by_hash {
+                format!(" (by {})", algos[0].label())
+            } else {
+                String::new()
             }
-        } else {
-            out.println(&format!(
-                "  {}  {}",
-                "OK".green(),
-                path.file_name().unwrap().to_string_lossy()
-            ));
-        }
+            .dimmed()
+        ));
     }
+    if algos != [HashAlgo::Sha256] {
+        let names: Vec<&str> = algos.iter().map(HashAlgo::name).collect();
+        out.println(&format!(
+            "  {} {}",
+            "checking digests".dimmed(),
+            names.join(", ").yellow()
+        ));
+    }
+    out.println("");
 
-    let files_with_checksums: Vec<_> = idx
+    // entries grouped by the part file holding them; dedup aliases are
+    // not stored, and every part up to total_parts is expected to exist.
+    // A stream spanning parts is checked piece by piece.
+    let bases = idx.header.part_bases.len().max(1) as u32;
+    let mut by_part: BTreeMap<(u32, u32), Vec<&IndexEntry>> = (0..idx.header.total_parts)
+        .map(|p| ((0, p), vec![]))
+        .collect();
+    let pieces: Vec<IndexEntry> = idx
         .entries
         .iter()
-        .filter(|e| e.entry_type == EntryType::File && e.sha256.is_some() && e.dedup_of.is_none())
+        .filter(|e| !e.pieces.is_empty())
+        .flat_map(IndexEntry::members)
         .collect();
-
-    if files_with_checksums.is_empty() {
-        out.println("");
-        out.println("  No checksums stored - archive was created without checksum support.");
-        return Ok(());
+    for e in idx
+        .entries
+        .iter()
+        .filter(|e| e.dedup_of.is_none() && e.pieces.is_empty())
+        .chain(&pieces)
+    {
+        let base = e.tar_base.unwrap_or(0);
+        if base < bases {
+            by_part.entry((base, e.tar_part)).or_default().push(e);
+        }
     }
 
-    let total_bytes: u64 = files_with_checksums.iter().map(|e| e.size).sum();
+    let total_bytes: u64 = by_part.values().flatten().map(|e| e.size).sum();
     let pb = ProgressBar::new(total_bytes);
     pb.set_style(
         ProgressStyle::with_template(
@@ -81,80 +892,350 @@ pub fn verify(index_path: &Path, continue_on_error: bool, out: &OutputCtx) -> Re
         .progress_chars("=> "),
     );
 
-    let mut by_part: HashMap<u32, Vec<&crate::index::IndexEntry>> = HashMap::new();
-    for e in &files_with_checksums {
-        by_part.entry(e.tar_part).or_default().push(e);
-    }
+    let mut findings = Findings {
+        list: vec![],
+        continue_on_error,
+        out,
+    };
+    let mut totals = PartResult::default();
+    let mut damaged = vec![];
+    let started = Instant::now();
 
-    let mut ok = 0usize;
-    let mut bad = 0usize;
-    let mut missing = 0usize;
+    let checked = (|| -> Result<()> {
+        check_structure(&idx, &mut findings, &pb)?;
 
-    let mut sorted_parts: Vec<u32> = by_part.keys().cloned().collect();
-    sorted_parts.sort_unstable();
+        let work: Vec<((u32, u32), &Vec<&IndexEntry>)> =
+            by_part.iter().map(|(&k, v)| (k, v)).collect();
 
-    for part in sorted_parts {
-        let entries = &by_part[&part];
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to build thread pool: {}", e))?;
 
-        let part_path = {
-            let rep = entries[0];
-            rep.part_path(index_dir, &idx.header)
-        };
+        // parts finish in any order but are reported in part order, each as
+        // soon as every part before it has been
+        let stop = AtomicUsize::new(usize::MAX);
+        let report = Mutex::new(Report {
+            next: 0,
+            done: BTreeMap::new(),
+            findings: &mut findings,
+            totals: &mut totals,
+            damaged: &mut damaged,
+            error: None,
+        });
+        pool.install(|| {
+            work.par_iter()
+                .enumerate()
+                .for_each(|(i, &((base, part), entries))| {
+                    if stop.load(Ordering::Relaxed) < i {
+                        return;
+                    }
+                    let path = idx.header.part_file(index_dir, base, part);
+                    let name = part_name(&idx, index_dir, base, part);
+                    let result = check_part(
+                        store,
+                        &idx,
+                        &path,
+                        &name,
+                        base,
+                        entries,
+                        i,
+                        &pb,
+                        &stop,
+                        continue_on_error,
+                        against,
+                        algos,
+                    );
+                    if result.is_err() {
+                        stop.fetch_min(i, Ordering::Relaxed);
+                    }
+                    // once the run has failed, nothing after the failure is
+                    // printed or counted, however far other workers got
+                    let mut report = report.lock().unwrap();
+                    if report.error.is_some() {
+                        return;
+                    }
+                    report.done.insert(i, (name, result));
+                    if let Err(e) = report.flush(&pb) {
+                        report.error = Some(e);
+                        stop.store(0, Ordering::Relaxed);
+                    }
+                });
+        });
+        if let Some(e) = report.into_inner().unwrap().error {
+            pb.finish_and_clear();
+            return Err(e);
+        }
 
-        if !part_path.exists() {
-            missing += entries.len();
-            pb.inc(entries.iter().map(|e| e.size).sum());
-            continue;
+        // dedup aliases are in no part; their source files are compared with
+        // the recorded digest
+        if let Some(a) = against {
+            for e in idx
+                .entries
+                .iter()
+                .filter(|e| e.dedup_of.is_some() && !e.from_stdin)
+            {
+                let (_, diffs) = check_source(a, &idx.header, e, None, algos)?;
+                if diffs.is_empty() {
+                    totals.same += 1;
+                }
+                for f in diffs {
+                    findings.push(f, &pb)?;
+                }
+            }
         }
 
-        let mut want: HashMap<std::path::PathBuf, &str> = HashMap::new();
-        for e in entries {
-            want.insert(e.path.clone(), e.sha256.as_deref().unwrap());
+        // leftovers of an earlier, larger archive in the same directory
+        let mut next = by_part
+            .keys()
+            .filter(|(b, _)| *b == 0)
+            .map(|(_, p)| p + 1)
+            .max()
+            .unwrap_or(0);
+        while store.exists(&idx.header.part_file(index_dir, 0, next))? {
+            findings.push(
+                Finding::OrphanPart {
+                    part: part_name(&idx, index_dir, 0, next),
+                },
+                &pb,
+            )?;
+            next += 1;
         }
+        Ok(())
+    })();
 
-        let reader = idx.header.compression.wrap_reader(&part_path)?;
-        let mut archive = tar::Archive::new(reader);
+    let PartResult {
+        ok,
+        bad,
+        missing,
+        same,
+        ..
+    } = totals;
+    // a run cut short is journaled too: it is when damage shows up
+    let record = |complete: bool| -> Result<()> {
+        if !journal {
+            return Ok(());
+        }
+        let failing = findings.list.iter().map(Finding::subject);
+        let run = scrub::Run::new(
+            "full",
+            started,
+            complete,
+            (ok, bad, missing),
+            damaged,
+            failing,
+        );
+        scrub::append(store, index_path, &run, out)
+    };
+    if let Err(e) = checked {
+        record(false)?;
+        return Err(e);
+    }
 
-        for item in archive.entries()? {
-            let mut item = item?;
-            let item_path = item.path()?.into_owned();
+    pb.finish_with_message("verification done");
 
-            if let Some(&expected) = want.get(&item_path) {
-                let actual = hash_reader(&mut item)?;
-                let entry = entries.iter().find(|e| e.path == item_path).unwrap();
+    let failed = !findings.list.is_empty();
+    if out.json {
+        let mut result = serde_json::json!({
+            "status": if failed { "FAIL" } else { "PASS" },
+            "ok": ok,
+            "corrupt": bad,
+            "missing": missing,
+            "algorithms": algos.iter().map(HashAlgo::name).collect::<Vec<_>>(),
+            "all_parts_present": !findings.list.iter().any(|f| matches!(f, Finding::MissingPart { .. })),
+            "findings": findings.list,
+        });
+        if against.is_some() {
+            result["source_matched"] = same.into();
+        }
+        out.raw(&serde_json::to_string_pretty(&result).unwrap());
+        out.raw("\n");
+    } else {
+        if !idx.entries.iter().any(|e| e.sha256.is_some()) {
+            out.println("");
+            out.println("  No checksums stored - archive was created without checksum support.");
+        }
+        out.println("");
+        out.println(&"-".repeat(50).dimmed().to_string());
+        let status_str = if failed {
+            "FAIL".red().bold().to_string()
+        } else {
+            "PASS".green().bold().to_string()
+        };
+        out.println(&format!(
+            "  {}  OK: {}  CORRUPT: {}  MISSING: {}",
+            status_str,
+            ok.to_string().green(),
+            if bad > 0 {
+                bad.to_string().red().to_string()
+            } else {
+                bad.to_string().green().to_string()
+            },
+            if missing > 0 {
+                missing.to_string().red().to_string()
+            } else {
+                missing.to_string().green().to_string()
+            }
+        ));
+        if against.is_some() {
+            out.println(&format!(
+                "  SOURCE: {} entries match",
+                same.to_string().green()
+            ));
+        }
+        if failed {
+            let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+            for f in &findings.list {
+                *kinds.entry(f.kind()).or_default() += 1;
+            }
+            let kinds: Vec<String> = kinds.iter().map(|(k, n)| format!("{} {}", n, k)).collect();
+            out.println(&format!("  Findings: {}", kinds.join(", ")));
+        }
+        out.println(&"-".repeat(50).dimmed().to_string());
+    }
+    record(true)?;
 
-                if actual == expected {
-                    ok += 1;
-                } else {
+    if failed && !continue_on_error {
+        anyhow::bail!("{} problem(s) found", findings.list.len());
+    }
+
+    Ok(())
+}
+
+/// `verify --quick`: check each part file against the size and BLAKE3
+/// recorded when it was written. Nothing is decompressed, so this finds
+/// damaged or missing parts at disk speed but cannot tell which files in a
+/// damaged part are affected; a full `verify` does that.
+pub fn verify_quick(
+    store: &Store,
+    index_path: &Path,
+    continue_on_error: bool,
+    journal: bool,
+    out: &OutputCtx,
+) -> Result<()> {
+    let idx = store
+        .read_index(index_path)
+        .map_err(|e| anyhow::anyhow!("Cannot read index: {}", e))?;
+    let index_dir = index_path.parent().unwrap_or(Path::new("."));
+    let hashes = &idx.header.part_hashes;
+
+    out.println(&format!(
+        "{} {}",
+        "Quick-verifying archive:".cyan().bold(),
+        store.location(index_path).yellow()
+    ));
+    out.println("");
+
+    if hashes.is_empty() {
+        anyhow::bail!(
+            "{} records no part checksums (written before they were added); run a full verify",
+            store.location(index_path)
+        );
+    }
+
+    let pb = ProgressBar::new(hashes.iter().map(|h| h.size).sum());
+    pb.set_style(
+        ProgressStyle::with_template(
+            "  {spinner:.cyan} Hashing    [{bar:40.cyan/blue}] {bytes}/{total_bytes}  ETA {eta}",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+
+    let (mut ok, mut bad, mut missing) = (0usize, 0usize, 0usize);
+    let mut damaged = vec![];
+    let started = Instant::now();
+    let checked = (|| -> Result<()> {
+        for h in hashes {
+            let path = idx.header.part_file(index_dir, h.base.unwrap_or(0), h.part);
+            let name = store.location(&path);
+            let problem = match store.size(&path)? {
+                None => {
+                    missing += 1;
+                    Some(format!("  {} {}", "MISSING".red().bold(), name))
+                }
+                Some(size) if size != h.size => {
                     bad += 1;
-                    pb.suspend(|| {
-                        eprintln!(
+                    Some(format!(
+                        "  {} {} (size {}, expected {})",
+                        "CORRUPT".red().bold(),
+                        name,
+                        size,
+                        h.size
+                    ))
+                }
+                Some(_) => {
+                    let mut reader = pb.wrap_read(store.reader(&path, 0, None)?);
+                    let (_, actual) = blake3_reader(&mut reader)?;
+                    if actual == h.blake3 {
+                        ok += 1;
+                        pb.suspend(|| out.println(&format!("  {}  {}", "OK".green(), name)));
+                        None
+                    } else {
+                        bad += 1;
+                        Some(format!(
                             "  {} {} (expected {}... got {}...)",
                             "CORRUPT".red().bold(),
-                            item_path.display(),
-                            &expected[..12],
+                            name,
+                            &h.blake3[..12],
                             &actual[..12]
-                        );
-                    });
-                    if !continue_on_error {
-                        pb.finish_and_clear();
-                        anyhow::bail!("Checksum mismatch for {}", item_path.display());
+                        ))
                     }
                 }
-                pb.inc(entry.size);
+            };
+            if let Some(line) = problem {
+                damaged.push(part_name(&idx, index_dir, h.base.unwrap_or(0), h.part));
+                pb.suspend(|| out.println(&line));
+                if !continue_on_error {
+                    pb.finish_and_clear();
+                    anyhow::bail!("Part check failed for {}", name);
+                }
             }
         }
+        Ok(())
+    })();
+    pb.finish_and_clear();
+
+    let record = |complete: bool| -> Result<()> {
+        if !journal {
+            return Ok(());
+        }
+        let failing = damaged.iter().map(String::as_str);
+        let run = scrub::Run::new(
+            "quick",
+            started,
+            complete,
+            (ok, bad, missing),
+            damaged.clone(),
+            failing,
+        );
+        scrub::append(store, index_path, &run, out)
+    };
+    if let Err(e) = checked {
+        record(false)?;
+        return Err(e);
     }
 
-    pb.finish_with_message("verification done");
+    // parts added by tools that predate part checksums
+    let recorded: HashSet<(u32, u32)> = hashes
+        .iter()
+        .map(|h| (h.base.unwrap_or(0), h.part))
+        .collect();
+    let unchecked: HashSet<(u32, u32)> = idx
+        .entries
+        .iter()
+        .flat_map(|e| (e.tar_part..=e.last_part()).map(|p| (e.tar_base.unwrap_or(0), p)))
+        .filter(|p| !recorded.contains(p))
+        .collect();
 
     if out.json {
         let result = serde_json::json!({
             "status": if bad + missing == 0 { "PASS" } else { "FAIL" },
-            "ok": ok,
-            "corrupt": bad,
-            "missing": missing,
-            "all_parts_present": all_parts_ok
+            "mode": "quick",
+            "parts_ok": ok,
+            "parts_corrupt": bad,
+            "parts_missing": missing,
+            "parts_unchecked": unchecked.len(),
         });
         out.raw(&serde_json::to_string_pretty(&result).unwrap());
         out.raw("\n");
@@ -167,7 +1248,7 @@ pub fn verify(index_path: &Path, continue_on_error: bool, out: &OutputCtx) -> Re
             "FAIL".red().bold().to_string()
         };
         out.println(&format!(
-            "  {}  OK: {}  CORRUPT: {}  MISSING: {}",
+            "  {}  PARTS OK: {}  CORRUPT: {}  MISSING: {}",
             status_str,
             ok.to_string().green(),
             if bad > 0 {
@@ -181,12 +1262,171 @@ pub fn verify(index_path: &Path, continue_on_error: bool, out: &OutputCtx) -> Re
                 missing.to_string().green().to_string()
             }
         ));
+        if !unchecked.is_empty() {
+            out.println(&format!(
+                "  {} {} part(s) have no recorded checksum",
+                "Note:".yellow(),
+                unchecked.len()
+            ));
+        }
         out.println(&"-".repeat(50).dimmed().to_string());
     }
+    record(true)?;
+
+    Ok(())
+}
 
-    if bad + missing > 0 && !continue_on_error {
-        anyhow::bail!("{} file(s) failed verification", bad + missing);
+/// `verify --prove PATH`: show that `path` is part of the archive's
+/// content. The entry's Merkle proof must lead to the root recorded in the
+/// header, and a file's data, read back from its part (a stream's from all
+/// of its parts), must have the SHA-256 its leaf commits to. With --json the
+/// proof is printed for anyone holding the root to check.
+pub fn prove(store: &Store, index_path: &Path, path: &Path, out: &OutputCtx) -> Result<()> {
+    let idx = store
+        .read_index(index_path)
+        .map_err(|e| anyhow::anyhow!("Cannot read index: {}", e))?;
+    let index_dir = index_path.parent().unwrap_or(Path::new("."));
+    let Some(root) = &idx.header.merkle_root else {
+        anyhow::bail!(
+            "{} records no Merkle root (written before roots were added)",
+            store.location(index_path)
+        );
+    };
+    let entry = idx
+        .entries
+        .iter()
+        .find(|e| e.path == path)
+        .with_context(|| format!("Not in the archive: {}", path.display()))?;
+    let proof = merkle::prove(&idx.entries, path).expect("entry is in the index");
+
+    let mut problems = vec![];
+    if proof.root != *root {
+        problems.push(format!(
+            "the entries give root {}..., the header records {}...",
+            &proof.root[..12],
+            &root[..root.len().min(12)]
+        ));
+    } else if !proof.check(&merkle::record(entry)) {
+        problems.push("the proof does not lead to the root".to_string());
+    }
+
+    // the content the leaf commits to, as stored
+    let content = match (&entry.entry_type, &entry.sha256) {
+        (EntryType::File, Some(want)) => {
+            let origin = match &entry.dedup_of {
+                Some(o) => idx
+                    .entries
+                    .iter()
+                    .find(|e| &e.path == o)
+                    .with_context(|| format!("Dedup origin not found: {}", o.display()))?,
+                None => entry,
+            };
+            // a stream is hashed across its pieces, in part order
+            let mut hasher = MultiHasher::new(&[HashAlgo::Sha256]);
+            let mut buf = vec![0u8; 1 << 16];
+            let mut missing = None;
+            for piece in origin.members() {
+                let part = piece.part_path(index_dir, &idx.header);
+                let mut archive =
+                    tar::Archive::new(store.open_part(&part, &idx.header.compression)?);
+                let mut found = false;
+                for item in archive.entries()? {
+                    let mut item = item?;
+                    if is_manifest(&mut item)? || item.path()? != piece.path {
+                        continue;
+                    }
+                    loop {
+                        let n = item.read(&mut buf)?;
+                        if n == 0 {
+                            break;
+                        }
+                        hasher.update(&buf[..n]);
+                    }
+                    found = true;
+                    break;
+                }
+                if !found {
+                    missing = Some(part);
+                    break;
+                }
+            }
+            let actual = match missing {
+                Some(part) => Err(part),
+                None => Ok(hasher.finish().remove(0).1),
+            };
+            match actual {
+                Ok(a) if a == *want => Some(true),
+                Ok(a) => {
+                    problems.push(format!(
+                        "stored data hashes to {}..., not {}...",
+                        &a[..12],
+                        &want[..12]
+                    ));
+                    Some(false)
+                }
+                Err(part) => {
+                    problems.push(format!("not found in {}", store.location(&part)));
+                    Some(false)
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let proven = problems.is_empty();
+    if out.json {
+        let result = serde_json::json!({
+            "status": if proven { "PASS" } else { "FAIL" },
+            "content_checked": content.is_some(),
+            "problems": problems,
+            "proof": proof,
+        });
+        out.raw(&serde_json::to_string_pretty(&result).unwrap());
+        out.raw("\n");
+    } else {
+        out.println(&format!(
+            "{} {}",
+            "Proving:".cyan().bold(),
+            path.display().to_string().yellow()
+        ));
+        out.println("");
+        out.println(&format!(
+            "  Leaf      : {} ({} of {})",
+            proof.leaf.dimmed(),
+            proof.index + 1,
+            proof.leaves
+        ));
+        for step in &proof.steps {
+            out.println(&format!("  {:<10}: {}", step.side, step.hash.dimmed()));
+        }
+        out.println(&format!("  Root      : {}", proof.root.dimmed()));
+        match content {
+            Some(true) => out.println(&format!(
+                "  Content   : {}",
+                "stored data matches its SHA-256".green()
+            )),
+            Some(false) => {}
+            None => out.println(&format!(
+                "  Content   : {}",
+                format!("none to check ({})", entry.entry_type.name()).dimmed()
+            )),
+        }
+        out.println("");
+        for p in &problems {
+            out.println(&format!("  {} {}", "FAIL".red().bold(), p));
+        }
+        if proven {
+            out.println(&format!(
+                "  {}  {} is in the archive with root {}",
+                "PROVEN".green().bold(),
+                path.display(),
+                root
+            ));
+        }
     }
 
+    if !proven {
+        anyhow::bail!("Inclusion of {} could not be proven", path.display());
+    }
     Ok(())
 }
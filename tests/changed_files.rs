@@ -0,0 +1,105 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use common::{Sandbox, entry, stderr};
+
+/// Keep appending to `path` until the returned flag is raised.
+fn keep_growing(path: std::path::PathBuf) -> (Arc<AtomicBool>, thread::JoinHandle<()>) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    let handle = thread::spawn(move || {
+        let mut f = OpenOptions::new().append(true).open(path).unwrap();
+        while !flag.load(Ordering::Relaxed) {
+            f.write_all(&[b'x'; 4096]).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    thread::sleep(Duration::from_millis(20));
+    (stop, handle)
+}
+
+#[test]
+fn growing_file_is_flagged_and_fails_on_request() {
+    let sb = Sandbox::new();
+    sb.write("src/steady.txt", "steady");
+    let log = sb.write("src/app.log", vec![b'a'; 1 << 20]);
+    let (stop, writer) = keep_growing(log);
+
+    let flagged = sb.exec(&["create", "src", "out", "--compress", "none"]);
+    let failed = sb.exec(&[
+        "create",
+        "src",
+        "out2",
+        "--compress",
+        "none",
+        "--fail-on-change",
+    ]);
+    stop.store(true, Ordering::Relaxed);
+    writer.join().unwrap();
+
+    assert!(flagged.status.success(), "{}", stderr(&flagged));
+    assert!(stderr(&flagged).contains("changed while being archived"));
+    let idx = sb.index("out/index.arc.json");
+    assert_eq!(entry(&idx, "app.log")["changed_during_backup"], true);
+    assert_eq!(entry(&idx, "steady.txt")["changed_during_backup"], false);
+    // what went into the tar is what the index describes
+    sb.run(&["verify", "out/index.arc.json"]);
+
+    assert!(!failed.status.success());
+    assert!(stderr(&failed).contains("changed during backup"));
+}
+
+#[test]
+fn update_does_not_report_flags_it_carried_over() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/b.txt", "beta");
+    sb.run(&["create", "src", "v1", "--compress", "none"]);
+
+    // as if a.txt had moved while v1 was written
+    let mut idx = sb.index("v1/index.arc.json");
+    for e in idx["entries"].as_array_mut().unwrap() {
+        if e["path"] == "a.txt" {
+            e["changed_during_backup"] = true.into();
+        }
+    }
+    sb.write_index("v1/index.arc.json", &idx);
+
+    sb.write("src/c.txt", "gamma");
+    let out = sb.run(&[
+        "update",
+        "v1/index.arc.json",
+        "src",
+        "v2",
+        "--compress",
+        "none",
+        "--fail-on-change",
+    ]);
+    assert!(!stderr(&out).contains("changed while being archived"));
+    let v2 = sb.index("v2/index.arc.json");
+    assert_eq!(entry(&v2, "a.txt")["changed_during_backup"], true);
+    assert_eq!(entry(&v2, "c.txt")["changed_during_backup"], false);
+}
@@ -0,0 +1,226 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Helpers for driving the `archivum` binary from integration tests.
+//!
+//! Every [`Sandbox`] gets its own temp directory and its own
+//! `XDG_CONFIG_HOME`, so a developer's real config never leaks into a run.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+/// Every required section and key except `[create]`.
+const BASE_CONFIG: &str = r#"[defaults]
+compress = "zstd"
+zstd_level = 3
+split_gb = 4.0
+split_files = 0
+threads = 4
+color = false
+
+[output]
+json = false
+quiet = false
+
+[restore]
+force = false
+restore_permissions = false
+
+[update]
+checksum_diff = false
+
+[prune]
+keep_last = 5
+max_age_days = 0
+
+"#;
+
+pub struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().expect("tempdir");
+        fs::create_dir_all(dir.path().join("xdg")).unwrap();
+        Self { dir }
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn path(&self, rel: &str) -> PathBuf {
+        self.dir.path().join(rel)
+    }
+
+    /// Write `content` to `rel`, creating parent directories.
+    pub fn write(&self, rel: &str, content: impl AsRef<[u8]>) -> PathBuf {
+        let p = self.path(rel);
+        if let Some(parent) = p.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(&p, content).unwrap();
+        p
+    }
+
+    pub fn read(&self, rel: &str) -> Vec<u8> {
+        fs::read(self.path(rel)).unwrap_or_else(|e| panic!("read {rel}: {e}"))
+    }
+
+    /// Write a complete config file: `create` is the body of its `[create]`
+    /// section and `rest` any further sections.
+    pub fn config(&self, create: &str, rest: &str) {
+        let exclude = if create.lines().any(|l| l.starts_with("exclude =")) {
+            ""
+        } else {
+            "exclude = []\n"
+        };
+        self.write(
+            "xdg/archivum/config.toml",
+            format!(
+                "{BASE_CONFIG}[create]\ndedup = false\nnotes = \"\"\n{exclude}{create}\n{rest}"
+            ),
+        );
+    }
+
+    /// The binary with cwd at the sandbox root and an isolated config dir.
+    pub fn cmd(&self) -> Command {
+        let mut c = Command::new(env!("CARGO_BIN_EXE_archivum"));
+        c.current_dir(self.root())
+            .env("XDG_CONFIG_HOME", self.path("xdg"))
+            .env("NO_COLOR", "1")
+            .stdin(Stdio::null());
+        c
+    }
+
+    /// Run with `args`; the exit status is not checked.
+    pub fn exec(&self, args: &[&str]) -> Output {
+        self.cmd().args(args).output().expect("spawn archivum")
+    }
+
+    /// Run with `args` and panic with the captured output unless it succeeds.
+    pub fn run(&self, args: &[&str]) -> Output {
+        let out = self.exec(args);
+        if !out.status.success() {
+            panic!("archivum {args:?} failed\n{}", describe(&out));
+        }
+        out
+    }
+
+    /// Run with `args` and panic unless it fails.
+    pub fn fail(&self, args: &[&str]) -> Output {
+        let out = self.exec(args);
+        if out.status.success() {
+            panic!(
+                "archivum {args:?} unexpectedly succeeded\n{}",
+                describe(&out)
+            );
+        }
+        out
+    }
+
+    /// Run with `--json` and parse stdout.
+    pub fn json(&self, args: &[&str]) -> Value {
+        let mut full = vec!["--json"];
+        full.extend_from_slice(args);
+        let out = self.exec(&full);
+        serde_json::from_slice(&out.stdout)
+            .unwrap_or_else(|e| panic!("archivum {full:?}: bad JSON ({e})\n{}", describe(&out)))
+    }
+
+    /// Parse an index file (`rel` is relative to the sandbox).
+    pub fn index(&self, rel: &str) -> Value {
+        serde_json::from_slice(&self.read(rel)).expect("index JSON")
+    }
+}
+
+impl Sandbox {
+    /// Overwrite the index at `rel` with `index`, resealing it so the edit
+    /// passes the integrity check.
+    pub fn write_index(&self, rel: &str, index: &Value) {
+        let json = serde_json::to_vec_pretty(index).unwrap();
+        self.write(rel, &json);
+        self.write(&format!("{rel}.b3"), blake3::hash(&json).to_hex().as_str());
+    }
+}
+
+pub fn stdout(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stdout).into_owned()
+}
+
+pub fn stderr(out: &Output) -> String {
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+fn describe(out: &Output) -> String {
+    format!(
+        "status: {}\n--- stdout\n{}--- stderr\n{}",
+        out.status,
+        stdout(out),
+        stderr(out)
+    )
+}
+
+/// Index entry whose `path` equals `path`.
+pub fn entry<'a>(index: &'a Value, path: &str) -> &'a Value {
+    index["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["path"] == path)
+        .unwrap_or_else(|| panic!("no index entry for {path}"))
+}
+
+/// Paths of all index entries, in index order.
+pub fn paths(index: &Value) -> Vec<String> {
+    index["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| match &e["path"] {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+/// Paths of all entries of the index at `index` (sandbox-relative), sorted.
+pub fn sorted_paths(sb: &Sandbox, index: &str) -> Vec<String> {
+    let mut p = paths(&sb.index(index));
+    p.sort();
+    p
+}
+
+/// `(path, kind)` of every member of an uncompressed tar part.
+pub fn tar_members(part: &Path) -> Vec<(String, tar::EntryType)> {
+    let mut ar = tar::Archive::new(fs::File::open(part).unwrap());
+    ar.entries()
+        .unwrap()
+        .map(|e| {
+            let e = e.unwrap();
+            let path = e.path().unwrap().to_string_lossy().into_owned();
+            (path, e.header().entry_type())
+        })
+        .collect()
+}
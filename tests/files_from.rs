@@ -0,0 +1,196 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+use common::{Sandbox, sorted_paths, stderr};
+
+/// Every path below `dir`, absolute, in no particular order.
+fn all_below(dir: &Path, out: &mut Vec<String>) {
+    for e in fs::read_dir(dir).unwrap() {
+        let p = e.unwrap().path();
+        out.push(p.to_str().unwrap().to_string());
+        if p.is_dir() {
+            all_below(&p, out);
+        }
+    }
+}
+
+#[test]
+fn relative_lines_follow_the_working_directory() {
+    let sb = Sandbox::new();
+    sb.config("", "");
+    sb.write("src/a.txt", "a");
+    sb.write("src/sub/b.txt", "b");
+    sb.write("src/sub/c.txt", "c");
+    sb.write(
+        "list.txt",
+        "src/a.txt\r\n./src/sub\nsrc/sub/../sub/b.txt\n\n",
+    );
+
+    sb.run(&["create", "src", "out", "--files-from", "list.txt"]);
+    // the listed directory is an entry but is not descended into
+    assert_eq!(
+        sorted_paths(&sb, "out/index.arc.json"),
+        vec!["a.txt", "sub", "sub/b.txt"]
+    );
+}
+
+#[test]
+fn nul_separated_list_on_stdin() {
+    let sb = Sandbox::new();
+    sb.config("", "");
+    sb.write("src/a b.txt", "a");
+    sb.write("src/line\nbreak", "b");
+    let list = format!(
+        "{}\0src/line\nbreak\0",
+        sb.path("src/a b.txt").to_str().unwrap()
+    );
+
+    let mut child = sb
+        .cmd()
+        .args(["create", "src", "out", "--files-from", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(list.as_bytes())
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    assert_eq!(
+        sorted_paths(&sb, "out/index.arc.json"),
+        vec!["a b.txt", "line\nbreak"]
+    );
+}
+
+#[test]
+fn paths_outside_the_roots_are_rejected() {
+    let sb = Sandbox::new();
+    sb.config("", "");
+    sb.write("src/a.txt", "a");
+    sb.write("other/x.txt", "x");
+    sb.write("list.txt", "src/../other/x.txt\n");
+
+    let out = sb.fail(&["create", "src", "out", "--files-from", "list.txt"]);
+    assert!(
+        stderr(&out).contains("is not under any source root"),
+        "{}",
+        stderr(&out)
+    );
+}
+
+#[test]
+fn listing_everything_matches_the_walk() {
+    let sb = Sandbox::new();
+    sb.config("", "");
+    sb.write("src/.gitignore", "*.log\n");
+    sb.write("src/a.txt", "a");
+    sb.write("src/app.log", "noise");
+    sb.write("src/sub/b.txt", "b");
+    sb.write("src/sub/deep/c.txt", "c");
+    sb.write("src/sub/.ignore", "!keep.log\n");
+    sb.write("src/sub/keep.log", "kept");
+    sb.write("src/cache/.nobackup", "");
+    sb.write("src/cache/data", "cached");
+
+    let mut all = vec![];
+    all_below(&sb.path("src"), &mut all);
+    sb.write("list.txt", all.join("\n"));
+
+    let rules = [
+        "--ignore-files",
+        "--exclude-if-present",
+        ".nobackup",
+        "--max-depth",
+        "2",
+    ];
+    let mut walk = vec!["create", "src", "walk"];
+    walk.extend(rules);
+    sb.run(&walk);
+    let mut listed = vec!["create", "src", "listed", "--files-from", "list.txt"];
+    listed.extend(rules);
+    sb.run(&listed);
+
+    let walked = sorted_paths(&sb, "walk/index.arc.json");
+    assert_eq!(
+        walked,
+        vec![
+            ".gitignore",
+            "a.txt",
+            "sub",
+            "sub/.ignore",
+            "sub/b.txt",
+            "sub/deep",
+            "sub/keep.log"
+        ]
+    );
+    assert_eq!(sorted_paths(&sb, "listed/index.arc.json"), walked);
+
+    let sources = |index: &str| {
+        let mut s: Vec<_> = sb.index(index)["header"]["ignore_sources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s.as_str().unwrap().to_string())
+            .collect();
+        s.sort();
+        s
+    };
+    assert_eq!(
+        sources("listed/index.arc.json"),
+        vec![".gitignore", "sub/.ignore"]
+    );
+    assert_eq!(
+        sources("listed/index.arc.json"),
+        sources("walk/index.arc.json")
+    );
+}
+
+#[test]
+fn restoring_one_root_brings_aliases_of_other_roots() {
+    let sb = Sandbox::new();
+    sb.config("", "");
+    sb.write("one/shared.txt", "same bytes");
+    sb.write("two/copy.txt", "same bytes");
+    sb.write("two/own.txt", "own");
+
+    sb.run(&["create", "a=one", "out", "--root", "b=two", "--dedup"]);
+    let idx = sb.index("out/index.arc.json");
+    assert_eq!(
+        sorted_paths(&sb, "out/index.arc.json"),
+        vec!["a", "a/shared.txt", "b", "b/copy.txt", "b/own.txt"]
+    );
+    assert_eq!(
+        common::entry(&idx, "b/copy.txt")["dedup_of"],
+        "a/shared.txt"
+    );
+
+    sb.run(&["restore", "out/index.arc.json", "back", "--root", "b"]);
+    assert_eq!(sb.read("back/copy.txt"), b"same bytes");
+    assert_eq!(sb.read("back/own.txt"), b"own");
+    assert!(!sb.path("back/shared.txt").exists());
+}
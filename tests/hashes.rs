@@ -0,0 +1,187 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+
+use common::{Sandbox, entry, stdout};
+use md5::Md5;
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const INDEX: &str = "out/index.arc.json";
+
+fn digests(data: &[u8]) -> Value {
+    serde_json::json!({
+        "md5": hex::encode(Md5::digest(data)),
+        "sha1": hex::encode(Sha1::digest(data)),
+        "blake3": blake3::hash(data).to_hex().as_str(),
+    })
+}
+
+fn archive(sb: &Sandbox) {
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/sub/b.bin", vec![7u8; 300_000]);
+    sb.write("src/empty.txt", "");
+    sb.run(&[
+        "create", "src", "out", "--hash", "md5,sha1", "--hash", "blake3",
+    ]);
+}
+
+fn set_digest(sb: &Sandbox, path: &str, algo: &str, hex: &str) {
+    let mut idx = sb.index(INDEX);
+    let e = idx["entries"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|e| e["path"] == path)
+        .unwrap();
+    e["hashes"][algo] = hex.into();
+    sb.write_index(INDEX, &idx);
+}
+
+#[test]
+fn extra_digests_are_recorded_per_file() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    let idx = sb.index(INDEX);
+    assert_eq!(
+        idx["header"]["hashes"],
+        serde_json::json!(["md5", "sha1", "blake3"])
+    );
+    for path in ["a.txt", "sub/b.bin", "empty.txt"] {
+        let data = sb.read(&format!("src/{path}"));
+        let e = entry(&idx, path);
+        assert_eq!(e["hashes"], digests(&data), "{path}");
+        assert_eq!(e["sha256"], hex::encode(Sha256::digest(&data)).as_str());
+    }
+
+    let info = sb.json(&["info", INDEX, "a.txt"]);
+    assert_eq!(info["hashes"], digests(b"alpha"));
+    let text = stdout(&sb.run(&["info", INDEX, "a.txt"]));
+    assert!(text.contains(&hex::encode(Md5::digest(b"alpha"))), "{text}");
+
+    // without --hash only SHA-256 is kept
+    sb.run(&["create", "src", "plain"]);
+    let idx = sb.index("plain/index.arc.json");
+    assert_eq!(idx["header"]["hashes"], serde_json::json!([]));
+    assert_eq!(entry(&idx, "a.txt")["hashes"], serde_json::json!({}));
+}
+
+#[test]
+fn verify_checks_the_digests_asked_for() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    sb.run(&["verify", INDEX, "--hash", "md5,sha1,sha256,blake3"]);
+
+    set_digest(&sb, "a.txt", "md5", &"0".repeat(32));
+    sb.run(&["verify", INDEX]);
+    sb.run(&["verify", INDEX, "--hash", "sha1,blake3"]);
+    let out = sb.fail(&["verify", INDEX, "--hash", "md5"]);
+    let text = stdout(&out);
+    assert!(text.contains("a.txt") && text.contains("md5"), "{text}");
+
+    sb.fail(&["verify", INDEX, "--hash", "crc32"]);
+}
+
+#[test]
+fn diff_and_search_use_any_recorded_digest() {
+    let sb = Sandbox::new();
+    archive(&sb);
+
+    for algo in ["md5", "sha1", "sha256", "blake3"] {
+        let d = if algo == "sha256" {
+            hex::encode(Sha256::digest(b"alpha"))
+        } else {
+            digests(b"alpha")[algo].as_str().unwrap().to_string()
+        };
+        let found = stdout(&sb.run(&["search", INDEX, "--hash", &d]));
+        assert!(found.contains("a.txt"), "{algo}: {found}");
+        assert!(!found.contains("b.bin"), "{algo}: {found}");
+    }
+
+    // same size and mtime, other bytes: only a digest tells
+    let path = sb.path("src/a.txt");
+    let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+    sb.write("src/a.txt", "ALPHA");
+    fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    let d = sb.json(&["--quiet", "diff", INDEX, "src", "--hash", "blake3"]);
+    assert_eq!(d["modified"][0]["path"], "a.txt", "{d}");
+    assert!(
+        d["modified"][0]["reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("checksum mismatch")
+    );
+
+    // the digest compared is the one named
+    set_digest(&sb, "sub/b.bin", "blake3", &"0".repeat(64));
+    let modified = |algo: &str| -> Vec<String> {
+        let d = sb.json(&["--quiet", "diff", INDEX, "src", "--hash", algo]);
+        let mut paths: Vec<String> = d["modified"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["path"].as_str().unwrap().to_string())
+            .collect();
+        paths.sort();
+        paths
+    };
+    assert_eq!(modified("sha1"), ["a.txt"]);
+    assert_eq!(modified("blake3"), ["a.txt", "sub/b.bin"]);
+}
+
+#[test]
+fn later_writers_keep_the_archives_digests() {
+    let sb = Sandbox::new();
+    archive(&sb);
+
+    // update: new files get them too
+    sb.write("src/new.txt", "new");
+    sb.run(&["update", INDEX, "src", "v2"]);
+    let idx = sb.index("v2/index.arc.json");
+    assert_eq!(
+        idx["header"]["hashes"],
+        serde_json::json!(["md5", "sha1", "blake3"])
+    );
+    assert_eq!(entry(&idx, "new.txt")["hashes"], digests(b"new"));
+    assert_eq!(entry(&idx, "a.txt")["hashes"], digests(b"alpha"));
+    sb.run(&["verify", "v2/index.arc.json", "--hash", "md5,sha1,blake3"]);
+
+    // repair: recomputed from the members
+    let original = sb.index(INDEX);
+    fs::remove_file(sb.path(INDEX)).unwrap();
+    fs::remove_file(sb.path("out/index.arc.json.b3")).unwrap();
+    sb.run(&["repair", "out"]);
+    let idx = sb.index(INDEX);
+    assert_eq!(idx["header"]["hashes"], original["header"]["hashes"]);
+    for path in ["a.txt", "sub/b.bin", "empty.txt"] {
+        assert_eq!(
+            entry(&idx, path)["hashes"],
+            entry(&original, path)["hashes"],
+            "{path}"
+        );
+    }
+    sb.run(&["verify", INDEX, "--hash", "md5,sha1,blake3"]);
+}
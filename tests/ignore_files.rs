@@ -0,0 +1,78 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use common::{Sandbox, sorted_paths};
+
+#[test]
+fn nested_ignore_files_are_honored_and_recorded() {
+    let sb = Sandbox::new();
+    sb.write("src/.gitignore", "*.log\nbuild/\n");
+    sb.write("src/app.log", "noise");
+    sb.write("src/build/out.o", "object");
+    sb.write("src/main.c", "int main;");
+    sb.write("src/logs/.ignore", "!keep.log\n");
+    sb.write("src/logs/keep.log", "kept");
+    sb.write("src/logs/drop.log", "dropped");
+    sb.write("src/.archivumignore", "secret.txt\n");
+    sb.write("src/secret.txt", "hush");
+
+    sb.run(&["create", "src", "out", "--ignore-files"]);
+    let files: Vec<_> = sorted_paths(&sb, "out/index.arc.json")
+        .into_iter()
+        .filter(|p| !p.starts_with('.') && !p.ends_with("/.ignore"))
+        .collect();
+    assert_eq!(files, vec!["logs", "logs/keep.log", "main.c"]);
+
+    let idx = sb.index("out/index.arc.json");
+    let mut sources: Vec<_> = idx["header"]["ignore_sources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.as_str().unwrap().to_string())
+        .collect();
+    sources.sort();
+    assert_eq!(
+        sources,
+        vec![".archivumignore", ".gitignore", "logs/.ignore"]
+    );
+}
+
+#[test]
+fn ignore_files_are_off_unless_asked_for() {
+    let sb = Sandbox::new();
+    sb.write("src/.gitignore", "*.log\n");
+    sb.write("src/app.log", "noise");
+
+    sb.run(&["create", "src", "out"]);
+    assert!(sorted_paths(&sb, "out/index.arc.json").contains(&"app.log".to_string()));
+}
+
+#[test]
+fn custom_ignore_file_name() {
+    let sb = Sandbox::new();
+    sb.write("src/.backupignore", "*.iso\n");
+    sb.write("src/.gitignore", "*.txt\n");
+    sb.write("src/disk.iso", "image");
+    sb.write("src/note.txt", "note");
+
+    sb.run(&["create", "src", "out", "--ignore-file", ".backupignore"]);
+    let p = sorted_paths(&sb, "out/index.arc.json");
+    assert!(!p.contains(&"disk.iso".to_string()));
+    assert!(p.contains(&"note.txt".to_string()));
+}
@@ -0,0 +1,194 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::io::Write;
+use std::process::Stdio;
+
+use common::{Sandbox, entry, stdout};
+use serde_json::Value;
+
+fn root(sb: &Sandbox, dir: &str) -> String {
+    let idx = sb.index(&format!("{dir}/index.arc.json"));
+    idx["header"]["merkle_root"].as_str().unwrap().to_string()
+}
+
+fn prove(sb: &Sandbox, path: &str) -> Value {
+    sb.json(&["--quiet", "verify", "out/index.arc.json", "--prove", path])
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; 32] {
+    let mut h = blake3::Hasher::new();
+    for p in parts {
+        h.update(p);
+    }
+    *h.finalize().as_bytes()
+}
+
+/// Fold a `--prove` proof up to its root, from the proof alone.
+fn fold(proof: &Value) -> String {
+    let record = proof["record"].as_str().unwrap().as_bytes();
+    let mut h = hash(&[&[0], record]);
+    for step in proof["steps"].as_array().unwrap() {
+        let sibling = blake3::Hash::from_hex(step["hash"].as_str().unwrap()).unwrap();
+        h = match step["side"].as_str().unwrap() {
+            "left" => hash(&[&[1], sibling.as_bytes(), &h]),
+            _ => hash(&[&[1], &h, sibling.as_bytes()]),
+        };
+    }
+    blake3::Hash::from(h).to_hex().to_string()
+}
+
+#[test]
+fn the_root_follows_content_not_layout() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/sub/b.txt", "beta");
+    sb.write("src/c.txt", "gamma");
+    sb.run(&["create", "src", "one"]);
+    sb.run(&[
+        "create",
+        "src",
+        "two",
+        "--compress",
+        "gzip",
+        "--split-files",
+        "1",
+        "--notes",
+        "same files",
+    ]);
+    let want = root(&sb, "one");
+    assert_eq!(root(&sb, "two"), want);
+
+    let list = stdout(&sb.run(&["list", "one/index.arc.json"]));
+    assert!(list.contains(&want), "{list}");
+    let stats = stdout(&sb.run(&["stats", "two/index.arc.json"]));
+    assert!(stats.contains(&want), "{stats}");
+
+    sb.write("src/c.txt", "GAMMA");
+    sb.run(&["create", "src", "three"]);
+    assert_ne!(root(&sb, "three"), want);
+}
+
+#[test]
+fn prove_shows_one_entry_is_in_the_archive() {
+    let sb = Sandbox::new();
+    for i in 0..5 {
+        sb.write(&format!("src/f{i}.txt"), format!("file {i}"));
+    }
+    sb.write("src/sub/g.txt", "in a directory");
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    let want = root(&sb, "out");
+
+    let r = prove(&sb, "f3.txt");
+    assert_eq!(r["status"], "PASS", "{r}");
+    assert_eq!(r["content_checked"], true);
+    assert_eq!(r["proof"]["root"], want.as_str());
+    // checkable by anyone holding only the root
+    assert_eq!(fold(&r["proof"]), want);
+    let sha = entry(&sb.index("out/index.arc.json"), "f3.txt")["sha256"].clone();
+    assert!(
+        r["proof"]["record"]
+            .as_str()
+            .unwrap()
+            .ends_with(sha.as_str().unwrap())
+    );
+
+    let r = prove(&sb, "sub");
+    assert_eq!(r["status"], "PASS", "{r}");
+    assert_eq!(r["content_checked"], false);
+    assert_eq!(fold(&r["proof"]), want);
+
+    sb.fail(&["verify", "out/index.arc.json", "--prove", "nope.txt"]);
+}
+
+#[test]
+fn prove_fails_on_changed_data_or_entries() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha ".repeat(100));
+    sb.write("src/b.txt", "beta");
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+
+    // stored data that no longer matches its leaf
+    let part = "out/data.part000.tar";
+    let mut data = sb.read(part);
+    let at = data.windows(6).position(|w| w == b"alpha ").unwrap();
+    data[at] = b'A';
+    sb.write(part, &data);
+    let r = prove(&sb, "a.txt");
+    assert_eq!(r["status"], "FAIL");
+    assert!(
+        r["problems"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("stored data hashes to"),
+        "{r}"
+    );
+    sb.fail(&["verify", "out/index.arc.json", "--prove", "a.txt"]);
+
+    // an entry edited after the root was recorded
+    let mut idx = sb.index("out/index.arc.json");
+    let b = idx["entries"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|e| e["path"] == "b.txt")
+        .unwrap();
+    b["unix_mode"] = 0o100600.into();
+    sb.write_index("out/index.arc.json", &idx);
+    let r = prove(&sb, "b.txt");
+    assert_eq!(r["status"], "FAIL");
+    assert!(
+        r["problems"][0]
+            .as_str()
+            .unwrap()
+            .contains("the header records"),
+        "{r}"
+    );
+}
+
+#[test]
+fn prove_hashes_a_stream_across_its_parts() {
+    let sb = Sandbox::new();
+    let data: Vec<u8> = (0..20_000u32)
+        .flat_map(|i| i.wrapping_mul(2_654_435_761).to_le_bytes())
+        .collect();
+    let mut child = sb
+        .cmd()
+        .args([
+            "create",
+            "-",
+            "out",
+            "--stdin-name",
+            "s.bin",
+            "--split-gb",
+            "0.000004",
+        ])
+        .args(["--compress", "none"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&data).unwrap();
+    assert!(child.wait().unwrap().success());
+    let idx = sb.index("out/index.arc.json");
+    assert!(entry(&idx, "s.bin")["pieces"].as_array().unwrap().len() > 2);
+
+    let r = prove(&sb, "s.bin");
+    assert_eq!(r["status"], "PASS", "{r}");
+    assert_eq!(r["content_checked"], true);
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::symlink;
+
+use common::{Sandbox, tar_members};
+
+#[test]
+fn directories_and_symlinks_are_tar_members_of_part_000() {
+    let sb = Sandbox::new();
+    sb.write("src/docs/readme.txt", "read me");
+    fs::create_dir_all(sb.path("src/empty")).unwrap();
+    symlink("docs/readme.txt", sb.path("src/link")).unwrap();
+
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    let members = tar_members(&sb.path("out/data.part000.tar"));
+    let kind = |name: &str| {
+        members
+            .iter()
+            .find(|(p, _)| p.trim_end_matches('/') == name)
+            .map(|(_, k)| *k)
+            .unwrap_or_else(|| panic!("{name} missing from part 000: {members:?}"))
+    };
+    assert_eq!(kind("docs"), tar::EntryType::Directory);
+    assert_eq!(kind("empty"), tar::EntryType::Directory);
+    assert_eq!(kind("link"), tar::EntryType::Symlink);
+    assert_eq!(kind("docs/readme.txt"), tar::EntryType::Regular);
+
+    // metadata-only headers must parse cleanly in every numeric field
+    let mut ar = tar::Archive::new(fs::File::open(sb.path("out/data.part000.tar")).unwrap());
+    for member in ar.entries().unwrap() {
+        let member = member.unwrap();
+        let h = member.header();
+        if matches!(
+            h.entry_type(),
+            tar::EntryType::Directory | tar::EntryType::Symlink
+        ) {
+            h.uid().unwrap();
+            h.gid().unwrap();
+            h.mtime().unwrap();
+            h.mode().unwrap();
+        }
+    }
+}
+
+#[test]
+fn repair_recovers_directories_and_symlinks_from_parts() {
+    let sb = Sandbox::new();
+    sb.write("src/docs/readme.txt", "read me");
+    fs::create_dir_all(sb.path("src/empty")).unwrap();
+    symlink("docs/readme.txt", sb.path("src/link")).unwrap();
+
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    fs::remove_file(sb.path("out/index.arc.json")).unwrap();
+    fs::remove_file(sb.path("out/index.arc.json.b3")).unwrap();
+    sb.run(&["repair", "out"]);
+
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert!(sb.path("dst/empty").is_dir());
+    assert_eq!(
+        fs::read_link(sb.path("dst/link")).unwrap(),
+        std::path::Path::new("docs/readme.txt")
+    );
+    assert_eq!(sb.read("dst/link"), b"read me");
+}
+
+#[test]
+fn a_parts_manifest_is_never_taken_for_an_archived_file() {
+    let sb = Sandbox::new();
+    sb.write("src/.archivum/manifest.json", "mine");
+    sb.write("src/a.txt", "a");
+    sb.run(&[
+        "create",
+        "src",
+        "out",
+        "--compress",
+        "none",
+        "--split-files",
+        "1",
+    ]);
+    let members = tar_members(&sb.path("out/data.part000.tar"));
+    assert!(members.iter().any(|(p, _)| p == ".archivum/manifest.json"));
+
+    let cat = sb.run(&["cat", "out/index.arc.json", ".archivum/manifest.json"]);
+    assert_eq!(cat.stdout, b"mine");
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/.archivum/manifest.json"), b"mine");
+
+    // pointed at a part that holds only the other file and a manifest,
+    // the file is not found rather than read from the manifest
+    let mut idx = sb.index("out/index.arc.json");
+    let e = idx["entries"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|e| e["path"] == ".archivum/manifest.json")
+        .unwrap();
+    let other = 1 - e["tar_part"].as_u64().unwrap_or(0);
+    e["tar_part"] = other.into();
+    sb.write_index("out/index.arc.json", &idx);
+    sb.fail(&["cat", "out/index.arc.json", ".archivum/manifest.json"]);
+    sb.fail(&[
+        "extract",
+        "out/index.arc.json",
+        ".archivum/manifest.json",
+        "--output",
+        "one.json",
+    ]);
+    assert!(!sb.path("one.json").exists());
+}
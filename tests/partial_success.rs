@@ -0,0 +1,84 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use common::{Sandbox, paths, stdout};
+use serde_json::json;
+
+const EXIT_PARTIAL: i32 = 3;
+
+#[cfg(unix)]
+#[test]
+fn unreadable_paths_are_recorded_and_exit_partial() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let sb = Sandbox::new();
+    sb.write("src/ok.txt", "fine");
+    let secret = sb.write("src/secret.txt", "hidden");
+    sb.write("src/locked/inner.txt", "hidden too");
+    fs::set_permissions(&secret, fs::Permissions::from_mode(0o000)).unwrap();
+    fs::set_permissions(sb.path("src/locked"), fs::Permissions::from_mode(0o000)).unwrap();
+    if fs::read(&secret).is_ok() {
+        eprintln!("skipping: permissions are not enforced for this user");
+        return;
+    }
+
+    let out = sb.exec(&["create", "src", "out", "--compress", "none"]);
+    fs::set_permissions(sb.path("src/locked"), fs::Permissions::from_mode(0o755)).unwrap();
+    assert_eq!(out.status.code(), Some(EXIT_PARTIAL));
+
+    let idx = sb.index("out/index.arc.json");
+    let names = paths(&idx);
+    assert!(names.contains(&"ok.txt".to_string()));
+    assert!(!names.contains(&"secret.txt".to_string()));
+    let errors = idx["errors"].as_array().unwrap();
+    let kinds: Vec<_> = errors
+        .iter()
+        .map(|e| (e["path"].as_str().unwrap(), e["kind"].as_str().unwrap()))
+        .collect();
+    assert!(kinds.contains(&("secret.txt", "permission_denied")));
+    assert!(kinds.iter().any(|(p, _)| p.starts_with("locked")));
+    sb.run(&["verify", "out/index.arc.json"]);
+}
+
+#[test]
+fn list_and_stats_show_skipped_paths() {
+    let sb = Sandbox::new();
+    sb.write("src/ok.txt", "fine");
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+
+    let mut idx = sb.index("out/index.arc.json");
+    idx["errors"] = json!([{
+        "path": "gone.txt",
+        "stage": "archive",
+        "kind": "vanished",
+        "message": "No such file or directory",
+    }]);
+    sb.write_index("out/index.arc.json", &idx);
+
+    let list = stdout(&sb.run(&["list", "out/index.arc.json"]));
+    assert!(list.contains("gone.txt"), "{list}");
+    let stats = stdout(&sb.run(&["stats", "out/index.arc.json"]));
+    assert!(
+        stats.contains("vanished") && stats.contains("gone.txt"),
+        "{stats}"
+    );
+    let json = sb.json(&["stats", "out/index.arc.json"]);
+    assert_eq!(json["skipped"][0]["path"], "gone.txt");
+}
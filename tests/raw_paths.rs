@@ -0,0 +1,54 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![cfg(unix)]
+
+mod common;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+
+use common::Sandbox;
+
+#[test]
+fn non_utf8_names_survive_create_and_restore() {
+    let sb = Sandbox::new();
+    sb.write("src/plain.txt", "plain");
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    fs::write(sb.path("src").join(name), "latin-1").unwrap();
+
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    let idx = sb.index("out/index.arc.json");
+    let hexed: Vec<_> = idx["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["path"].is_object())
+        .collect();
+    assert_eq!(hexed.len(), 1);
+    assert_eq!(hexed[0]["path"]["hex"], hex_of(b"caf\xe9.txt"));
+
+    sb.run(&["verify", "out/index.arc.json"]);
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert_eq!(fs::read(sb.path("dst").join(name)).unwrap(), b"latin-1");
+    assert_eq!(sb.read("dst/plain.txt"), b"plain");
+}
+
+fn hex_of(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
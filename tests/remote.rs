@@ -0,0 +1,118 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{Sandbox, stderr};
+
+/// `HOST:PATH` for `rel` in the sandbox, served by the `local` transport.
+fn remote(sb: &Sandbox, rel: &str) -> String {
+    format!("backuphost:{}", sb.path(rel).display())
+}
+
+fn with_transport(sb: &Sandbox, args: &[&str]) -> Command {
+    fs::create_dir_all(sb.path("tmp")).unwrap();
+    let mut c = sb.cmd();
+    c.args(args)
+        .env("ARCHIVUM_RSH", "local")
+        .env("TMPDIR", sb.path("tmp"));
+    c
+}
+
+fn staged(sb: &Sandbox) -> Vec<String> {
+    fs::read_dir(sb.path("tmp"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn create_verify_restore_over_the_local_transport() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/sub/b.txt", "b".repeat(10_000));
+    let out = remote(&sb, "far/out");
+    let index = format!("{out}/index.arc.json");
+
+    let run = |args: &[&str]| {
+        let o = with_transport(&sb, args).output().unwrap();
+        assert!(o.status.success(), "{args:?}: {}", stderr(&o));
+        o
+    };
+    run(&["create", "src", &out, "--split-gb", "0.000004"]);
+    assert!(sb.path("far/out/index.arc.json").exists());
+    assert!(sb.path("far/out/data.part001.tar.zst").exists());
+    assert!(staged(&sb).is_empty(), "{:?}", staged(&sb));
+
+    run(&["verify", &index]);
+    run(&["restore", &index, "dst"]);
+    assert_eq!(sb.read("dst/a.txt"), b"alpha");
+    assert_eq!(sb.read("dst/sub/b.txt"), "b".repeat(10_000).as_bytes());
+}
+
+#[test]
+fn failed_create_leaves_no_staging_directory() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    let out = remote(&sb, "far/out");
+
+    // the files upload, then the stdin stream is refused
+    let o = with_transport(
+        &sb,
+        &[
+            "create",
+            "src",
+            &out,
+            "--stdin-name",
+            "dump.bin",
+            "--compress",
+            "bzip2",
+        ],
+    )
+    .output()
+    .unwrap();
+    assert!(!o.status.success());
+    assert!(stderr(&o).contains("bzip2"), "{}", stderr(&o));
+    assert!(staged(&sb).is_empty(), "{:?}", staged(&sb));
+}
+
+#[test]
+fn a_login_banner_is_not_taken_for_a_message_length() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    // prints a banner, then reads the length of the hello and hangs up
+    sb.write(
+        "chatty.sh",
+        "printf 'Welcome to backuphost\\n'\nhead -c 4 >/dev/null\n",
+    );
+    let o = with_transport(&sb, &["create", "src", &remote(&sb, "far/out")])
+        .env(
+            "ARCHIVUM_RSH",
+            format!("sh {}", sb.path("chatty.sh").display()),
+        )
+        .output()
+        .unwrap();
+    assert!(!o.status.success());
+    assert!(
+        stderr(&o).contains("not an archivum serve --stdio endpoint"),
+        "{}",
+        stderr(&o)
+    );
+}
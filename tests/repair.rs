@@ -0,0 +1,321 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use common::{Sandbox, entry, stdout};
+use sha2::{Digest, Sha256};
+
+fn lose_index(sb: &Sandbox, dir: &str) {
+    fs::remove_file(sb.path(&format!("{dir}/index.arc.json"))).unwrap();
+    fs::remove_file(sb.path(&format!("{dir}/index.arc.json.b3"))).unwrap();
+}
+
+/// Replace the content of `rel` without changing its size or mtime.
+fn swap_bytes(sb: &Sandbox, rel: &str, content: &str) {
+    let mtime = fs::metadata(sb.path(rel)).unwrap().modified().unwrap();
+    sb.write(rel, content);
+    fs::File::options()
+        .write(true)
+        .open(sb.path(rel))
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+}
+
+#[test]
+fn rebuilt_index_carries_checksums_for_verify_diff_and_update() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/sub/b.txt", "b".repeat(50_000));
+    sb.write("src/empty.txt", "");
+    sb.run(&["create", "src", "out"]);
+    lose_index(&sb, "out");
+    sb.run(&["repair", "out"]);
+
+    let idx = sb.index("out/index.arc.json");
+    for path in ["a.txt", "sub/b.txt", "empty.txt"] {
+        let want = hex::encode(Sha256::digest(sb.read(&format!("src/{path}"))));
+        assert_eq!(entry(&idx, path)["sha256"], want.as_str(), "{path}");
+    }
+    sb.run(&["verify", "out/index.arc.json"]);
+
+    // a touched file is recognised as unchanged by its checksum
+    fs::File::options()
+        .write(true)
+        .open(sb.path("src/sub/b.txt"))
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    let text = stdout(&sb.run(&["update", "out/index.arc.json", "src", "v2", "--checksum"]));
+    assert!(text.contains("Unchanged: 3  Changed: 0"), "{text}");
+
+    // and an edit that keeps size and mtime is still caught by diff
+    swap_bytes(&sb, "src/a.txt", "ALPHA");
+    let diff = sb.json(&["--quiet", "diff", "out/index.arc.json", "src", "--checksum"]);
+    let a = diff["modified"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["path"] == "a.txt")
+        .expect("a.txt reported as modified");
+    assert!(
+        a["reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("checksum mismatch"),
+        "{a}"
+    );
+}
+
+#[test]
+fn aliases_come_back_and_stored_duplicates_stay_separate() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "same bytes");
+    sb.write("src/b.txt", "same bytes");
+    sb.write("src/c.txt", "other");
+    sb.run(&["create", "src", "dedup", "--dedup"]);
+    sb.run(&["create", "src", "full"]);
+    lose_index(&sb, "dedup");
+    lose_index(&sb, "full");
+
+    sb.run(&["repair", "dedup"]);
+    let idx = sb.index("dedup/index.arc.json");
+    assert_eq!(entry(&idx, "b.txt")["dedup_of"], "a.txt");
+    assert_eq!(
+        entry(&idx, "b.txt")["sha256"],
+        entry(&idx, "a.txt")["sha256"]
+    );
+    sb.run(&["verify", "dedup/index.arc.json"]);
+    sb.run(&["restore", "dedup/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/b.txt"), b"same bytes");
+
+    let text = stdout(&sb.run(&["repair", "full"]));
+    assert!(text.contains("1 file(s) duplicate"), "{text}");
+    let idx = sb.index("full/index.arc.json");
+    assert!(entry(&idx, "b.txt")["dedup_of"].is_null());
+    assert_eq!(
+        entry(&idx, "b.txt")["sha256"],
+        entry(&idx, "a.txt")["sha256"]
+    );
+}
+
+#[test]
+fn compression_is_detected_and_misnamed_parts_are_renamed() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/b.txt", "beta");
+    sb.write("src/c.txt", "gamma");
+    sb.run(&[
+        "create",
+        "src",
+        "out",
+        "--compress",
+        "gzip",
+        "--split-files",
+        "1",
+    ]);
+    lose_index(&sb, "out");
+    fs::rename(
+        sb.path("out/data.part001.tar.gz"),
+        sb.path("out/data.part001.tar.zst"),
+    )
+    .unwrap();
+    // a stray part in another format is left alone
+    sb.write(
+        "out/data.part009.tar.zst",
+        zstd::encode_all(&b"not gzip"[..], 3).unwrap(),
+    );
+
+    let out = sb.run(&["repair", "out"]);
+    let text = stdout(&out);
+    assert!(text.contains("Compression: gzip (detected)"), "{text}");
+    assert!(text.contains("Renamed"), "{text}");
+    assert!(
+        common::stderr(&out).contains("holds zstd data, not gzip"),
+        "{}",
+        common::stderr(&out)
+    );
+    assert!(sb.path("out/data.part001.tar.gz").exists());
+    assert!(sb.path("out/data.part009.tar.zst").exists());
+
+    let idx = sb.index("out/index.arc.json");
+    assert_eq!(idx["header"]["compression"], "gzip");
+    assert_eq!(idx["header"]["total_files"], 3);
+    fs::remove_file(sb.path("out/data.part009.tar.zst")).unwrap();
+    sb.run(&["verify", "out/index.arc.json"]);
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/b.txt"), b"beta");
+}
+
+#[test]
+fn parts_of_older_bases_are_gathered() {
+    let sb = Sandbox::new();
+    sb.write("src/keep.txt", "kept");
+    sb.write("src/edit.txt", "before");
+    sb.write("src/gone.txt", "dropped by the update");
+    sb.run(&["create", "src", "v1"]);
+    sb.write("src/edit.txt", "after the update");
+    sb.write("src/new.txt", "new");
+    fs::remove_file(sb.path("src/gone.txt")).unwrap();
+    sb.run(&["update", "v1/index.arc.json", "src", "v2"]);
+    let original = sb.read("v2/index.arc.json");
+
+    // without the old index the bases must be named; what the update
+    // dropped comes back, as nothing says it was dropped
+    lose_index(&sb, "v2");
+    sb.run(&["repair", "v2", "--base", "v1"]);
+    let idx = sb.index("v2/index.arc.json");
+    assert_eq!(
+        idx["header"]["part_bases"],
+        serde_json::json!(["", "../v1"])
+    );
+    assert_eq!(entry(&idx, "keep.txt")["tar_base"], 1);
+    assert!(entry(&idx, "edit.txt")["tar_base"].is_null());
+    assert_eq!(entry(&idx, "gone.txt")["tar_base"], 1);
+    sb.run(&["verify", "v2/index.arc.json"]);
+    sb.run(&["restore", "v2/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/edit.txt"), b"after the update");
+    assert_eq!(sb.read("dst/keep.txt"), b"kept");
+
+    // an old index that parses but fails its seal still says which files
+    // of the older parts the archive holds, whatever their manifests list
+    sb.write("v2/index.arc.json", &original);
+    sb.write("v2/index.arc.json.b3", "0".repeat(64));
+    let text = stdout(&sb.run(&["repair", "v2"]));
+    assert!(text.contains("seal does not match"), "{text}");
+    assert!(
+        text.contains("1 member(s) of older parts not in the old index left out"),
+        "{text}"
+    );
+    let idx = sb.index("v2/index.arc.json");
+    assert_eq!(
+        common::paths(&idx),
+        common::paths(&serde_json::from_slice(&original).unwrap())
+    );
+    sb.run(&["verify", "v2/index.arc.json"]);
+}
+
+#[test]
+fn what_parses_of_a_truncated_index_is_merged_in() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/b.txt", "beta");
+    sb.write("src/c.txt", "gamma");
+    sb.run(&["create", "src", "out", "--notes", "nightly"]);
+    let original = sb.index("out/index.arc.json");
+
+    // cut the file off in the middle of the entry list; the notes are
+    // changed to tell its header from the copies in the parts' manifests
+    let bytes = String::from_utf8(sb.read("out/index.arc.json"))
+        .unwrap()
+        .replace("\"nightly\"", "\"weekly\"")
+        .into_bytes();
+    let cut = String::from_utf8_lossy(&bytes)
+        .find("\"path\": \"b.txt\"")
+        .unwrap();
+    sb.write("out/index.arc.json", &bytes[..cut]);
+
+    let text = stdout(&sb.run(&["repair", "out"]));
+    assert!(
+        text.contains("Old index: damaged; recovered header and"),
+        "{text}"
+    );
+    let idx = sb.index("out/index.arc.json");
+    let (old, new) = (&original["header"], &idx["header"]);
+    for key in ["created_at_unix", "created_at_human", "roots", "zstd_level"] {
+        assert_eq!(new[key], old[key], "{key}");
+    }
+    assert!(new["notes"].as_str().unwrap().ends_with("(was: weekly)"));
+    // the entry that survived comes back as it was
+    assert_eq!(entry(&idx, "a.txt"), entry(&original, "a.txt"));
+    assert_eq!(common::paths(&idx), common::paths(&original));
+    sb.run(&["verify", "out/index.arc.json"]);
+}
+
+#[test]
+#[cfg(unix)]
+fn manifests_give_the_index_back_as_it_was() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "same");
+    sb.write("src/sub/b.txt", "same");
+    sb.write("src/c.txt", "c".repeat(9000));
+    fs::set_permissions(sb.path("src/c.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+    symlink("a.txt", sb.path("src/link")).unwrap();
+    fs::create_dir_all(sb.path("src/empty")).unwrap();
+    sb.run(&["create", "src", "out", "--dedup", "--split-files", "2"]);
+    let mut data = vec![];
+    for i in 0..6000u32 {
+        data.extend_from_slice(&i.to_le_bytes());
+    }
+    let added = piped(
+        &sb,
+        &[
+            "add",
+            "out/index.arc.json",
+            "--stdin-name",
+            "dump.bin",
+            "--split-gb",
+            "0.000004",
+        ],
+        &data,
+    );
+    assert!(added.status.success(), "{}", common::stderr(&added));
+    let original = sb.index("out/index.arc.json");
+
+    lose_index(&sb, "out");
+    let text = stdout(&sb.run(&["repair", "out"]));
+    assert!(text.contains("Manifests:"), "{text}");
+    let idx = sb.index("out/index.arc.json");
+    assert_eq!(idx["entries"], original["entries"]);
+    assert_eq!(entry(&idx, "dump.bin")["from_stdin"], true);
+    assert_eq!(entry(&idx, "sub/b.txt")["dedup_of"], "a.txt");
+
+    // the stream is not looked for in the source
+    let r = sb.json(&[
+        "--quiet",
+        "verify",
+        "out/index.arc.json",
+        "--continue-on-error",
+        "--against",
+        "src",
+    ]);
+    assert_eq!(r["status"], "PASS", "{r}");
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/dump.bin"), data);
+}
+
+fn piped(sb: &Sandbox, args: &[&str], input: &[u8]) -> std::process::Output {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = sb
+        .cmd()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use common::{Sandbox, stdout};
+
+const EPOCH: u64 = 1700000000;
+
+fn populate(sb: &Sandbox, root: &str, reverse: bool) {
+    let mut files = vec![
+        ("a.txt", "alpha"),
+        ("sub/b.txt", "beta"),
+        ("sub/c.bin", "gamma"),
+    ];
+    if reverse {
+        files.reverse();
+    }
+    for (name, body) in files {
+        sb.write(&format!("{root}/{name}"), body);
+    }
+}
+
+/// Give everything under `root` the mtime `EPOCH + after`, the root last.
+fn stamp(sb: &Sandbox, root: &str, after: u64) {
+    let t = SystemTime::UNIX_EPOCH + Duration::from_secs(EPOCH + after);
+    for rel in ["a.txt", "sub/b.txt", "sub/c.bin", "sub", ""] {
+        fs::File::open(sb.path(&format!("{root}/{rel}")))
+            .unwrap()
+            .set_modified(t)
+            .unwrap();
+    }
+}
+
+fn create(sb: &Sandbox, src: &str, out: &str) {
+    let st = sb
+        .cmd()
+        .env("SOURCE_DATE_EPOCH", EPOCH.to_string())
+        .args(["-q", "create", src, out, "--reproducible", "--hash", "md5"])
+        .status()
+        .unwrap();
+    assert!(st.success());
+}
+
+#[test]
+fn two_runs_are_byte_identical() {
+    let sb = Sandbox::new();
+    populate(&sb, "one", false);
+    populate(&sb, "two", true);
+    // both trees are newer than the epoch, and not equally new
+    stamp(&sb, "one", 100);
+    stamp(&sb, "two", 5000);
+
+    create(&sb, "one", "out1");
+    create(&sb, "two", "out2");
+
+    let mut names: Vec<_> = fs::read_dir(sb.path("out1"))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert!(names.iter().any(|n| n.starts_with("data.part000")));
+    for name in &names {
+        assert_eq!(
+            sb.read(&format!("out1/{name}")),
+            sb.read(&format!("out2/{name}")),
+            "{name} differs between runs"
+        );
+    }
+}
+
+#[test]
+fn mtimes_are_clamped_to_source_date_epoch() {
+    let sb = Sandbox::new();
+    populate(&sb, "src", false);
+    create(&sb, "src", "out");
+
+    let idx = sb.index("out/index.arc.json");
+    assert_eq!(idx["header"]["created_at_unix"], EPOCH);
+    for e in idx["entries"].as_array().unwrap() {
+        assert!(e["mtime"].as_u64().unwrap() <= EPOCH, "{e}");
+    }
+    let members = {
+        let part = fs::File::open(sb.path("out/data.part000.tar.zst")).unwrap();
+        let mut ar = tar::Archive::new(zstd::Decoder::new(part).unwrap());
+        ar.entries()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .filter(|e| !e.path().unwrap().starts_with(".archivum"))
+            .map(|e| {
+                let h = e.header();
+                (h.mtime().unwrap(), h.uid().unwrap(), h.gid().unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+    assert!(!members.is_empty());
+    for (mtime, uid, gid) in members {
+        assert!(mtime <= EPOCH);
+        assert_eq!((uid, gid), (0, 0));
+    }
+
+    // the clamped mtimes still count as unchanged
+    let out = sb.run(&["diff", "out/index.arc.json", "src", "--changed-only"]);
+    assert!(!stdout(&out).contains("MODIFIED"), "{}", stdout(&out));
+}
@@ -0,0 +1,85 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::process::{Output, Stdio};
+
+use common::{Sandbox, stderr};
+
+fn restore_from(sb: &Sandbox, stream: &[u8], args: &[&str]) -> Output {
+    let mut child = sb
+        .cmd()
+        .arg("restore")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // the command may stop reading early; a broken pipe is fine here
+    let _ = child.stdin.take().unwrap().write_all(stream);
+    child.wait_with_output().unwrap()
+}
+
+fn leftovers(sb: &Sandbox, dir: &str) -> Vec<String> {
+    fs::read_dir(sb.path(dir))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|n| n.contains("archivum-partial"))
+        .collect()
+}
+
+#[test]
+fn stdin_takes_its_own_target() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/sub/b.txt", "beta");
+    let stream = sb.run(&["create", "src", "--stdout"]).stdout;
+
+    let out = restore_from(&sb, &stream, &["--stdin", "dst"]);
+    assert!(out.status.success(), "{}", stderr(&out));
+    assert_eq!(sb.read("dst/a.txt"), b"alpha");
+    assert_eq!(sb.read("dst/sub/b.txt"), b"beta");
+    assert!(leftovers(&sb, "dst").is_empty());
+
+    // an INDEX alongside --stdin is a usage error, not a second target
+    let out = restore_from(&sb, &stream, &["--stdin", "dst2", "other"]);
+    assert!(!out.status.success());
+    assert!(!sb.path("dst2").exists());
+}
+
+#[test]
+fn corrupt_member_is_never_written() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "A".repeat(4096));
+    sb.write("src/b.txt", "B".repeat(4096));
+    let mut stream = sb
+        .run(&["create", "src", "--stdout", "--compress", "none"])
+        .stdout;
+    let at = stream.windows(64).position(|w| w == [b'A'; 64]).unwrap();
+    stream[at + 100] = b'Z';
+
+    let out = restore_from(&sb, &stream, &["--stdin", "dst"]);
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("CORRUPT"), "{}", stderr(&out));
+    assert!(!sb.path("dst/a.txt").exists());
+    assert_eq!(sb.read("dst/b.txt"), "B".repeat(4096).as_bytes());
+    assert!(leftovers(&sb, "dst").is_empty());
+}
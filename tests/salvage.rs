@@ -0,0 +1,221 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::process::Output;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use common::{Sandbox, stderr};
+use serde_json::Value;
+
+const PART: &str = "out/data.part000.tar.zst";
+const FILES: usize = 8;
+
+/// Bytes that do not compress, distinct per `seed`.
+fn noise(seed: u64, len: usize) -> Vec<u8> {
+    let mut x = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+fn archive(sb: &Sandbox, extra: &[&str]) {
+    for i in 0..FILES {
+        sb.write(&format!("src/f{i}.bin"), noise(i as u64, 20_000));
+    }
+    let mut args = vec!["create", "src", "out"];
+    args.extend_from_slice(extra);
+    sb.run(&args);
+}
+
+/// `restore --salvage` into `dst`, killed if it has not finished within a
+/// minute. The scratch files go to the sandbox's `tmp`.
+fn salvage(sb: &Sandbox) -> (Output, Value) {
+    fs::create_dir_all(sb.path("tmp")).unwrap();
+    let mut child = sb
+        .cmd()
+        .args([
+            "restore",
+            "out/index.arc.json",
+            "dst",
+            "--salvage=report.json",
+        ])
+        .env("TMPDIR", sb.path("tmp"))
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().ok();
+            panic!("restore --salvage did not finish");
+        }
+        sleep(Duration::from_millis(50));
+    }
+    let out = child.wait_with_output().unwrap();
+    assert!(!out.status.success(), "nothing was lost\n{}", stderr(&out));
+    let leftovers: Vec<_> = fs::read_dir(sb.path("tmp")).unwrap().collect();
+    assert!(leftovers.is_empty(), "scratch left behind: {leftovers:?}");
+    let report = serde_json::from_slice(&sb.read("report.json")).unwrap();
+    (out, report)
+}
+
+/// Where each file's header starts in the uncompressed tar stream.
+fn header_offsets(tar: &[u8]) -> Vec<(String, u64)> {
+    let mut ar = tar::Archive::new(tar);
+    ar.entries()
+        .unwrap()
+        .map(|e| {
+            let e = e.unwrap();
+            let path = e.path().unwrap().to_string_lossy().into_owned();
+            (path, e.raw_header_position())
+        })
+        .filter(|(p, _)| p.ends_with(".bin"))
+        .collect()
+}
+
+fn lost(report: &Value) -> Vec<(String, String)> {
+    report["lost"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| {
+            let path = l["path"].as_str().unwrap().to_string();
+            (path, l["reason"].as_str().unwrap().to_string())
+        })
+        .collect()
+}
+
+fn restored(sb: &Sandbox) -> Vec<String> {
+    (0..FILES)
+        .map(|i| format!("f{i}.bin"))
+        .filter(|name| match fs::read(sb.path(&format!("dst/{name}"))) {
+            Ok(data) => {
+                assert_eq!(data, sb.read(&format!("src/{name}")), "{name}");
+                true
+            }
+            Err(_) => false,
+        })
+        .collect()
+}
+
+#[test]
+fn bad_frame_header_resumes_at_the_next_frame() {
+    let sb = Sandbox::new();
+    archive(&sb, &[]);
+
+    // write the part again as two frames, like a multi-threaded zstd
+    let mut tar = vec![];
+    zstd::Decoder::new(fs::File::open(sb.path(PART)).unwrap())
+        .unwrap()
+        .read_to_end(&mut tar)
+        .unwrap();
+    let cut = tar.len() / 2 / 512 * 512;
+    let mut first = zstd::encode_all(&tar[..cut], 3).unwrap();
+    let second = zstd::encode_all(&tar[cut..], 3).unwrap();
+    // a reserved bit of the frame header descriptor
+    first[4] |= 0x08;
+    let resume = first.len() as u64;
+    sb.write(PART, [first, second].concat());
+
+    let (_, report) = salvage(&sb);
+    let damage = &report["parts"][0]["damage"];
+    assert_eq!(damage[0]["layer"], "compression");
+    assert_eq!(damage[0]["offset"], 0);
+    assert_eq!(damage[0]["resumed_at"], resume);
+
+    // only members whose header lies in the second frame come back
+    let (after, before): (Vec<_>, Vec<_>) = header_offsets(&tar)
+        .into_iter()
+        .partition(|(_, at)| *at >= cut as u64);
+    assert!(!after.is_empty() && !before.is_empty());
+    let after: Vec<String> = after.into_iter().map(|(p, _)| p).collect();
+    assert_eq!(restored(&sb), after);
+    let mut want: Vec<_> = before
+        .into_iter()
+        .map(|(p, _)| (p, "not found in readable data".to_string()))
+        .collect();
+    want.sort();
+    assert_eq!(lost(&report), want);
+}
+
+#[test]
+fn damage_inside_a_part_loses_only_the_members_it_touches() {
+    let sb = Sandbox::new();
+    archive(&sb, &["--compress", "none"]);
+    let part = "out/data.part000.tar";
+    let mut tar = sb.read(part);
+    let offsets = header_offsets(&tar);
+    let at = |name: &str| offsets.iter().find(|(p, _)| p == name).unwrap().1 as usize;
+
+    // a broken header for f2 and a changed byte in the data of f5
+    tar[at("f2.bin")..at("f2.bin") + 100].fill(0xff);
+    tar[at("f5.bin") + 512 + 1000] ^= 0x01;
+    sb.write(part, &tar);
+
+    let (_, report) = salvage(&sb);
+    let damage = &report["parts"][0]["damage"];
+    assert_eq!(damage.as_array().unwrap().len(), 1, "{damage}");
+    assert_eq!(damage[0]["layer"], "tar");
+    assert_eq!(damage[0]["resumed_at"], at("f3.bin") as u64);
+
+    assert_eq!(
+        restored(&sb),
+        ["f0.bin", "f1.bin", "f3.bin", "f4.bin", "f6.bin", "f7.bin"]
+    );
+    assert_eq!(
+        lost(&report),
+        [
+            ("f2.bin".into(), "not found in readable data".into()),
+            ("f5.bin".into(), "checksum mismatch".into()),
+        ]
+    );
+}
+
+#[test]
+fn damage_inside_a_zstd_frame_keeps_what_was_decoded_before_it() {
+    let sb = Sandbox::new();
+    // text compresses, so the frame holds real compressed blocks that a
+    // flipped byte breaks
+    for i in 0..FILES {
+        let text: String = (0..4000).map(|n| format!("file {i} line {n}\n")).collect();
+        sb.write(&format!("src/f{i}.bin"), text);
+    }
+    sb.run(&["create", "src", "out"]);
+    let mut part = sb.read(PART);
+    let mid = part.len() / 2;
+    part[mid..mid + 64].fill(0xff);
+    sb.write(PART, &part);
+
+    let (_, report) = salvage(&sb);
+    let damage = &report["parts"][0]["damage"];
+    assert_eq!(damage[0]["layer"], "compression", "{damage}");
+    let back = restored(&sb);
+    assert!(back.contains(&"f0.bin".to_string()), "{back:?}");
+    assert!(!back.contains(&"f7.bin".to_string()), "{back:?}");
+    assert_eq!(back.len() + lost(&report).len(), FILES);
+}
@@ -0,0 +1,283 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+
+use common::{Sandbox, stdout};
+use serde_json::Value;
+
+const INDEX: &str = "out/index.arc.json";
+const PART: &str = "out/data.part001.tar";
+
+/// Three single-file parts, uncompressed so a flipped byte lands in data.
+fn archive(sb: &Sandbox) {
+    for name in ["a", "b", "c"] {
+        sb.write(&format!("src/{name}.txt"), name.repeat(2000));
+    }
+    sb.run(&[
+        "create",
+        "src",
+        "out",
+        "--compress",
+        "none",
+        "--split-files",
+        "1",
+    ]);
+}
+
+fn flip(sb: &Sandbox, rel: &str, at: usize) {
+    let mut data = sb.read(rel);
+    data[at] ^= 0x20;
+    sb.write(rel, &data);
+}
+
+fn journal(sb: &Sandbox) -> Vec<Value> {
+    String::from_utf8(sb.read("out/scrub.arc.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+fn report(sb: &Sandbox, extra: &[&str]) -> Value {
+    let mut args = vec!["--quiet", "scrub-report", INDEX];
+    args.extend_from_slice(extra);
+    sb.json(&args)
+}
+
+#[test]
+fn every_run_is_journaled_with_its_counts_and_damage() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    sb.run(&["verify", INDEX, "--journal"]);
+    let clean = sb.read(PART);
+    flip(&sb, PART, 1000);
+    sb.exec(&["verify", INDEX, "--journal", "--continue-on-error"]);
+    sb.exec(&[
+        "verify",
+        INDEX,
+        "--journal",
+        "--quick",
+        "--continue-on-error",
+    ]);
+    // without --continue-on-error the run stops at the damage
+    sb.fail(&["verify", INDEX, "--journal"]);
+
+    let runs = journal(&sb);
+    assert_eq!(runs.len(), 4);
+    let (pass, full, quick, stopped) = (&runs[0], &runs[1], &runs[2], &runs[3]);
+
+    assert_eq!(pass["mode"], "full");
+    assert_eq!(pass["passed"], true);
+    assert_eq!(pass["complete"], true);
+    assert_eq!(
+        (pass["ok"].as_u64(), pass["corrupt"].as_u64()),
+        (Some(3), Some(0))
+    );
+    assert_eq!(pass["damaged_parts"], serde_json::json!([]));
+
+    assert_eq!(full["passed"], false);
+    assert_eq!(full["complete"], true);
+    assert_eq!(
+        (full["ok"].as_u64(), full["corrupt"].as_u64()),
+        (Some(2), Some(1))
+    );
+    assert_eq!(full["missing"], 0);
+    assert_eq!(
+        full["damaged_parts"],
+        serde_json::json!(["data.part001.tar"])
+    );
+    let failing = full["failing"].as_array().unwrap();
+    assert_eq!(failing.len(), 1, "{failing:?}");
+    assert!(
+        failing[0].as_str().unwrap().ends_with(".txt"),
+        "{failing:?}"
+    );
+
+    // --quick counts parts
+    assert_eq!(quick["mode"], "quick");
+    assert_eq!(
+        (quick["ok"].as_u64(), quick["corrupt"].as_u64()),
+        (Some(2), Some(1))
+    );
+    assert_eq!(
+        quick["damaged_parts"],
+        serde_json::json!(["data.part001.tar"])
+    );
+
+    assert_eq!(stopped["complete"], false);
+    assert_eq!(stopped["passed"], false);
+
+    // and the part is put right again
+    sb.write(PART, &clean);
+    sb.run(&["verify", INDEX, "--journal"]);
+    assert_eq!(journal(&sb)[4]["passed"], true);
+}
+
+#[test]
+fn the_report_dates_damage_between_the_last_clean_and_first_failed_run() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    sb.fail(&["scrub-report", INDEX]);
+
+    sb.run(&["verify", INDEX, "--journal"]);
+    let clean = sb.read(PART);
+    flip(&sb, PART, 1000);
+    sb.exec(&["verify", INDEX, "--journal", "--continue-on-error"]);
+    sb.exec(&["verify", INDEX, "--journal", "--continue-on-error"]);
+
+    // runs a second apart or less share a start time; label them apart
+    let lines: Vec<String> = journal(&sb)
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut run)| {
+            run["started_at_human"] = format!("run {i}").into();
+            run.to_string()
+        })
+        .collect();
+    sb.write("out/scrub.arc.jsonl", lines.join("\n") + "\n");
+
+    let r = report(&sb, &[]);
+    assert_eq!(r["runs_total"], 3);
+    assert_eq!(r["unreadable_lines"], 0);
+    let runs = r["runs"].as_array().unwrap();
+    let newly: Vec<&Value> = runs.iter().map(|r| &r["newly_damaged"]).collect();
+    assert_eq!(
+        newly,
+        [
+            &serde_json::json!([]),
+            &serde_json::json!(["data.part001.tar"]),
+            &serde_json::json!([]),
+        ]
+    );
+    let parts = r["damaged_parts"].as_array().unwrap();
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0]["part"], "data.part001.tar");
+    assert_eq!(parts[0]["first_damaged"], "run 1");
+    assert_eq!(parts[0]["last_clean"], "run 0");
+    assert_eq!(parts[0]["damaged_now"], true);
+
+    sb.write(PART, &clean);
+    sb.run(&["verify", INDEX, "--journal"]);
+    let r = report(&sb, &["--last", "1"]);
+    assert_eq!(r["runs_total"], 4);
+    assert_eq!(r["runs"].as_array().unwrap().len(), 1);
+    assert_eq!(r["damaged_parts"][0]["damaged_now"], false);
+
+    let text = stdout(&sb.run(&["scrub-report", INDEX]));
+    assert!(text.contains("newly damaged: data.part001.tar"), "{text}");
+    assert!(text.contains("intact now"), "{text}");
+}
+
+#[test]
+fn stopped_runs_are_not_compared_with() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    flip(&sb, "out/data.part000.tar", 1000);
+    flip(&sb, "out/data.part002.tar", 1000);
+    let run = |go_on: bool| {
+        let mut args = vec!["verify", INDEX, "--journal", "--threads", "1"];
+        if go_on {
+            args.push("--continue-on-error");
+        }
+        sb.exec(&args);
+    };
+    run(true);
+    run(false);
+    run(true);
+    run(false);
+
+    let lines: Vec<String> = journal(&sb)
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut run)| {
+            run["started_at_human"] = format!("run {i}").into();
+            run.to_string()
+        })
+        .collect();
+    sb.write("out/scrub.arc.jsonl", lines.join("\n") + "\n");
+
+    let r = report(&sb, &[]);
+    let runs = r["runs"].as_array().unwrap();
+    let complete: Vec<bool> = runs
+        .iter()
+        .map(|r| r["complete"].as_bool().unwrap())
+        .collect();
+    assert_eq!(complete, [true, false, true, false]);
+    assert_eq!(
+        runs[1]["damaged_parts"],
+        serde_json::json!(["data.part000.tar"])
+    );
+    // the stopped run never reached part002; the run after it compares
+    // with the complete run before and finds nothing new
+    for run in runs {
+        if run["started_at_human"] != "run 0" {
+            assert_eq!(run["newly_damaged"], serde_json::json!([]), "{run}");
+        }
+    }
+    let parts = r["damaged_parts"].as_array().unwrap();
+    assert_eq!(parts.len(), 2);
+    for p in parts {
+        assert_eq!(p["first_damaged"], "run 0");
+        assert_eq!(p["damaged_now"], true, "{p}");
+    }
+
+    // with no complete run, a part the latest run did not find damaged
+    // may or may not be damaged now
+    let mut early: Value = serde_json::from_str(&lines[1]).unwrap();
+    early["damaged_parts"] = serde_json::json!(["data.part000.tar", "data.part002.tar"]);
+    sb.write("out/scrub.arc.jsonl", format!("{early}\n{}\n", lines[3]));
+    let r = report(&sb, &[]);
+    let now: Vec<(&Value, &Value)> = r["damaged_parts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| (&p["part"], &p["damaged_now"]))
+        .collect();
+    assert_eq!(
+        now,
+        [
+            (&"data.part000.tar".into(), &true.into()),
+            (&"data.part002.tar".into(), &Value::Null),
+        ]
+    );
+    let text = stdout(&sb.run(&["scrub-report", INDEX]));
+    assert!(text.contains("unknown"), "{text}");
+}
+
+#[test]
+fn a_cut_off_journal_line_is_skipped() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    sb.run(&["verify", INDEX, "--journal"]);
+    let mut text = fs::read_to_string(sb.path("out/scrub.arc.jsonl")).unwrap();
+    text.push_str("{\"started_at_unix\": 17");
+    sb.write("out/scrub.arc.jsonl", &text);
+    // the next run starts on a line of its own
+    sb.run(&["verify", INDEX, "--journal"]);
+
+    let r = report(&sb, &[]);
+    assert_eq!(r["runs_total"], 2);
+    assert_eq!(r["unreadable_lines"], 1);
+    let text = stdout(&sb.run(&["scrub-report", INDEX]));
+    assert!(
+        text.contains("1 unreadable journal line(s) skipped"),
+        "{text}"
+    );
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use common::{Sandbox, sorted_paths, stderr};
+use serde_json::Value;
+
+/// `diff --json` prints its banner first; the report is the last JSON object.
+fn diff_report(sb: &Sandbox, args: &[&str]) -> Value {
+    let mut full = vec!["--json", "diff"];
+    full.extend_from_slice(args);
+    let out = sb.run(&full);
+    let text = String::from_utf8(out.stdout).unwrap();
+    let start = text.find("\n{").map_or(0, |i| i + 1);
+    serde_json::from_str(&text[start..]).unwrap()
+}
+
+#[test]
+fn create_applies_every_rule() {
+    let sb = Sandbox::new();
+    sb.write("src/keep.rs", "fn main() {}");
+    sb.write("src/skip.txt", "not included");
+    sb.write("src/big.rs", vec![b'x'; 4096]);
+    sb.write("src/gen/out.rs", "generated");
+    sb.write("src/gen/.nobackup", "");
+    sb.write("src/deep/er/still.rs", "too deep");
+    sb.write("src/tmp/scratch.rs", "regex");
+
+    sb.run(&[
+        "create",
+        "src",
+        "out",
+        "--include",
+        "**/*.rs",
+        "--max-file-size",
+        "1K",
+        "--exclude-if-present",
+        ".nobackup",
+        "--max-depth",
+        "2",
+        "--exclude-regex",
+        "^tmp/",
+    ]);
+    let files: Vec<_> = sorted_paths(&sb, "out/index.arc.json")
+        .into_iter()
+        .filter(|p| p.ends_with(".rs"))
+        .collect();
+    assert_eq!(files, vec!["keep.rs"]);
+}
+
+#[test]
+fn update_keeps_files_outside_its_selection() {
+    let sb = Sandbox::new();
+    sb.write("src/old.txt", "old");
+    sb.write("src/gone.txt", "deleted later");
+    sb.write("src/huge.bin", vec![0u8; 8192]);
+    sb.run(&["create", "src", "v1", "--compress", "none"]);
+
+    std::fs::remove_file(sb.path("src/gone.txt")).unwrap();
+    sb.write("src/new.txt", "new");
+    let out = sb.run(&[
+        "update",
+        "v1/index.arc.json",
+        "src",
+        "v2",
+        "--compress",
+        "none",
+        "--max-file-size",
+        "1K",
+        "--exclude",
+        "old.txt",
+    ]);
+    assert!(
+        String::from_utf8_lossy(&out.stdout).contains("Kept outside the selection: 2"),
+        "{}",
+        stderr(&out)
+    );
+
+    assert_eq!(
+        sorted_paths(&sb, "v2/index.arc.json"),
+        vec!["huge.bin", "new.txt", "old.txt"]
+    );
+    sb.run(&["verify", "v2/index.arc.json"]);
+    sb.run(&["restore", "v2/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/huge.bin").len(), 8192);
+    assert_eq!(sb.read("dst/old.txt"), b"old");
+}
+
+#[test]
+fn diff_applies_the_configured_create_rules() {
+    let sb = Sandbox::new();
+    sb.config("exclude = [\"*.bak\"]", "");
+    sb.write("src/a.txt", "a");
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    sb.write("src/a.txt.bak", "backup copy");
+    sb.write("src/b.txt", "b");
+
+    let report = diff_report(&sb, &["out/index.arc.json", "src"]);
+    let added: Vec<_> = report["added"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|a| a["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(added, vec!["b.txt"]);
+
+    let report = diff_report(
+        &sb,
+        &["out/index.arc.json", "src", "--newer-than", "2000-01-01"],
+    );
+    assert_eq!(report["added"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn diff_does_not_call_files_outside_the_selection_removed() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "a");
+    sb.write("src/b.log", "log");
+    sb.write("src/gone.txt", "gone");
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    std::fs::remove_file(sb.path("src/gone.txt")).unwrap();
+
+    let removed = |report: &Value| -> Vec<String> {
+        report["removed"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p.as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let report = diff_report(&sb, &["out/index.arc.json", "src", "--exclude", "*.log"]);
+    assert_eq!(removed(&report), vec!["gone.txt"]);
+
+    let report = diff_report(&sb, &["out/index.arc.json", "src", "--include", "*.log"]);
+    assert_eq!(removed(&report), vec!["gone.txt"]);
+    assert_eq!(report["unchanged"], 1);
+}
@@ -0,0 +1,91 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::process::Command;
+
+use common::{Sandbox, entry};
+
+fn mkfifo(path: &std::path::Path) {
+    let st = Command::new("mkfifo").arg(path).status().unwrap();
+    assert!(st.success(), "mkfifo failed");
+}
+
+#[test]
+fn fifo_is_indexed_and_restored() {
+    let sb = Sandbox::new();
+    sb.write("src/plain.txt", "hello");
+    mkfifo(&sb.path("src/pipe"));
+
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    let idx = sb.index("out/index.arc.json");
+    let e = entry(&idx, "pipe");
+    assert_eq!(e["entry_type"], "fifo");
+    assert_eq!(e["size"], 0);
+    assert!(e["sha256"].is_null());
+
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    let meta = fs::symlink_metadata(sb.path("dst/pipe")).unwrap();
+    assert!(meta.file_type().is_fifo());
+    assert_eq!(sb.read("dst/plain.txt"), b"hello");
+}
+
+#[test]
+fn char_device_keeps_major_minor() {
+    let sb = Sandbox::new();
+    sb.write("src/plain.txt", "x");
+    let node = sb.path("src/null");
+    let made = Command::new("mknod")
+        .arg(&node)
+        .args(["c", "1", "3"])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !made {
+        eprintln!("skipping: mknod needs CAP_MKNOD");
+        return;
+    }
+
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    let idx = sb.index("out/index.arc.json");
+    let e = entry(&idx, "null");
+    assert_eq!(e["entry_type"], "chardevice");
+    assert_eq!(e["dev_major"], 1);
+    assert_eq!(e["dev_minor"], 3);
+
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    let meta = fs::symlink_metadata(sb.path("dst/null")).unwrap();
+    assert!(meta.file_type().is_char_device());
+    assert_eq!(meta.rdev(), fs::metadata(&node).unwrap().rdev());
+}
+
+#[test]
+fn sockets_are_skipped() {
+    let sb = Sandbox::new();
+    sb.write("src/plain.txt", "x");
+    let _sock = std::os::unix::net::UnixListener::bind(sb.path("src/sock")).unwrap();
+
+    sb.run(&["create", "src", "out", "--compress", "none"]);
+    let idx = sb.index("out/index.arc.json");
+    assert!(common::paths(&idx).iter().all(|p| p != "sock"));
+}
@@ -0,0 +1,273 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::process::{Output, Stdio};
+
+use common::{Sandbox, entry, stderr};
+
+/// About 4 KiB per part, so a 20 KB stream spans several.
+const SPLIT: &str = "0.000004";
+
+/// Bytes that do not compress, so the part sizes stay meaningful.
+fn noise(len: usize) -> Vec<u8> {
+    let mut x = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+fn piped(sb: &Sandbox, args: &[&str], input: &[u8]) -> Output {
+    let mut child = sb
+        .cmd()
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn parts(sb: &Sandbox, dir: &str) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(sb.path(dir))
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|n| n.starts_with("data.part"))
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn stream_rolls_over_at_the_split_size() {
+    let data = noise(20_000);
+    for algo in ["zstd", "gzip", "lz4", "none"] {
+        let sb = Sandbox::new();
+        let out = piped(
+            &sb,
+            &[
+                "create",
+                "-",
+                "out",
+                "--stdin-name",
+                "dump.bin",
+                "--compress",
+                algo,
+                "--split-gb",
+                SPLIT,
+            ],
+            &data,
+        );
+        assert!(out.status.success(), "{algo}: {}", stderr(&out));
+
+        let names = parts(&sb, "out");
+        assert!(names.len() >= 4, "{algo}: {names:?}");
+        assert!(names.iter().all(|n| !n.contains("spool")), "{names:?}");
+        // the last part also holds the manifest
+        for name in &names[..names.len() - 1] {
+            let len = fs::metadata(sb.path(&format!("out/{name}"))).unwrap().len();
+            assert!(len <= 4295 + 64, "{algo}: {name} is {len} bytes");
+        }
+
+        let idx = sb.index("out/index.arc.json");
+        let e = entry(&idx, "dump.bin");
+        assert_eq!(e["size"], 20_000);
+        assert_eq!(e["from_stdin"], true);
+        let pieces = e["pieces"].as_array().unwrap();
+        assert_eq!(pieces.len(), names.len());
+        let sum: u64 = pieces.iter().map(|p| p["size"].as_u64().unwrap()).sum();
+        assert_eq!(sum, 20_000);
+
+        sb.run(&["verify", "out/index.arc.json"]);
+        sb.run(&["restore", "out/index.arc.json", "dst"]);
+        assert_eq!(sb.read("dst/dump.bin"), data, "{algo}");
+        let cat = sb.run(&["cat", "out/index.arc.json", "dump.bin"]);
+        assert_eq!(cat.stdout, data, "{algo}");
+        sb.run(&[
+            "extract",
+            "out/index.arc.json",
+            "dump.bin",
+            "--output",
+            "one.bin",
+        ]);
+        assert_eq!(sb.read("one.bin"), data, "{algo}");
+    }
+}
+
+#[test]
+fn member_headers_carry_the_final_size_and_a_fixed_owner() {
+    let sb = Sandbox::new();
+    let data = noise(10_000);
+    let out = piped(
+        &sb,
+        &[
+            "create",
+            "-",
+            "out",
+            "--stdin-name",
+            "dump.bin",
+            "--split-gb",
+            SPLIT,
+        ],
+        &data,
+    );
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let idx = sb.index("out/index.arc.json");
+    let pieces = entry(&idx, "dump.bin")["pieces"]
+        .as_array()
+        .unwrap()
+        .clone();
+    for (i, name) in parts(&sb, "out").iter().enumerate() {
+        let raw = fs::read(sb.path(&format!("out/{name}"))).unwrap();
+        let tar = zstd::stream::decode_all(&raw[..]).unwrap();
+        let mut ar = tar::Archive::new(&tar[..]);
+        let mut sizes = vec![];
+        for m in ar.entries().unwrap() {
+            let mut m = m.unwrap();
+            let h = m.header().clone();
+            assert_eq!(h.uid().unwrap(), 0);
+            assert_eq!(h.gid().unwrap(), 0);
+            if m.path().unwrap().to_str() == Some("dump.bin") {
+                sizes.push(h.size().unwrap());
+                let mut body = vec![];
+                m.read_to_end(&mut body).unwrap();
+                assert_eq!(body.len() as u64, h.size().unwrap());
+            }
+        }
+        assert_eq!(sizes, vec![pieces[i]["size"].as_u64().unwrap()], "{name}");
+    }
+}
+
+#[test]
+fn add_rolls_over_after_the_existing_parts() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "a");
+    sb.run(&["create", "src", "out"]);
+    let data = noise(12_000);
+    let out = piped(
+        &sb,
+        &[
+            "add",
+            "out/index.arc.json",
+            "--stdin-name",
+            "late.bin",
+            "--split-gb",
+            SPLIT,
+        ],
+        &data,
+    );
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let idx = sb.index("out/index.arc.json");
+    assert_eq!(entry(&idx, "late.bin")["tar_part"], 1);
+    assert!(parts(&sb, "out").len() >= 4);
+    let hashes = idx["header"]["part_hashes"].as_array().unwrap();
+    assert_eq!(hashes.len(), parts(&sb, "out").len());
+
+    sb.run(&["verify", "out/index.arc.json"]);
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/late.bin"), data);
+    assert_eq!(sb.read("dst/a.txt"), b"a");
+}
+
+#[test]
+fn repair_joins_the_pieces_of_a_stream() {
+    let sb = Sandbox::new();
+    let data = noise(15_000);
+    let out = piped(
+        &sb,
+        &[
+            "create",
+            "-",
+            "out",
+            "--stdin-name",
+            "dump.bin",
+            "--split-gb",
+            SPLIT,
+        ],
+        &data,
+    );
+    assert!(out.status.success(), "{}", stderr(&out));
+    let before = sb.index("out/index.arc.json");
+    fs::remove_file(sb.path("out/index.arc.json")).unwrap();
+    fs::remove_file(sb.path("out/index.arc.json.b3")).unwrap();
+
+    sb.run(&["repair", "out"]);
+    let idx = sb.index("out/index.arc.json");
+    let e = entry(&idx, "dump.bin");
+    assert_eq!(e["size"], 15_000);
+    assert_eq!(e["sha256"], entry(&before, "dump.bin")["sha256"]);
+    assert_eq!(
+        e["pieces"].as_array().unwrap().len(),
+        entry(&before, "dump.bin")["pieces"]
+            .as_array()
+            .unwrap()
+            .len()
+    );
+
+    sb.run(&["verify", "out/index.arc.json"]);
+    sb.run(&["restore", "out/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/dump.bin"), data);
+}
+
+#[test]
+fn bzip2_streams_are_refused() {
+    let sb = Sandbox::new();
+    let out = piped(
+        &sb,
+        &[
+            "create",
+            "-",
+            "out",
+            "--stdin-name",
+            "dump.bin",
+            "--compress",
+            "bzip2",
+        ],
+        b"data",
+    );
+    assert!(!out.status.success());
+    assert!(stderr(&out).contains("bzip2"), "{}", stderr(&out));
+}
+
+#[test]
+fn diff_leaves_streams_out() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "a");
+    let out = piped(
+        &sb,
+        &["create", "src", "out", "--stdin-name", "db.sql"],
+        b"dump",
+    );
+    assert!(out.status.success(), "{}", stderr(&out));
+
+    let out = sb.run(&["diff", "out/index.arc.json", "src"]);
+    let text = String::from_utf8(out.stdout).unwrap();
+    assert!(!text.contains("db.sql"), "{text}");
+    assert!(text.contains("Removed: 0"), "{text}");
+}
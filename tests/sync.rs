@@ -0,0 +1,162 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+
+use common::{Sandbox, stderr, stdout};
+use serde_json::Value;
+
+fn replicas(sb: &Sandbox, index: &str) -> Vec<Value> {
+    let rel = index.replace("index.arc.json", "replicas.arc.json");
+    let v: Value = serde_json::from_slice(&sb.read(&rel)).unwrap();
+    v["replicas"].as_array().unwrap().clone()
+}
+
+fn flip_byte(sb: &Sandbox, rel: &str, at: usize) {
+    let mut data = sb.read(rel);
+    data[at] ^= 0xff;
+    sb.write(rel, data);
+}
+
+fn source(sb: &Sandbox) {
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/b.txt", "b".repeat(6_000));
+    sb.write("src/c.txt", "c".repeat(6_000));
+}
+
+#[test]
+fn sync_copies_the_archive_and_records_the_replica() {
+    let sb = Sandbox::new();
+    source(&sb);
+    sb.run(&["create", "src", "out", "--split-gb", "0.000004"]);
+    sb.run(&["sync", "out/index.arc.json", "copy"]);
+
+    for name in fs::read_dir(sb.path("out")).unwrap() {
+        let name = name.unwrap().file_name().into_string().unwrap();
+        if name.starts_with("data.part") || name.starts_with("index.arc.json") {
+            assert_eq!(
+                sb.read(&format!("copy/{name}")),
+                sb.read(&format!("out/{name}"))
+            );
+        }
+    }
+
+    let recorded = replicas(&sb, "out/index.arc.json");
+    assert_eq!(recorded.len(), 1);
+    let location = recorded[0]["location"].as_str().unwrap();
+    assert_eq!(
+        std::path::Path::new(location),
+        fs::canonicalize(sb.path("copy")).unwrap()
+    );
+    let parts = recorded[0]["parts"].as_array().unwrap();
+    assert_eq!(
+        parts.len(),
+        sb.index("out/index.arc.json")["header"]["total_parts"]
+    );
+    assert_eq!(
+        recorded[0]["index_blake3"].as_str().unwrap(),
+        blake3::hash(&sb.read("copy/index.arc.json"))
+            .to_hex()
+            .as_str()
+    );
+
+    sb.run(&["verify", "out/index.arc.json", "--replicas"]);
+    sb.run(&["restore", "copy/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/c.txt"), "c".repeat(6_000).as_bytes());
+}
+
+#[test]
+fn resync_copies_only_what_differs() {
+    let sb = Sandbox::new();
+    source(&sb);
+    sb.run(&["create", "src", "out", "--split-gb", "0.000004"]);
+    sb.run(&["sync", "out/index.arc.json", "copy"]);
+
+    // same size, different bytes: not taken for a copy
+    flip_byte(&sb, "copy/data.part001.tar.zst", 30);
+    fs::remove_file(sb.path("copy/data.part002.tar.zst")).unwrap();
+    let text = stdout(&sb.run(&["sync", "out/index.arc.json", "copy"]));
+    assert!(text.contains("copied data.part001.tar.zst"), "{text}");
+    assert!(text.contains("copied data.part002.tar.zst"), "{text}");
+    assert!(text.contains("present data.part000.tar.zst"), "{text}");
+    assert_eq!(
+        sb.read("copy/data.part001.tar.zst"),
+        sb.read("out/data.part001.tar.zst")
+    );
+    // the second sync to the same place replaces the first record
+    assert_eq!(replicas(&sb, "out/index.arc.json").len(), 1);
+}
+
+#[test]
+fn verify_replicas_catches_a_damaged_or_stale_copy() {
+    let sb = Sandbox::new();
+    source(&sb);
+    sb.run(&["create", "src", "out", "--split-gb", "0.000004"]);
+    sb.run(&["sync", "out/index.arc.json", "one"]);
+    sb.run(&["sync", "out/index.arc.json", "two"]);
+    assert_eq!(replicas(&sb, "out/index.arc.json").len(), 2);
+
+    flip_byte(&sb, "one/data.part000.tar.zst", 40);
+    let out = sb.fail(&["verify", "out/index.arc.json", "--replicas", "--quick"]);
+    assert!(
+        stderr(&out).contains("1 of 2 replica(s)"),
+        "{}",
+        stderr(&out)
+    );
+
+    // an index that is not the one synced is refused before any part is read
+    sb.run(&["sync", "out/index.arc.json", "one"]);
+    sb.run(&["create", "src", "two", "--split-gb", "1"]);
+    let out = sb.fail(&["verify", "out/index.arc.json", "--replicas"]);
+    assert!(
+        stderr(&out).contains("is not the one synced"),
+        "{}",
+        stderr(&out)
+    );
+}
+
+#[test]
+fn replica_of_an_update_is_self_contained() {
+    let sb = Sandbox::new();
+    source(&sb);
+    sb.run(&["create", "src", "v1"]);
+    sb.write("src/d.txt", "delta");
+    sb.run(&["update", "v1/index.arc.json", "src", "v2"]);
+    assert!(
+        !sb.index("v2/index.arc.json")["header"]["part_bases"]
+            .as_array()
+            .unwrap()
+            .is_empty()
+    );
+
+    sb.run(&["sync", "v2/index.arc.json", "copy"]);
+    let idx = sb.index("copy/index.arc.json");
+    assert_eq!(
+        idx["header"]["part_bases"],
+        serde_json::json!(["", "base1"])
+    );
+    assert!(sb.path("copy/base1/data.part000.tar.zst").exists());
+
+    fs::remove_dir_all(sb.path("v1")).unwrap();
+    fs::remove_dir_all(sb.path("v2")).unwrap();
+    sb.run(&["verify", "copy/index.arc.json"]);
+    sb.run(&["restore", "copy/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/a.txt"), b"alpha");
+    assert_eq!(sb.read("dst/d.txt"), b"delta");
+}
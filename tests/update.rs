@@ -0,0 +1,78 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::{Sandbox, entry};
+
+#[test]
+fn chained_updates_keep_every_earlier_base() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/sub/b.txt", "beta");
+    sb.run(&["create", "src", "v1"]);
+    sb.write("src/c.txt", "gamma");
+    sb.run(&["update", "v1/index.arc.json", "src", "v2"]);
+    sb.write("src/d.txt", "delta");
+    sb.run(&["update", "v2/index.arc.json", "src", "v3"]);
+
+    let idx = sb.index("v3/index.arc.json");
+    assert_eq!(
+        idx["header"]["part_bases"],
+        serde_json::json!(["", "../v2", "../v1"])
+    );
+    assert_eq!(entry(&idx, "d.txt")["tar_base"], serde_json::Value::Null);
+    assert_eq!(entry(&idx, "c.txt")["tar_base"], 1);
+    assert_eq!(entry(&idx, "a.txt")["tar_base"], 2);
+    assert_eq!(entry(&idx, "sub")["tar_base"], 2);
+    let bases: BTreeSet<u64> = idx["header"]["part_hashes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|h| h["base"].as_u64().unwrap_or(0))
+        .collect();
+    assert_eq!(bases, BTreeSet::from([0, 1, 2]));
+
+    sb.run(&["verify", "v3/index.arc.json"]);
+    sb.run(&["verify", "v3/index.arc.json", "--quick"]);
+    sb.run(&["restore", "v3/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/a.txt"), b"alpha");
+    assert_eq!(sb.read("dst/sub/b.txt"), b"beta");
+    assert_eq!(sb.read("dst/c.txt"), b"gamma");
+    assert_eq!(sb.read("dst/d.txt"), b"delta");
+}
+
+#[test]
+fn a_changed_file_moves_to_the_newest_base() {
+    let sb = Sandbox::new();
+    sb.write("src/a.txt", "one");
+    sb.write("src/b.txt", "same");
+    sb.run(&["create", "src", "v1"]);
+    sb.write("src/a.txt", "two!");
+    sb.run(&["update", "v1/index.arc.json", "src", "v2", "--checksum"]);
+    sb.write("src/a.txt", "three");
+    sb.run(&["update", "v2/index.arc.json", "src", "v3", "--checksum"]);
+
+    let idx = sb.index("v3/index.arc.json");
+    assert_eq!(entry(&idx, "a.txt")["tar_base"], serde_json::Value::Null);
+    assert_eq!(entry(&idx, "b.txt")["tar_base"], 2);
+    sb.run(&["restore", "v3/index.arc.json", "dst"]);
+    assert_eq!(sb.read("dst/a.txt"), b"three");
+    assert_eq!(sb.read("dst/b.txt"), b"same");
+}
@@ -0,0 +1,378 @@
+/*
+ * Copyright 2026 Ankit Chaubey <ankitchaubey.dev@gmail.com>
+ * github.com/ankit-chaubey
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod common;
+
+use std::fs;
+
+use common::Sandbox;
+use serde_json::{Value, json};
+
+/// `verify --json --continue-on-error` on `index`.
+fn report(sb: &Sandbox, index: &str) -> Value {
+    sb.json(&["--quiet", "verify", index, "--continue-on-error"])
+}
+
+fn findings(report: &Value) -> Vec<Value> {
+    report["findings"].as_array().unwrap().clone()
+}
+
+fn kinds(report: &Value) -> Vec<String> {
+    let mut k: Vec<String> = findings(report)
+        .iter()
+        .map(|f| f["type"].as_str().unwrap().to_string())
+        .collect();
+    k.sort();
+    k.dedup();
+    k
+}
+
+fn entry_mut<'a>(idx: &'a mut Value, path: &str) -> &'a mut Value {
+    idx["entries"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .find(|e| e["path"] == path)
+        .unwrap()
+}
+
+fn archive(sb: &Sandbox) {
+    sb.write("src/a.txt", "alpha");
+    sb.write("src/b.txt", "beta");
+    sb.write("src/dup.txt", "alpha");
+    sb.write("src/sub/c.txt", "gamma");
+    sb.run(&["create", "src", "out", "--dedup"]);
+}
+
+#[test]
+fn a_sound_archive_has_no_findings() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    let r = report(&sb, "out/index.arc.json");
+    assert_eq!(r["status"], "PASS");
+    assert_eq!(findings(&r), Vec::<Value>::new());
+    assert_eq!(r["all_parts_present"], true);
+}
+
+#[test]
+fn header_totals_are_checked_against_the_entries() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    let mut idx = sb.index("out/index.arc.json");
+    idx["header"]["total_files"] = json!(99);
+    idx["header"]["total_size"] = json!(1);
+    sb.write_index("out/index.arc.json", &idx);
+
+    let r = report(&sb, "out/index.arc.json");
+    assert_eq!(r["status"], "FAIL");
+    let fields: Vec<_> = findings(&r)
+        .iter()
+        .filter(|f| f["type"] == "header_mismatch")
+        .map(|f| (f["field"].clone(), f["recorded"].clone()))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            (json!("total_files"), json!(99)),
+            (json!("total_size"), json!(1))
+        ]
+    );
+}
+
+#[test]
+fn dedup_references_must_point_at_a_stored_file() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    let mut idx = sb.index("out/index.arc.json");
+    assert_eq!(common::entry(&idx, "dup.txt")["dedup_of"], "a.txt");
+    entry_mut(&mut idx, "dup.txt")["dedup_of"] = json!("gone.txt");
+    // pointing a file at a directory
+    let b = entry_mut(&mut idx, "b.txt");
+    b["dedup_of"] = json!("sub");
+    sb.write_index("out/index.arc.json", &idx);
+
+    let r = report(&sb, "out/index.arc.json");
+    let by_type = |t: &str| -> Vec<Value> {
+        findings(&r)
+            .into_iter()
+            .filter(|f| f["type"] == t)
+            .collect()
+    };
+    let missing = by_type("dedup_target_missing");
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0]["path"], "dup.txt");
+    assert_eq!(missing[0]["target"], "gone.txt");
+    let invalid = by_type("dedup_target_invalid");
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0]["path"], "b.txt");
+    assert_eq!(invalid[0]["reason"], "target is not a regular file");
+}
+
+#[test]
+fn members_and_entries_are_matched_both_ways() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    let mut idx = sb.index("out/index.arc.json");
+    let entries = idx["entries"].as_array_mut().unwrap();
+    let mut ghost = entries
+        .iter()
+        .find(|e| e["path"] == "b.txt")
+        .unwrap()
+        .clone();
+    ghost["path"] = json!("ghost.txt");
+    entries.retain(|e| e["path"] != "b.txt");
+    entries.push(ghost);
+    sb.write_index("out/index.arc.json", &idx);
+
+    let r = report(&sb, "out/index.arc.json");
+    let found: Vec<_> = findings(&r)
+        .iter()
+        .filter(|f| f["type"] == "missing_member" || f["type"] == "unindexed_member")
+        .map(|f| (f["type"].clone(), f["path"].clone(), f["part"].clone()))
+        .collect();
+    assert!(found.contains(&(
+        json!("missing_member"),
+        json!("ghost.txt"),
+        json!("data.part000.tar.zst")
+    )));
+    assert!(found.contains(&(
+        json!("unindexed_member"),
+        json!("b.txt"),
+        json!("data.part000.tar.zst")
+    )));
+}
+
+#[test]
+fn bases_and_part_files_are_resolved_and_accounted_for() {
+    let sb = Sandbox::new();
+    archive(&sb);
+    sb.write("src/new.txt", "new");
+    sb.run(&["update", "out/index.arc.json", "src", "v2"]);
+
+    // a part left over from an earlier, larger archive
+    fs::copy(
+        sb.path("v2/data.part000.tar.zst"),
+        sb.path("v2/data.part001.tar.zst"),
+    )
+    .unwrap();
+    // the old archive's part, reached through part_bases, is gone
+    fs::remove_file(sb.path("out/data.part000.tar.zst")).unwrap();
+    let mut idx = sb.index("v2/index.arc.json");
+    entry_mut(&mut idx, "new.txt")["tar_base"] = json!(7);
+    sb.write_index("v2/index.arc.json", &idx);
+
+    let r = report(&sb, "v2/index.arc.json");
+    assert_eq!(
+        kinds(&r),
+        // new.txt's member is still in part 0, now unclaimed
+        vec![
+            "missing_part",
+            "orphan_part",
+            "unindexed_member",
+            "unknown_base"
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>()
+    );
+    let parts: Vec<_> = findings(&r)
+        .iter()
+        .filter(|f| f["part"].is_string())
+        .map(|f| (f["type"].clone(), f["part"].clone()))
+        .collect();
+    assert!(parts.contains(&(json!("missing_part"), json!("../out/data.part000.tar.zst"))));
+    assert!(parts.contains(&(json!("orphan_part"), json!("data.part001.tar.zst"))));
+    assert_eq!(r["all_parts_present"], false);
+}
+
+/// Files of about one part each, with the recorded checksums of three of
+/// them, in different parts, made wrong. The first damaged part and the
+/// last few are large, so workers finish around the failure.
+fn damaged_archive(sb: &Sandbox) {
+    for i in 0..24 {
+        let len = if i == 3 || i >= 20 { 3 << 20 } else { 3000 };
+        sb.write(&format!("src/f{i:02}.bin"), vec![i as u8; len]);
+    }
+    sb.run(&["create", "src", "out", "--split-gb", "0.000004"]);
+    let mut idx = sb.index("out/index.arc.json");
+    for path in ["f03.bin", "f07.bin", "f10.bin"] {
+        entry_mut(&mut idx, path)["sha256"] = json!("00".repeat(32));
+    }
+    // the root would give the edit away before any part is read
+    idx["header"]["merkle_root"] = Value::Null;
+    sb.write_index("out/index.arc.json", &idx);
+}
+
+#[test]
+fn parallel_verify_reports_the_same_at_any_thread_count() {
+    let sb = Sandbox::new();
+    damaged_archive(&sb);
+    assert!(sb.index("out/index.arc.json")["header"]["total_parts"].as_u64() >= Some(20));
+
+    // stopping at the first problem: always f03's part, and nothing after it
+    let first = |threads: &str| {
+        let out = sb.fail(&["verify", "out/index.arc.json", "--threads", threads]);
+        (common::stdout(&out), common::stderr(&out))
+    };
+    let (text, err) = first("1");
+    assert!(text.contains("f03.bin"), "{text}");
+    assert!(!text.contains("f07.bin"), "{text}");
+    assert!(err.contains("f03.bin"), "{err}");
+    for _ in 0..5 {
+        assert_eq!(first("8"), (text.clone(), err.clone()));
+    }
+
+    // carrying on: every finding, in part order
+    let all = |threads: &str| {
+        findings(&sb.json(&[
+            "--quiet",
+            "verify",
+            "out/index.arc.json",
+            "--continue-on-error",
+            "--threads",
+            threads,
+        ]))
+    };
+    let serial = all("1");
+    let paths: Vec<_> = serial.iter().map(|f| f["path"].clone()).collect();
+    assert_eq!(
+        paths,
+        vec![json!("f03.bin"), json!("f07.bin"), json!("f10.bin")]
+    );
+    for _ in 0..5 {
+        assert_eq!(all("8"), serial);
+    }
+}
+
+/// `verify --against` findings, as (type, path, field or detail).
+fn against(sb: &Sandbox, extra: &[&str]) -> (Value, Vec<(String, String, String)>) {
+    let mut args = vec![
+        "--quiet",
+        "verify",
+        "out/index.arc.json",
+        "--continue-on-error",
+        "--against",
+        "src",
+    ];
+    args.extend(extra);
+    let r = sb.json(&args);
+    let mut found: Vec<_> = findings(&r)
+        .iter()
+        .map(|f| {
+            let what = f
+                .get("field")
+                .or(f.get("detail"))
+                .or(f.get("source"))
+                .and_then(Value::as_str)
+                .unwrap_or("");
+            (
+                f["type"].as_str().unwrap().to_string(),
+                f["path"].as_str().unwrap().to_string(),
+                what.to_string(),
+            )
+        })
+        .collect();
+    found.sort();
+    (r, found)
+}
+
+fn set_mtime(path: &std::path::Path, t: std::time::SystemTime) {
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(t)
+        .unwrap();
+}
+
+/// Replace the content of `rel` but keep its mtime, so only the bytes tell.
+fn rewrite_in_place(sb: &Sandbox, rel: &str, content: &str) {
+    let mtime = fs::metadata(sb.path(rel)).unwrap().modified().unwrap();
+    sb.write(rel, content);
+    set_mtime(&sb.path(rel), mtime);
+}
+
+#[cfg(unix)]
+#[test]
+fn against_reports_each_difference_from_the_source() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let sb = Sandbox::new();
+    for name in [
+        "same", "edited", "shorter", "gone", "chmod", "touched", "retyped",
+    ] {
+        sb.write(&format!("src/{name}.txt"), format!("{name} content"));
+    }
+    sb.write("src/alias.txt", "same content");
+    symlink("same.txt", sb.path("src/link")).unwrap();
+    sb.run(&["create", "src", "out", "--dedup"]);
+
+    let (r, found) = against(&sb, &[]);
+    assert_eq!(r["status"], "PASS", "{found:?}");
+    assert_eq!(r["source_matched"], 9);
+
+    rewrite_in_place(&sb, "src/edited.txt", "EDITED content");
+    rewrite_in_place(&sb, "src/shorter.txt", "short");
+    // an alias is compared with the source as well
+    rewrite_in_place(&sb, "src/alias.txt", "SAME content");
+    fs::remove_file(sb.path("src/gone.txt")).unwrap();
+    fs::set_permissions(sb.path("src/chmod.txt"), fs::Permissions::from_mode(0o600)).unwrap();
+    set_mtime(
+        &sb.path("src/touched.txt"),
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000),
+    );
+    fs::remove_file(sb.path("src/retyped.txt")).unwrap();
+    fs::create_dir(sb.path("src/retyped.txt")).unwrap();
+    fs::remove_file(sb.path("src/link")).unwrap();
+    symlink("edited.txt", sb.path("src/link")).unwrap();
+
+    let t = |k: &str, p: &str, w: &str| (k.to_string(), p.to_string(), w.to_string());
+    let (r, mut found) = against(&sb, &[]);
+    assert_eq!(r["status"], "FAIL");
+    // the new link's mtime is the clock's; it differs once a second ticks
+    found.retain(|f| *f != t("source_metadata", "link", "mtime"));
+    assert_eq!(
+        found,
+        vec![
+            t("source_content", "alias.txt", "first difference at byte 0"),
+            t("source_content", "edited.txt", "first difference at byte 0"),
+            t("source_content", "shorter.txt", "source ends at byte 5"),
+            t("source_metadata", "chmod.txt", "mode"),
+            t("source_metadata", "link", "target"),
+            t("source_metadata", "touched.txt", "mtime"),
+            t("source_missing", "gone.txt", ""),
+            t("source_type", "retyped.txt", "dir"),
+        ]
+    );
+    assert_eq!(r["source_matched"], 1);
+
+    // by hash, the same files differ, described by digest
+    let (_, hashed) = against(&sb, &["--by-hash"]);
+    let content: Vec<_> = hashed
+        .iter()
+        .filter(|f| f.0 == "source_content")
+        .map(|f| (f.1.as_str(), f.2.contains("in archive")))
+        .collect();
+    assert_eq!(
+        content,
+        vec![
+            ("alias.txt", true),
+            ("edited.txt", true),
+            ("shorter.txt", true)
+        ]
+    );
+}